@@ -1,9 +1,10 @@
 //! An example of decoding multipart form requests
 use futures::prelude::*;
-use gotham::handler::{HandlerFuture, IntoHandlerError};
-use gotham::helpers::http::response::create_response;
+use gotham::handler::{HandlerError, HandlerFuture};
+use gotham::helpers::http::request::body::{read_body, BodyReadConfig};
+use gotham::helpers::http::response::{create_empty_response, create_response};
 use gotham::hyper::header::CONTENT_TYPE;
-use gotham::hyper::{body, Body, HeaderMap, StatusCode};
+use gotham::hyper::{Body, HeaderMap, StatusCode};
 use gotham::router::builder::{build_simple_router, DefineSingleRoute, DrawRoutes};
 use gotham::router::Router;
 use gotham::state::{FromState, State};
@@ -12,8 +13,69 @@ use std::io::Cursor;
 use std::io::Read;
 use std::pin::Pin;
 
-/// Extracts the elements of the POST request and responds with the form keys and values
-fn form_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
+/// Limits applied to each form submitted to the handler.
+#[derive(Clone, Copy, Debug)]
+struct FormLimits {
+    /// The maximum size of the whole request body, in bytes.
+    max_body_size: usize,
+    /// The maximum number of parts accepted in a single form.
+    max_parts: usize,
+    /// The maximum size of a single part of a form, in bytes.
+    max_part_size: u64,
+}
+
+impl Default for FormLimits {
+    fn default() -> FormLimits {
+        FormLimits {
+            max_body_size: 1024 * 1024,
+            max_parts: 16,
+            max_part_size: 64 * 1024,
+        }
+    }
+}
+
+/// Reads the data of each part of the form, stopping with `413 Payload Too Large` as soon as
+/// either the number of parts or the size of a part exceeds its limit. Each part is only read up
+/// to the size limit, so an oversized part is never held in memory in full.
+fn read_parts<R: Read>(
+    mut multipart: Multipart<R>,
+    limits: FormLimits,
+) -> Result<Vec<Vec<u8>>, StatusCode> {
+    let mut parts = Vec::new();
+
+    loop {
+        match multipart.read_entry() {
+            Ok(Some(mut field)) => {
+                if parts.len() == limits.max_parts {
+                    return Err(StatusCode::PAYLOAD_TOO_LARGE);
+                }
+
+                let mut data = Vec::new();
+                field
+                    .data
+                    .by_ref()
+                    .take(limits.max_part_size + 1)
+                    .read_to_end(&mut data)
+                    .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+                if data.len() as u64 > limits.max_part_size {
+                    return Err(StatusCode::PAYLOAD_TOO_LARGE);
+                }
+
+                parts.push(data);
+            }
+            Ok(None) => return Ok(parts),
+            Err(_) => return Err(StatusCode::BAD_REQUEST),
+        }
+    }
+}
+
+/// Extracts the elements of the POST request and responds with the value of the first form field.
+///
+/// The body is read with a `BodyReadConfig` limiting its total size, so reading stops with
+/// `413 Payload Too Large` as soon as the limit is exceeded, rather than once the whole body has
+/// been buffered.
+fn form_handler(mut state: State, limits: FormLimits) -> Pin<Box<HandlerFuture>> {
     const BOUNDARY: &str = "boundary=";
     let header_map = HeaderMap::take_from(&mut state);
     let boundary = header_map
@@ -25,53 +87,45 @@ fn form_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
         })
         .unwrap();
 
-    body::to_bytes(Body::take_from(&mut state))
-        .then(|full_body| match full_body {
-            Ok(valid_body) => {
-                let mut m = Multipart::with_body(Cursor::new(valid_body), boundary);
-                match m.read_entry() {
-                    Ok(Some(mut field)) => {
-                        let mut data = Vec::new();
-                        field.data.read_to_end(&mut data).expect("can't read");
-                        let res_result = String::from_utf8(data);
-                        let res_body;
-                        match res_result {
-                            Ok(r) => res_body = r.to_string(),
-                            Err(e) => res_body = format!("{:?}", e),
-                        }
-                        let res =
-                            create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, res_body);
-                        future::ok((state, res))
-                    }
-                    Ok(None) => {
-                        let res = create_response(
-                            &state,
-                            StatusCode::OK,
-                            mime::TEXT_PLAIN,
-                            "can't read".to_string(),
-                        );
-                        future::ok((state, res))
-                    }
-                    Err(e) => {
-                        let res = create_response(
-                            &state,
-                            StatusCode::OK,
-                            mime::TEXT_PLAIN,
-                            format!("{:?}", e),
-                        );
-                        future::ok((state, res))
-                    }
-                }
+    let body = Body::take_from(&mut state);
+
+    async move {
+        let config = BodyReadConfig::default().with_max_size(limits.max_body_size);
+        let full_body = match read_body(body, &config).await {
+            Ok(buffered) => buffered.into_bytes().await,
+            Err(e) => Err(e),
+        };
+
+        let valid_body = match full_body {
+            Ok(valid_body) => valid_body,
+            Err(e) => return Err((state, HandlerError::from(e))),
+        };
+
+        let m = Multipart::with_body(Cursor::new(valid_body), boundary);
+        let res = match read_parts(m, limits) {
+            Ok(parts) => {
+                let res_body = match parts.into_iter().next() {
+                    Some(data) => match String::from_utf8(data) {
+                        Ok(r) => r,
+                        Err(e) => format!("{:?}", e),
+                    },
+                    None => "can't read".to_string(),
+                };
+                create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, res_body)
             }
-            Err(e) => future::err((state, e.into_handler_error())),
-        })
-        .boxed()
+            Err(status) => create_empty_response(&state, status),
+        };
+        Ok((state, res))
+    }
+    .boxed()
 }
 
-/// Create a `Router`
-fn router() -> Router {
+/// Create a `Router`, applying the provided limits to each form
+fn router(limits: FormLimits) -> Router {
     build_simple_router(|route| {
-        route.post("/").to(form_handler);
+        route
+            .post("/")
+            .to(move |state: State| form_handler(state, limits));
     })
 }
 
@@ -79,27 +133,33 @@ fn router() -> Router {
 pub fn main() {
     let addr = "127.0.0.1:7878";
     println!("Listening for requests at http://{}", addr);
-    gotham::start(addr, router())
+    gotham::start(addr, router(FormLimits::default()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use gotham::hyper::header::HeaderValue;
-    use gotham::test::TestServer;
+    use gotham::test::{TestResponse, TestServer};
 
-    #[test]
-    fn form_request() {
+    fn post_form(parts: &[(&str, String)]) -> TestResponse {
+        post_form_with_limits(parts, FormLimits::default())
+    }
+
+    fn post_form_with_limits(parts: &[(&str, String)], limits: FormLimits) -> TestResponse {
         let boundary = "--abcdef1234--";
-        let body = format!(
-            "--{0}\r\n\
-             content-disposition: form-data; name=\"foo\"\r\n\r\n\
-             bar\r\n\
-             --{0}--\r\n",
-            boundary
-        );
+        let mut body = String::new();
+        for (name, value) in parts {
+            body.push_str(&format!(
+                "--{0}\r\n\
+                 content-disposition: form-data; name=\"{1}\"\r\n\r\n\
+                 {2}\r\n",
+                boundary, name, value
+            ));
+        }
+        body.push_str(&format!("--{0}--\r\n", boundary));
 
-        let test_server = TestServer::new(router()).unwrap();
+        let test_server = TestServer::new(router(limits)).unwrap();
         let client = test_server.client();
         let mut request = client.post("http://localhost", body, mime::MULTIPART_FORM_DATA);
 
@@ -108,10 +168,53 @@ mod tests {
             CONTENT_TYPE,
             HeaderValue::from_str(content_type_string.as_str()).unwrap(),
         );
-        let response = request.perform().unwrap();
+        request.perform().unwrap()
+    }
+
+    #[test]
+    fn form_request() {
+        let response = post_form(&[("foo", "bar".to_string())]);
         assert_eq!(response.status(), StatusCode::OK);
         let body = response.read_body().unwrap();
         let r = String::from_utf8(body).unwrap();
         assert_eq!(r, "bar");
     }
+
+    #[test]
+    fn too_many_parts() {
+        let max_parts = FormLimits::default().max_parts;
+        let parts: Vec<(&str, String)> = (0..=max_parts).map(|i| ("foo", i.to_string())).collect();
+        let response = post_form(&parts);
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let response = post_form(&parts[..max_parts]);
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn part_too_large() {
+        let max_part_size = FormLimits::default().max_part_size as usize;
+        let value = "a".repeat(max_part_size + 1);
+        let response = post_form(&[("foo", value)]);
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let value = "a".repeat(max_part_size);
+        let response = post_form(&[("foo", value)]);
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn body_too_large() {
+        let limits = FormLimits {
+            max_body_size: 4 * 1024,
+            ..FormLimits::default()
+        };
+
+        let parts: Vec<(&str, String)> = (0..4).map(|_| ("foo", "a".repeat(1024))).collect();
+        let response = post_form_with_limits(&parts, limits);
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let response = post_form_with_limits(&parts[..3], limits);
+        assert_eq!(response.status(), StatusCode::OK);
+    }
 }
@@ -0,0 +1,257 @@
+//! Defines wrappers which are applied to accepted connections before they are served.
+
+use std::io;
+use std::mem::MaybeUninit;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::prelude::*;
+use log::debug;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::{delay_for, Delay};
+
+/// Read and write timeouts applied to each accepted connection.
+///
+/// A timeout is measured from the point at which the connection stops making progress, so a
+/// client which trickles bytes slowly is allowed to continue, while one which stalls entirely
+/// during header or body transfer has its connection closed. The read timeout also applies to
+/// idle keep-alive connections waiting for their next request.
+///
+/// By default, no timeouts are applied.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use std::time::Duration;
+/// # use gotham::connection::ConnectionTimeouts;
+/// # fn main() {
+/// let timeouts = ConnectionTimeouts::default()
+///     .with_read_timeout(Duration::from_secs(30))
+///     .with_write_timeout(Duration::from_secs(30));
+/// # drop(timeouts);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConnectionTimeouts {
+    read: Option<Duration>,
+    write: Option<Duration>,
+}
+
+impl ConnectionTimeouts {
+    /// Closes the connection when a read makes no progress for the given duration.
+    pub fn with_read_timeout(self, timeout: Duration) -> ConnectionTimeouts {
+        ConnectionTimeouts {
+            read: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Closes the connection when a write or flush makes no progress for the given duration.
+    pub fn with_write_timeout(self, timeout: Duration) -> ConnectionTimeouts {
+        ConnectionTimeouts {
+            write: Some(timeout),
+            ..self
+        }
+    }
+}
+
+/// Wraps a connection, failing reads and writes with `io::ErrorKind::TimedOut` once they have
+/// made no progress for longer than the configured `ConnectionTimeouts`.
+///
+/// When the error is returned to Hyper the connection is dropped. This is applied to every
+/// connection by `ServerConfig::with_timeouts`, and can also be used with `gotham::bind_server` as
+/// (part of) the `wrap` function.
+pub struct TimeoutStream<S> {
+    inner: S,
+    timeouts: ConnectionTimeouts,
+    read_deadline: Option<Delay>,
+    write_deadline: Option<Delay>,
+}
+
+impl<S> TimeoutStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Wraps the `inner` connection, applying the provided timeouts.
+    pub fn new(inner: S, timeouts: ConnectionTimeouts) -> Self {
+        TimeoutStream {
+            inner,
+            timeouts,
+            read_deadline: None,
+            write_deadline: None,
+        }
+    }
+
+    /// Returns a reference to the wrapped connection.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+}
+
+/// Polls the deadline for a stalled operation, starting it if this is the first time the
+/// operation has stalled since it last made progress.
+fn poll_deadline<T>(
+    deadline: &mut Option<Delay>,
+    timeout: Option<Duration>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<T>> {
+    if let Some(timeout) = timeout {
+        let delay = deadline.get_or_insert_with(|| delay_for(timeout));
+
+        if delay.poll_unpin(cx).is_ready() {
+            *deadline = None;
+            debug!(" connection stalled for {:?}, closing", timeout);
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "connection timed out",
+            )));
+        }
+    }
+
+    Poll::Pending
+}
+
+impl<S> AsyncRead for TimeoutStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [MaybeUninit<u8>]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                this.read_deadline = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending => poll_deadline(&mut this.read_deadline, this.timeouts.read, cx),
+        }
+    }
+}
+
+impl<S> AsyncWrite for TimeoutStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.inner).poll_write(cx, buf) {
+            Poll::Ready(result) => {
+                this.write_deadline = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending => poll_deadline(&mut this.write_deadline, this.timeouts.write, cx),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.inner).poll_flush(cx) {
+            Poll::Ready(result) => {
+                this.write_deadline = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending => poll_deadline(&mut this.write_deadline, this.timeouts.write, cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::runtime::Runtime;
+
+    /// A connection whose peer has stopped reading and writing entirely.
+    struct StalledConnection;
+
+    impl AsyncRead for StalledConnection {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Pending
+        }
+    }
+
+    impl AsyncWrite for StalledConnection {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Pending
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Pending
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn stalled_write_times_out() {
+        let mut rt = Runtime::new().unwrap();
+        let timeouts = ConnectionTimeouts::default().with_write_timeout(Duration::from_millis(50));
+        let mut stream = TimeoutStream::new(StalledConnection, timeouts);
+
+        let start = Instant::now();
+        let err = rt
+            .block_on(async { stream.write_all(b"HTTP/1.1 200 OK\r\n").await })
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn stalled_read_times_out() {
+        let mut rt = Runtime::new().unwrap();
+        let timeouts = ConnectionTimeouts::default().with_read_timeout(Duration::from_millis(50));
+        let mut stream = TimeoutStream::new(StalledConnection, timeouts);
+
+        let mut buf = [0; 16];
+        let err = rt
+            .block_on(async { stream.read(&mut buf).await })
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn progressing_connection_is_unaffected() {
+        let mut rt = Runtime::new().unwrap();
+        let timeouts = ConnectionTimeouts::default()
+            .with_read_timeout(Duration::from_millis(50))
+            .with_write_timeout(Duration::from_millis(50));
+        let mut stream = TimeoutStream::new(io::Cursor::new(Vec::new()), timeouts);
+
+        rt.block_on(async { stream.write_all(b"hello").await })
+            .unwrap();
+        assert_eq!(stream.get_ref().get_ref(), b"hello");
+    }
+}
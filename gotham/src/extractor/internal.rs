@@ -1153,4 +1153,37 @@ mod tests {
 
         assert_eq!(p.wrapped_int_val, IntWrapper(100));
     }
+
+    #[derive(Deserialize)]
+    struct WithDefaults {
+        #[serde(default = "first_page")]
+        page: u32,
+        #[serde(default)]
+        per_page: Option<u32>,
+    }
+
+    fn first_page() -> u32 {
+        1
+    }
+
+    #[test]
+    fn query_defaults_fill_missing_values() {
+        let qsm = QueryStringMapping::new();
+
+        let p = from_query_string_mapping::<WithDefaults>(&qsm).unwrap();
+
+        assert_eq!(p.page, 1);
+        assert_eq!(p.per_page, None);
+    }
+
+    #[test]
+    fn query_values_override_defaults() {
+        let mut qsm = QueryStringMapping::new();
+        qsm.insert("page".to_owned(), vec![FormUrlDecoded::new("5").unwrap()]);
+
+        let p = from_query_string_mapping::<WithDefaults>(&qsm).unwrap();
+
+        assert_eq!(p.page, 5);
+        assert_eq!(p.per_page, None);
+    }
 }
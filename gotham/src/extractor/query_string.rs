@@ -83,6 +83,81 @@ use crate::state::{State, StateData};
 /// #   let body = response.read_utf8_body().unwrap();
 /// #   assert_eq!(body, "x = 15, y = B");
 /// # }
+/// ```
+///
+/// # Default values
+///
+/// Query parameters which are missing from the request can be given a default value with Serde's
+/// `#[serde(default)]` or `#[serde(default = "path")]` attributes, rather than failing the
+/// extraction. Parameters provided in the request override the default.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// # extern crate serde;
+/// # #[macro_use]
+/// # extern crate serde_derive;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::state::{FromState, State};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::router::Router;
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// #
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// struct Pagination {
+///     #[serde(default = "first_page")]
+///     page: u32,
+///     #[serde(default)]
+///     sort: Option<String>,
+/// }
+///
+/// fn first_page() -> u32 {
+///     1
+/// }
+///
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let page = Pagination::borrow_from(&state).page;
+///     let response = create_response(
+///         &state,
+///         StatusCode::OK,
+///         mime::TEXT_PLAIN,
+///         format!("page {}", page),
+///     );
+///
+///     (state, response)
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route
+///             .get("/items")
+///             .with_query_string_extractor::<Pagination>()
+///             .to(handler);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://example.com/items")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "page 1");
+/// #
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://example.com/items?page=5")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "page 5");
+/// # }
+/// ```
 pub trait QueryStringExtractor<B>:
     for<'de> Deserialize<'de> + StaticResponseExtender<ResBody = B> + StateData
 where
@@ -12,7 +12,7 @@ use futures::prelude::*;
 use futures::ready;
 use futures::task::Poll;
 use http;
-use httpdate::parse_http_date;
+use httpdate::{fmt_http_date, parse_http_date};
 use hyper::header::*;
 use hyper::{Body, Response, StatusCode};
 use log::debug;
@@ -20,7 +20,7 @@ use mime::{self, Mime};
 use mime_guess::from_path;
 use serde_derive::Deserialize;
 use tokio::fs::File;
-use tokio::io::AsyncRead;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
 
 use self::accepted_encoding::accepted_encodings;
 use crate::handler::{Handler, HandlerFuture, IntoHandlerError, NewHandler};
@@ -30,11 +30,11 @@ use crate::state::{FromState, State, StateData};
 use std::cmp;
 use std::convert::From;
 use std::fs::Metadata;
-use std::io;
+use std::io::{self, SeekFrom};
 use std::iter::FromIterator;
 use std::path::{Component, Path, PathBuf};
 use std::pin::Pin;
-use std::time::UNIX_EPOCH;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Represents a handler for any files under a directory.
 #[derive(Clone)]
@@ -67,6 +67,7 @@ pub struct FileHandler {
 ///     .with_cache_control("public")
 ///     .with_gzip(false)
 ///     .with_brotli(false)
+///     .with_content_sniffing(false)
 ///     .build();
 ///
 /// assert_eq!(default_options, from_builder);
@@ -77,6 +78,7 @@ pub struct FileOptions {
     cache_control: String,
     gzip: bool,
     brotli: bool,
+    content_sniffing: bool,
 }
 
 impl FileOptions {
@@ -90,6 +92,7 @@ impl FileOptions {
             cache_control: "public".to_string(),
             gzip: false,
             brotli: false,
+            content_sniffing: false,
         }
     }
 
@@ -113,6 +116,14 @@ impl FileOptions {
         self
     }
 
+    /// If `true`, files without a recognised extension have their content type detected from the
+    /// first bytes of the file, for common types such as PNG, JPEG, GIF, PDF and HTML. Files which
+    /// are not detected are served as `application/octet-stream` (defaults to false).
+    pub fn with_content_sniffing(&mut self, content_sniffing: bool) -> &mut Self {
+        self.content_sniffing = content_sniffing;
+        self
+    }
+
     /// Clones `self` to return an owned value for passing to a handler.
     pub fn build(&mut self) -> Self {
         self.clone()
@@ -204,12 +215,15 @@ impl Handler for FileHandler {
 
 // Creates the `HandlerFuture` response based on the given `FileOptions`.
 fn create_file_response(options: FileOptions, state: State) -> Pin<Box<HandlerFuture>> {
-    let mime_type = mime_for_path(&options.path);
     let headers = HeaderMap::borrow_from(&state).clone();
 
     let (path, encoding) = check_compressed_options(&options, &headers);
 
-    let response_future = File::open(path).and_then(|file| async move {
+    // compressed files can't be sniffed, as their content is not of the served type
+    let guessed_type = from_path(&options.path).first();
+    let sniff = options.content_sniffing && guessed_type.is_none() && encoding.is_none();
+
+    let response_future = File::open(path).and_then(move |mut file| async move {
         let meta = file.metadata().await?;
         if not_modified(&meta, &headers) {
             return Ok(http::Response::builder()
@@ -217,6 +231,16 @@ fn create_file_response(options: FileOptions, state: State) -> Pin<Box<HandlerFu
                 .body(Body::empty())
                 .unwrap());
         }
+
+        let sniffed_type = if sniff {
+            sniff_file(&mut file).await?
+        } else {
+            None
+        };
+        let mime_type = guessed_type
+            .or(sniffed_type)
+            .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+
         let len = meta.len();
         let buf_size = optimal_buf_size(&meta);
 
@@ -231,6 +255,9 @@ fn create_file_response(options: FileOptions, state: State) -> Pin<Box<HandlerFu
         if let Some(etag) = entity_tag(&meta) {
             response = response.header(ETAG, etag);
         }
+        if let Some(last_modified) = last_modified(&meta) {
+            response = response.header(LAST_MODIFIED, fmt_http_date(last_modified));
+        }
         if let Some(content_encoding) = encoding {
             response = response.header(CONTENT_ENCODING, content_encoding);
         }
@@ -298,8 +325,57 @@ fn get_extension(encoding: &str, options: &FileOptions) -> Option<String> {
     None
 }
 
-fn mime_for_path(path: &Path) -> Mime {
-    from_path(path).first_or_octet_stream()
+// Reads the leading bytes of a file to detect its content type, leaving the file positioned at
+// the start for streaming.
+async fn sniff_file(file: &mut File) -> io::Result<Option<Mime>> {
+    let mut buf = [0u8; SNIFF_LEN];
+    let mut len = 0;
+
+    while len < buf.len() {
+        match file.read(&mut buf[len..]).await? {
+            0 => break,
+            n => len += n,
+        }
+    }
+
+    file.seek(SeekFrom::Start(0)).await?;
+    Ok(sniff_content_type(&buf[..len]))
+}
+
+// The number of leading bytes considered when sniffing the content type of a file.
+const SNIFF_LEN: usize = 512;
+
+// Detects the content type of common file formats from their leading bytes.
+fn sniff_content_type(bytes: &[u8]) -> Option<Mime> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+    ];
+
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(sig, _)| bytes.starts_with(sig)) {
+        return mime.parse().ok();
+    }
+
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let text = &bytes[start..];
+
+    let is_html = ["<!doctype html", "<html", "<head", "<body"]
+        .iter()
+        .any(|tag| {
+            text.len() >= tag.len() && text[..tag.len()].eq_ignore_ascii_case(tag.as_bytes())
+        });
+
+    if is_html {
+        Some(mime::TEXT_HTML)
+    } else {
+        None
+    }
 }
 
 fn normalize_path(path: &Path) -> PathBuf {
@@ -329,15 +405,23 @@ fn not_modified(metadata: &Metadata, headers: &HeaderMap) -> bool {
             .and_then(|v| v.to_str().ok())
             .and_then(|v| parse_http_date(v).ok())
             .and_then(|if_modified_time| {
-                metadata
-                    .modified()
-                    .map(|modified| modified <= if_modified_time)
-                    .ok()
+                last_modified(metadata).map(|modified| modified <= if_modified_time)
             })
             .unwrap_or(false),
     }
 }
 
+// The modification time of a file, truncated to whole seconds as HTTP dates have no finer
+// precision. Without this, a `Last-Modified` value sent back as `If-Modified-Since` would never
+// match the file it was taken from.
+fn last_modified(metadata: &Metadata) -> Option<SystemTime> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| UNIX_EPOCH + Duration::from_secs(duration.as_secs()))
+}
+
 fn entity_tag(metadata: &Metadata) -> Option<String> {
     metadata.modified().ok().and_then(|modified| {
         modified.duration_since(UNIX_EPOCH).ok().map(|duration| {
@@ -430,7 +514,7 @@ fn get_block_size(metadata: &Metadata) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::FileOptions;
+    use super::{sniff_content_type, FileOptions};
     use crate::router::builder::{build_simple_router, DefineSingleRoute, DrawRoutes};
     use crate::router::Router;
     use crate::test::TestServer;
@@ -614,6 +698,45 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[test]
+    fn assets_last_modified() {
+        use hyper::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+
+        let path = "resources/test/assets/doc.html";
+        let test_server =
+            TestServer::new(build_simple_router(|route| route.get("/").to_file(path))).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let last_modified = response.headers().get(LAST_MODIFIED).unwrap().clone();
+
+        // the client's copy is current
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(IF_MODIFIED_SINCE, last_modified.clone())
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // If-None-Match takes precedence, even when If-Modified-Since is current
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(IF_NONE_MATCH, HeaderValue::from_bytes(b"bogus").unwrap())
+            .with_header(IF_MODIFIED_SINCE, last_modified)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
     #[test]
     fn assets_with_cache_control() {
         let router = build_simple_router(|route| {
@@ -833,6 +956,66 @@ mod tests {
         assert_eq!(response.read_body().unwrap(), expected_body);
     }
 
+    #[test]
+    fn assets_sniffs_content_type_if_enabled() {
+        let router = build_simple_router(|route| {
+            route.get("/*").to_dir(
+                FileOptions::new("resources/test/assets/sniffed")
+                    .with_content_sniffing(true)
+                    .build(),
+            )
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        for (path, content_type) in vec![("logo", "image/png"), ("page", "text/html")] {
+            let response = test_server
+                .client()
+                .get(&format!("http://localhost/{}", path))
+                .perform()
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get(CONTENT_TYPE).unwrap(), content_type);
+
+            let expected_body =
+                fs::read(format!("resources/test/assets/sniffed/{}", path)).unwrap();
+            assert_eq!(response.read_body().unwrap(), expected_body);
+        }
+    }
+
+    #[test]
+    fn assets_no_sniffing_if_disabled() {
+        let test_server =
+            TestServer::new(static_router("/*", "resources/test/assets/sniffed")).unwrap();
+
+        for path in vec!["logo", "page"] {
+            let response = test_server
+                .client()
+                .get(&format!("http://localhost/{}", path))
+                .perform()
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get(CONTENT_TYPE).unwrap(),
+                "application/octet-stream"
+            );
+        }
+    }
+
+    #[test]
+    fn sniffs_common_content_types() {
+        assert_eq!(sniff_content_type(b"%PDF-1.4"), Some(mime::APPLICATION_PDF));
+        assert_eq!(
+            sniff_content_type(b"\xff\xd8\xff\xe0"),
+            Some(mime::IMAGE_JPEG)
+        );
+        assert_eq!(sniff_content_type(b"GIF89a"), Some(mime::IMAGE_GIF));
+        assert_eq!(sniff_content_type(b"  <HTML>"), Some(mime::TEXT_HTML));
+        assert_eq!(sniff_content_type(b"plain text"), None);
+        assert_eq!(sniff_content_type(b""), None);
+    }
+
     fn test_server() -> TestServer {
         TestServer::new(static_router("/*", "resources/test/assets")).unwrap()
     }
@@ -0,0 +1,574 @@
+//! Defines a handler which dispatches a batch of requests through a `Router` in a single HTTP
+//! request, for JSON-RPC style endpoints.
+
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+
+use futures::prelude::*;
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{body, Body, Method, StatusCode, Uri, Version};
+use log::{error, trace};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::handler::{
+    Handler, HandlerError, HandlerFuture, IntoHandlerError, IntoResponse, NewHandler,
+};
+use crate::helpers::http::request::body::{read_body, BodyReadConfig};
+use crate::helpers::http::request::path::RequestPathSegments;
+use crate::helpers::http::response::create_response;
+use crate::router::Router;
+use crate::state::client_addr::put_client_addr;
+use crate::state::{
+    client_addr, replace_generated_request_id, request_id, CancellationToken, FromState, State,
+};
+
+/// The default maximum number of requests in a batch.
+const DEFAULT_MAX_ITEMS: usize = 32;
+
+/// The default maximum size of the body of a batch request, in bytes.
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// A single request within a batch.
+#[derive(Deserialize)]
+struct BatchRequest {
+    method: String,
+    path: String,
+    #[serde(default)]
+    body: Option<Value>,
+}
+
+/// The result of a single request within a batch.
+#[derive(Serialize)]
+struct BatchResult {
+    status: u16,
+    body: Value,
+}
+
+/// The parts of the batch request which are replaced while each request in the batch is
+/// dispatched, to be restored afterwards.
+struct BaseRequest {
+    method: Method,
+    uri: Uri,
+    version: Version,
+    headers: HeaderMap,
+}
+
+impl BaseRequest {
+    fn borrow_from(state: &State) -> BaseRequest {
+        BaseRequest {
+            method: Method::borrow_from(state).clone(),
+            uri: Uri::borrow_from(state).clone(),
+            version: *Version::borrow_from(state),
+            headers: HeaderMap::borrow_from(state).clone(),
+        }
+    }
+
+    fn restore(self, state: &mut State) {
+        state.put(self.method);
+        state.put(self.uri);
+        state.put(self.version);
+        state.put(self.headers);
+    }
+}
+
+/// A `Handler` which accepts a JSON array of requests, dispatches each through a `Router`, and
+/// responds with a JSON array of the results in the same order.
+///
+/// Each request is an object with a `method`, a `path` (which may include a query string) and an
+/// optional JSON `body`. Each result is an object holding the `status` code of the response, and
+/// its `body`. Response bodies with a JSON content type are embedded as JSON, and other bodies are
+/// embedded as strings.
+///
+/// Requests are dispatched in order, one at a time, with the `State` of the batch request, so they
+/// share its headers, client address, request ID and any data stored by middleware (e.g. a
+/// session). A request which fails is reported via the status of its result, and does not prevent
+/// the remaining requests from being dispatched. A request which panics is reported with a `500`
+/// status; as its `State` is lost, the remaining requests are dispatched with a `State` holding
+/// only the headers, client address and request ID of the batch request.
+///
+/// A batch which is not a valid JSON array of requests is rejected with `400 Bad Request`. A batch
+/// of more than 32 requests, or with a body larger than 1MiB, is rejected with
+/// `413 Payload Too Large`; these limits can be changed via `with_max_items` and
+/// `with_max_body_size`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::StatusCode;
+/// # use gotham::handler::batch::BatchHandler;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn hello(state: State) -> (State, (StatusCode, mime::Mime, &'static str)) {
+///     (state, (StatusCode::OK, mime::TEXT_PLAIN, "Hello!"))
+/// }
+///
+/// # fn main() {
+/// let api = build_simple_router(|route| {
+///     route.get("/hello").to(hello);
+/// });
+///
+/// let router = build_simple_router(|route| {
+///     route.post("/batch").to_new_handler(BatchHandler::new(api));
+/// });
+///
+/// let test_server = TestServer::new(router).unwrap();
+/// let response = test_server
+///     .client()
+///     .post(
+///         "http://localhost/batch",
+///         r#"[{"method": "GET", "path": "/hello"}, {"method": "GET", "path": "/missing"}]"#,
+///         mime::APPLICATION_JSON,
+///     )
+///     .perform()
+///     .unwrap();
+///
+/// assert_eq!(response.status(), StatusCode::OK);
+/// assert_eq!(
+///     response.read_utf8_body().unwrap(),
+///     r#"[{"status":200,"body":"Hello!"},{"status":404,"body":""}]"#
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BatchHandler {
+    router: Router,
+    max_items: usize,
+    max_body_size: usize,
+}
+
+impl BatchHandler {
+    /// Creates a new `BatchHandler` which dispatches requests through `router`.
+    pub fn new(router: Router) -> BatchHandler {
+        BatchHandler {
+            router,
+            max_items: DEFAULT_MAX_ITEMS,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// Sets the maximum number of requests in a batch. Defaults to 32.
+    pub fn with_max_items(self, max_items: usize) -> BatchHandler {
+        BatchHandler { max_items, ..self }
+    }
+
+    /// Sets the maximum size of the body of a batch request, in bytes. Defaults to 1MiB.
+    pub fn with_max_body_size(self, max_body_size: usize) -> BatchHandler {
+        BatchHandler {
+            max_body_size,
+            ..self
+        }
+    }
+}
+
+impl NewHandler for BatchHandler {
+    type Instance = Self;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for BatchHandler {
+    fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
+        let body = state.try_take::<Body>().unwrap_or_else(Body::empty);
+
+        async move {
+            let config = BodyReadConfig::default().with_max_size(self.max_body_size);
+            let body = match read_body(body, &config).await {
+                Ok(body) => body,
+                Err(e) => return Err((state, HandlerError::from(e))),
+            };
+
+            let body = match body.into_bytes().await {
+                Ok(body) => body,
+                Err(e) => return Err((state, HandlerError::from(e))),
+            };
+
+            let requests: Vec<BatchRequest> = match serde_json::from_slice(&body) {
+                Ok(requests) => requests,
+                Err(e) => {
+                    trace!("[{}] invalid batch request: {}", request_id(&state), e);
+                    let err = e.into_handler_error().with_status(StatusCode::BAD_REQUEST);
+                    return Err((state, err));
+                }
+            };
+
+            if requests.len() > self.max_items {
+                trace!(
+                    "[{}] batch of {} requests exceeds the limit of {}",
+                    request_id(&state),
+                    requests.len(),
+                    self.max_items
+                );
+                let body = format!("a batch may hold at most {} requests", self.max_items);
+                let res = create_response(
+                    &state,
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    mime::TEXT_PLAIN,
+                    body,
+                );
+                return Ok((state, res));
+            }
+
+            let base = BaseRequest::borrow_from(&state);
+
+            let mut results = Vec::with_capacity(requests.len());
+            for request in requests {
+                let (next, result) = self.dispatch(state, &base, request).await;
+                state = next;
+                results.push(result);
+            }
+
+            base.restore(&mut state);
+
+            let body = serde_json::to_vec(&results).expect("batch results are serializable");
+            let res = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, body);
+            Ok((state, res))
+        }
+        .boxed()
+    }
+}
+
+impl BatchHandler {
+    /// Dispatches a single request through the `Router` with the `State` of the batch request,
+    /// returning the `State` to be used for the next request.
+    async fn dispatch(
+        &self,
+        mut state: State,
+        base: &BaseRequest,
+        request: BatchRequest,
+    ) -> (State, BatchResult) {
+        let method = Method::from_bytes(request.method.as_bytes());
+        let uri = request.path.parse::<Uri>();
+
+        let (method, uri) = match (method, uri) {
+            (Ok(method), Ok(uri)) => (method, uri),
+            _ => {
+                let result = BatchResult {
+                    status: StatusCode::BAD_REQUEST.as_u16(),
+                    body: Value::String(format!(
+                        "invalid request: {} {}",
+                        request.method, request.path
+                    )),
+                };
+                return (state, result);
+            }
+        };
+
+        let mut headers = base.headers.clone();
+        headers.remove(CONTENT_LENGTH);
+
+        let body = match request.body {
+            Some(value) => {
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                Body::from(value.to_string())
+            }
+            None => {
+                headers.remove(CONTENT_TYPE);
+                Body::empty()
+            }
+        };
+
+        let id = request_id(&state).to_owned();
+        let addr = client_addr(&state);
+        let token = CancellationToken::try_borrow_from(&state).cloned();
+
+        state.put(RequestPathSegments::new(uri.path()));
+        state.put(method);
+        state.put(uri);
+        state.put(base.version);
+        state.put(headers);
+        state.put(body);
+
+        let router = self.router.clone();
+        let handled = AssertUnwindSafe(async move { router.handle(state).await })
+            .catch_unwind()
+            .await;
+
+        let (state, response) = match handled {
+            Ok(Ok((state, response))) => (state, response),
+            Ok(Err((state, err))) => {
+                let response = err.into_response(&state);
+                (state, response)
+            }
+            Err(_) => {
+                error!("[PANIC][{}][a request within a batch panicked]", id);
+
+                let mut state = State::new();
+                state.put(base.headers.clone());
+                replace_generated_request_id(&mut state, id);
+                if let Some(addr) = addr {
+                    put_client_addr(&mut state, addr);
+                }
+                if let Some(token) = token {
+                    state.put(token);
+                }
+
+                let result = BatchResult {
+                    status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                    body: Value::Null,
+                };
+                return (state, result);
+            }
+        };
+
+        let status = response.status().as_u16();
+        let is_json = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<mime::Mime>().ok())
+            .map(|mime| mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON))
+            .unwrap_or(false);
+
+        let body = match body::to_bytes(response.into_body()).await {
+            Ok(body) => body,
+            Err(e) => {
+                trace!(
+                    "[{}] unable to read batch response body: {}",
+                    request_id(&state),
+                    e
+                );
+                let result = BatchResult {
+                    status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                    body: Value::Null,
+                };
+                return (state, result);
+            }
+        };
+
+        let body = match serde_json::from_slice(&body) {
+            Ok(value) if is_json => value,
+            _ => Value::String(String::from_utf8_lossy(&body).into_owned()),
+        };
+
+        (state, BatchResult { status, body })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io;
+
+    use hyper::Response;
+
+    use crate::middleware::{Middleware, NewMiddleware};
+    use crate::pipeline::new_pipeline;
+    use crate::pipeline::single::single_pipeline;
+    use crate::router::builder::*;
+    use crate::state::StateData;
+    use crate::test::TestServer;
+
+    struct Tenant(&'static str);
+
+    impl StateData for Tenant {}
+
+    #[derive(Clone, Copy)]
+    struct TenantMiddleware;
+
+    impl NewMiddleware for TenantMiddleware {
+        type Instance = Self;
+
+        fn new_middleware(&self) -> io::Result<Self> {
+            Ok(*self)
+        }
+    }
+
+    impl Middleware for TenantMiddleware {
+        fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+        where
+            Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+        {
+            state.put(Tenant("acme"));
+            chain(state)
+        }
+    }
+
+    fn user(state: State) -> (State, Response<Body>) {
+        let res = create_response(
+            &state,
+            StatusCode::OK,
+            mime::APPLICATION_JSON,
+            r#"{"id":1,"name":"alice"}"#,
+        );
+        (state, res)
+    }
+
+    fn echo(mut state: State) -> Pin<Box<HandlerFuture>> {
+        body::to_bytes(Body::take_from(&mut state))
+            .then(|body| match body {
+                Ok(body) => {
+                    let res = create_response(&state, StatusCode::CREATED, mime::TEXT_PLAIN, body);
+                    future::ok((state, res))
+                }
+                Err(e) => future::err((state, e.into_handler_error())),
+            })
+            .boxed()
+    }
+
+    fn tenant(state: State) -> (State, Response<Body>) {
+        let name = Tenant::try_borrow_from(&state).map_or("none", |tenant| tenant.0);
+        let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, name);
+        (state, res)
+    }
+
+    fn panics(_state: State) -> (State, Response<Body>) {
+        panic!("handler failed")
+    }
+
+    fn test_server_with(handler: fn(Router) -> BatchHandler) -> TestServer {
+        let api = build_simple_router(|route| {
+            route.get("/users/1").to(user);
+            route.post("/echo").to(echo);
+            route.get("/tenant").to(tenant);
+            route.get("/panic").to(panics);
+        });
+
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(TenantMiddleware).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.post("/batch").to_new_handler(handler(api));
+        });
+
+        TestServer::new(router).unwrap()
+    }
+
+    fn test_server() -> TestServer {
+        test_server_with(BatchHandler::new)
+    }
+
+    fn post_batch(test_server: &TestServer, batch: &str) -> (StatusCode, Vec<u8>) {
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/batch",
+                batch.to_owned(),
+                mime::APPLICATION_JSON,
+            )
+            .perform()
+            .unwrap();
+
+        (response.status(), response.read_body().unwrap())
+    }
+
+    #[test]
+    fn dispatches_requests_in_order() {
+        let response = test_server()
+            .client()
+            .post(
+                "http://localhost/batch",
+                r#"[
+                    {"method": "GET", "path": "/users/1"},
+                    {"method": "POST", "path": "/echo", "body": {"hello": "world"}},
+                    {"method": "GET", "path": "/missing"},
+                    {"method": "NOT A METHOD", "path": "/users/1"}
+                ]"#,
+                mime::APPLICATION_JSON,
+            )
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let results: Value = serde_json::from_slice(&response.read_body().unwrap()).unwrap();
+        assert_eq!(
+            results,
+            serde_json::json!([
+                {"status": 200, "body": {"id": 1, "name": "alice"}},
+                {"status": 201, "body": r#"{"hello":"world"}"#},
+                {"status": 404, "body": ""},
+                {"status": 400, "body": "invalid request: NOT A METHOD /users/1"},
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_batches() {
+        let response = test_server()
+            .client()
+            .post(
+                "http://localhost/batch",
+                r#"{"method": "GET", "path": "/users/1"}"#,
+                mime::APPLICATION_JSON,
+            )
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn shares_state_of_batch_request() {
+        let (status, body) = post_batch(
+            &test_server(),
+            r#"[{"method": "GET", "path": "/tenant"}, {"method": "GET", "path": "/tenant"}]"#,
+        );
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            serde_json::json!([
+                {"status": 200, "body": "acme"},
+                {"status": 200, "body": "acme"},
+            ])
+        );
+    }
+
+    #[test]
+    fn panics_are_reported_per_request() {
+        let (status, body) = post_batch(
+            &test_server(),
+            r#"[
+                {"method": "GET", "path": "/panic"},
+                {"method": "GET", "path": "/users/1"}
+            ]"#,
+        );
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            serde_json::json!([
+                {"status": 500, "body": null},
+                {"status": 200, "body": {"id": 1, "name": "alice"}},
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_batches() {
+        let test_server = test_server_with(|api| {
+            BatchHandler::new(api)
+                .with_max_items(2)
+                .with_max_body_size(256)
+        });
+
+        let request = r#"{"method": "GET", "path": "/users/1"}"#;
+
+        let batch = format!("[{}]", vec![request; 2].join(","));
+        assert_eq!(post_batch(&test_server, &batch).0, StatusCode::OK);
+
+        let batch = format!("[{}]", vec![request; 3].join(","));
+        assert_eq!(
+            post_batch(&test_server, &batch).0,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        let batch = format!(
+            r#"[{{"method": "POST", "path": "/echo", "body": "{}"}}]"#,
+            "a".repeat(512)
+        );
+        assert_eq!(
+            post_batch(&test_server, &batch).0,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+}
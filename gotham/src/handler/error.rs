@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::io;
 
 use hyper::{Body, Response, StatusCode};
 use log::{debug, trace};
@@ -10,6 +11,10 @@ use crate::state::{request_id, State};
 
 /// Describes an error which occurred during handler execution, and allows the creation of a HTTP
 /// `Response`.
+///
+/// Common error types (`std::io::Error`, `serde_json::Error` and `hyper::Error`) can be converted
+/// via `From`, which selects an appropriate status code for the error. This allows the `?`
+/// operator to be used directly in functions returning `Result<_, HandlerError>`.
 pub struct HandlerError {
     status_code: StatusCode,
     cause: Box<dyn Error + Send>,
@@ -62,6 +67,42 @@ where
     }
 }
 
+impl From<io::Error> for HandlerError {
+    /// Converts an I/O error into a `500 Internal Server Error`.
+    fn from(err: io::Error) -> HandlerError {
+        err.into_handler_error()
+    }
+}
+
+impl From<serde_json::Error> for HandlerError {
+    /// Converts a JSON error into a `400 Bad Request` when the input could not be parsed or did
+    /// not match the expected type, and a `500 Internal Server Error` when reading or writing the
+    /// underlying data failed.
+    fn from(err: serde_json::Error) -> HandlerError {
+        let status = if err.is_io() {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+
+        err.into_handler_error().with_status(status)
+    }
+}
+
+impl From<hyper::Error> for HandlerError {
+    /// Converts a Hyper error into a `400 Bad Request` when the request could not be parsed, and a
+    /// `500 Internal Server Error` otherwise.
+    fn from(err: hyper::Error) -> HandlerError {
+        let status = if err.is_parse() {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        err.into_handler_error().with_status(status)
+    }
+}
+
 impl Display for HandlerError {
     fn fmt(&self, out: &mut Formatter) -> fmt::Result {
         out.write_str("handler failed to process request")
@@ -88,6 +129,27 @@ impl Error for HandlerError {
 }
 
 impl HandlerError {
+    /// Returns the HTTP status code of the response which is generated by the `IntoResponse`
+    /// implementation.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::StatusCode;
+    /// # use gotham::handler::HandlerError;
+    /// #
+    /// # fn main() {
+    /// let io_error = std::io::Error::new(std::io::ErrorKind::Other, "disk on fire");
+    /// let handler_error = HandlerError::from(io_error);
+    ///
+    /// assert_eq!(handler_error.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    /// # }
+    /// ```
+    pub fn status(&self) -> StatusCode {
+        self.status_code
+    }
+
     /// Sets the HTTP status code of the response which is generated by the `IntoResponse`
     /// implementation.
     ///
@@ -146,3 +208,57 @@ impl IntoResponse for HandlerError {
         create_empty_response(state, self.status_code)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::prelude::*;
+    use hyper::body;
+    use serde_derive::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Payload {
+        #[allow(dead_code)]
+        id: u32,
+    }
+
+    fn parse_payload(raw: &str) -> Result<Payload, HandlerError> {
+        Ok(serde_json::from_str(raw)?)
+    }
+
+    #[test]
+    fn io_error_converts_to_internal_server_error() {
+        let err = io::Error::new(io::ErrorKind::Other, "disk on fire");
+        assert_eq!(
+            HandlerError::from(err).status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn json_errors_convert_to_bad_request() {
+        let syntax = parse_payload("{\"id\": ").unwrap_err();
+        assert_eq!(syntax.status(), StatusCode::BAD_REQUEST);
+
+        let data = parse_payload("{\"id\": \"one\"}").unwrap_err();
+        assert_eq!(data.status(), StatusCode::BAD_REQUEST);
+
+        assert!(parse_payload("{\"id\": 1}").is_ok());
+    }
+
+    #[test]
+    fn hyper_body_error_converts_to_internal_server_error() {
+        let chunks = vec![Err::<Vec<u8>, _>(io::Error::new(
+            io::ErrorKind::Other,
+            "stream failed",
+        ))];
+        let body = hyper::Body::wrap_stream(stream::iter(chunks));
+
+        let err = futures::executor::block_on(body::to_bytes(body)).unwrap_err();
+        assert_eq!(
+            HandlerError::from(err).status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
@@ -21,6 +21,9 @@ use crate::error::*;
 /// Defines handlers for serving static assets.
 pub mod assets;
 
+/// Defines a handler for dispatching batches of requests through a `Router`.
+pub mod batch;
+
 pub use self::error::{HandlerError, IntoHandlerError};
 
 /// A type alias for the trait objects returned by `HandlerService`.
@@ -384,6 +387,42 @@ where
     }
 }
 
+/// Converts a `StatusCode` into a response with an empty body, for handlers which only need to
+/// indicate the outcome of the request.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::StatusCode;
+/// # use gotham::state::State;
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// #
+/// fn delete_user(state: State) -> (State, StatusCode) {
+///     (state, StatusCode::NO_CONTENT)
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.delete("/users/1").to(delete_user);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .delete("http://localhost/users/1")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::NO_CONTENT);
+/// # }
+/// ```
+impl IntoResponse for StatusCode {
+    fn into_response(self, state: &State) -> Response<Body> {
+        response::create_empty_response(state, self)
+    }
+}
+
 impl<B> IntoResponse for (Mime, B)
 where
     B: Into<Body>,
@@ -423,3 +462,32 @@ derive_into_response!(&'static str);
 derive_into_response!(&'static [u8]);
 derive_into_response!(Cow<'static, str>);
 derive_into_response!(Cow<'static, [u8]>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    #[test]
+    fn status_code_responses_have_empty_bodies() {
+        fn accept(state: State) -> (State, StatusCode) {
+            (state, StatusCode::NO_CONTENT)
+        }
+
+        let router = build_simple_router(|route| {
+            route.post("/jobs").to(accept);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server
+            .client()
+            .post("http://localhost/jobs", "", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(response.read_body().unwrap().is_empty());
+    }
+}
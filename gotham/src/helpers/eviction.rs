@@ -0,0 +1,266 @@
+//! Defines a map which bounds the memory used by in-memory stores, such as those held by the
+//! rate limiting and idempotency middlewares.
+//!
+//! Entries are evicted in least recently used order once the map holds more than a maximum number
+//! of entries, and expire once they have gone unused for longer than a TTL. Expired entries are
+//! never returned, and are removed by a sweep which runs at most once per sweep interval, as the
+//! map is accessed.
+
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use linked_hash_map::LinkedHashMap;
+
+/// The default minimum time between sweeps for expired entries.
+const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Bounds on the entries held by an `EvictingMap`.
+///
+/// By default, no bounds are applied.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use std::time::Duration;
+/// # use gotham::helpers::eviction::EvictionPolicy;
+/// # fn main() {
+/// let policy = EvictionPolicy::default()
+///     .with_max_entries(10_000)
+///     .with_ttl(Duration::from_secs(15 * 60))
+///     .with_sweep_interval(Duration::from_secs(30));
+/// # drop(policy);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvictionPolicy {
+    max_entries: Option<usize>,
+    ttl: Option<Duration>,
+    sweep_interval: Duration,
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> EvictionPolicy {
+        EvictionPolicy {
+            max_entries: None,
+            ttl: None,
+            sweep_interval: DEFAULT_SWEEP_INTERVAL,
+        }
+    }
+}
+
+impl EvictionPolicy {
+    /// Evicts the least recently used entry whenever an insert takes the map beyond
+    /// `max_entries`.
+    pub fn with_max_entries(self, max_entries: usize) -> EvictionPolicy {
+        EvictionPolicy {
+            max_entries: Some(max_entries.max(1)),
+            ..self
+        }
+    }
+
+    /// Expires entries which have not been read or written for the given duration.
+    pub fn with_ttl(self, ttl: Duration) -> EvictionPolicy {
+        EvictionPolicy {
+            ttl: Some(ttl),
+            ..self
+        }
+    }
+
+    /// Sets the minimum time between sweeps for expired entries. Defaults to one minute.
+    pub fn with_sweep_interval(self, sweep_interval: Duration) -> EvictionPolicy {
+        EvictionPolicy {
+            sweep_interval,
+            ..self
+        }
+    }
+
+    /// The maximum number of entries, if bounded.
+    pub fn max_entries(&self) -> Option<usize> {
+        self.max_entries
+    }
+
+    /// The time after which unused entries expire, if any.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+}
+
+/// A map whose entries are evicted according to an `EvictionPolicy`.
+///
+/// Reading or writing an entry marks it as the most recently used, and restarts its TTL.
+pub struct EvictingMap<K, V>
+where
+    K: Hash + Eq,
+{
+    policy: EvictionPolicy,
+    entries: LinkedHashMap<K, (Instant, V)>,
+    last_sweep: Instant,
+}
+
+impl<K, V> EvictingMap<K, V>
+where
+    K: Hash + Eq,
+{
+    /// Creates an empty map, applying the provided `EvictionPolicy`.
+    pub fn new(policy: EvictionPolicy) -> EvictingMap<K, V> {
+        EvictingMap {
+            policy,
+            entries: LinkedHashMap::new(),
+            last_sweep: Instant::now(),
+        }
+    }
+
+    /// The number of entries held, which may include expired entries not yet swept.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the map holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the live entry for `key`, marking it as the most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Returns the live entry for `key` mutably, marking it as the most recently used.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let now = Instant::now();
+        self.sweep_if_due(now);
+
+        let expired = match self.entries.get(key) {
+            Some((used, _)) => self.is_expired(*used, now),
+            None => return None,
+        };
+
+        if expired {
+            self.entries.remove(key);
+            return None;
+        }
+
+        self.entries.get_refresh(key).map(|(used, value)| {
+            *used = now;
+            value
+        })
+    }
+
+    /// Returns the live entry for `key` mutably, first inserting the value returned by `f` if
+    /// there is none.
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> &mut V
+    where
+        K: Clone,
+        F: FnOnce() -> V,
+    {
+        if self.get_mut(&key).is_none() {
+            self.insert(key.clone(), f());
+        }
+
+        match self.entries.get_mut(&key) {
+            Some((_, value)) => value,
+            None => unreachable!("entry was inserted"),
+        }
+    }
+
+    /// Inserts an entry, returning the live value previously held for `key`. The least recently
+    /// used entries are evicted if the map grows beyond its maximum size.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let now = Instant::now();
+        self.sweep_if_due(now);
+
+        let previous = self
+            .entries
+            .insert(key, (now, value))
+            .filter(|(used, _)| !self.is_expired(*used, now))
+            .map(|(_, value)| value);
+
+        if let Some(max_entries) = self.policy.max_entries {
+            while self.entries.len() > max_entries {
+                self.entries.pop_front();
+            }
+        }
+
+        previous
+    }
+
+    /// Removes the entry for `key`, returning its value if it was live.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let now = Instant::now();
+
+        self.entries
+            .remove(key)
+            .filter(|(used, _)| !self.is_expired(*used, now))
+            .map(|(_, value)| value)
+    }
+
+    /// Removes every expired entry.
+    pub fn sweep(&mut self) {
+        let now = Instant::now();
+        self.last_sweep = now;
+
+        // entries are ordered by last use, so the expired entries are all at the front
+        while let Some((_, (used, _))) = self.entries.front() {
+            if !self.is_expired(*used, now) {
+                break;
+            }
+            self.entries.pop_front();
+        }
+    }
+
+    fn sweep_if_due(&mut self, now: Instant) {
+        if self.policy.ttl.is_some()
+            && now.duration_since(self.last_sweep) >= self.policy.sweep_interval
+        {
+            self.sweep();
+        }
+    }
+
+    fn is_expired(&self, used: Instant, now: Instant) -> bool {
+        self.policy
+            .ttl
+            .map_or(false, |ttl| now.duration_since(used) >= ttl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn least_recently_used_entries_are_evicted() {
+        let mut map = EvictingMap::new(EvictionPolicy::default().with_max_entries(2));
+        map.insert("a", 1);
+        map.insert("b", 2);
+        assert_eq!(map.get(&"a"), Some(&1));
+
+        map.insert("c", 3);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&"b"), None);
+        assert_eq!(map.get(&"a"), Some(&1));
+        assert_eq!(map.get(&"c"), Some(&3));
+    }
+
+    #[test]
+    fn unused_entries_expire() {
+        let policy = EvictionPolicy::default()
+            .with_ttl(Duration::from_millis(100))
+            .with_sweep_interval(Duration::from_secs(60));
+        let mut map = EvictingMap::new(policy);
+        map.insert("a", 1);
+        map.insert("b", 2);
+
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(map.get(&"b"), Some(&2));
+
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(map.get(&"a"), None);
+        assert_eq!(map.get(&"b"), Some(&2));
+
+        thread::sleep(Duration::from_millis(120));
+        assert_eq!(map.len(), 1);
+        map.sweep();
+        assert!(map.is_empty());
+    }
+}
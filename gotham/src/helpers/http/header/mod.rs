@@ -1,8 +1,68 @@
 //! Headers recognised by Gotham which do not exist in the standard headers
-//! provided by the Hyper library.
+//! provided by the Hyper library, and helpers for working with request headers.
+
+use hyper::header::{HeaderMap, HeaderValue};
 
 /// Marks the identifier of a request to a Gotham server.
 pub const X_REQUEST_ID: &str = "x-request-id";
 
 /// Marks the execution time of a Gotham request.
 pub const X_RUNTIME_DURATION: &str = "x-runtime-duration";
+
+/// Marks the time taken to produce a response, in milliseconds, measured from when the request
+/// was received.
+pub const X_RESPONSE_TIME: &str = "x-response-time";
+
+/// Normalizes request headers so that they can be matched predictably.
+///
+/// Header names are always stored by Hyper in their canonical lowercase form, regardless of the
+/// casing used by the client. Values are trimmed of leading and trailing whitespace, which some
+/// clients and proxies include.
+pub fn normalize_headers(headers: &mut HeaderMap) {
+    for value in headers.values_mut() {
+        let bytes = value.as_bytes();
+        let start = bytes.iter().position(|b| !is_whitespace(*b));
+        let end = bytes.iter().rposition(|b| !is_whitespace(*b));
+
+        let trimmed = match (start, end) {
+            (Some(start), Some(end)) => &bytes[start..=end],
+            _ => &bytes[..0],
+        };
+
+        if trimmed.len() == bytes.len() {
+            continue;
+        }
+
+        if let Ok(mut trimmed) = HeaderValue::from_bytes(trimmed) {
+            trimmed.set_sensitive(value.is_sensitive());
+            *value = trimmed;
+        }
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    b == b' ' || b == b'\t'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::HeaderName;
+
+    #[test]
+    fn normalize_headers_trims_values() {
+        let name = HeaderName::from_bytes(b"X-Api-Version").unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(name.clone(), "  2\t".parse().unwrap());
+        headers.append(name, "3".parse().unwrap());
+        headers.insert("x-blank", "   ".parse().unwrap());
+
+        normalize_headers(&mut headers);
+
+        let versions: Vec<_> = headers.get_all("x-api-version").iter().collect();
+        assert_eq!(versions, vec!["2", "3"]);
+        assert_eq!(headers["x-blank"], "");
+    }
+}
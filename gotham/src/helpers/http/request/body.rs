@@ -0,0 +1,908 @@
+//! Defines helper functions for reading the `Request` body.
+//!
+//! By default the body is buffered entirely in memory. `BodyReadConfig` allows the initial buffer
+//! capacity and the size of each incremental growth to be tuned, and optionally spills the body
+//! into a temporary file once it grows beyond a threshold, capping the peak memory used by a
+//! single large request.
+//!
+//! `read_decoded_body` additionally removes any `Content-Encoding` applied by the client, so that
+//! handlers see the decoded bytes.
+//!
+//! To bound the total memory used across concurrent requests, a `BodyBudget` can be shared between
+//! the `BodyReadConfig` values used throughout an application. Bodies which would exceed the
+//! budget while being buffered are rejected with `BodyReadError::BudgetExhausted`, which converts
+//! into a `503 Service Unavailable` response.
+
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use futures::prelude::*;
+use hyper::header::{HeaderMap, CONTENT_ENCODING};
+use hyper::{Body, StatusCode};
+use log::trace;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::task;
+use uuid::Uuid;
+
+use crate::handler::{HandlerError, IntoHandlerError};
+
+/// The default initial capacity of the in-memory body buffer.
+const DEFAULT_INITIAL_CAPACITY: usize = 8 * 1024;
+
+/// The default amount by which the in-memory body buffer grows when it runs out of space.
+const DEFAULT_GROWTH_INCREMENT: usize = 8 * 1024;
+
+/// Configuration for how a request body is buffered by `read_body`.
+///
+/// The default configuration buffers the whole body in memory, starting with an 8KiB buffer and
+/// growing it by 8KiB at a time.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::helpers::http::request::body::BodyReadConfig;
+/// # fn main() {
+/// let config = BodyReadConfig::default()
+///     .with_initial_capacity(16 * 1024)
+///     .with_growth_increment(64 * 1024)
+///     .spill_to_disk_after(1024 * 1024);
+/// # drop(config);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BodyReadConfig {
+    initial_capacity: usize,
+    growth_increment: usize,
+    spill_threshold: Option<usize>,
+    spill_dir: Option<PathBuf>,
+    max_size: Option<usize>,
+    max_decoded_size: Option<usize>,
+    max_decompression_ratio: Option<usize>,
+    budget: Option<BodyBudget>,
+}
+
+impl Default for BodyReadConfig {
+    fn default() -> BodyReadConfig {
+        BodyReadConfig {
+            initial_capacity: DEFAULT_INITIAL_CAPACITY,
+            growth_increment: DEFAULT_GROWTH_INCREMENT,
+            spill_threshold: None,
+            spill_dir: None,
+            max_size: None,
+            max_decoded_size: None,
+            max_decompression_ratio: None,
+            budget: None,
+        }
+    }
+}
+
+impl BodyReadConfig {
+    /// Sets the capacity of the in-memory buffer allocated before the first chunk is read.
+    pub fn with_initial_capacity(self, initial_capacity: usize) -> BodyReadConfig {
+        BodyReadConfig {
+            initial_capacity,
+            ..self
+        }
+    }
+
+    /// Sets the minimum number of bytes by which the in-memory buffer grows when an incoming
+    /// chunk does not fit into the remaining capacity.
+    pub fn with_growth_increment(self, growth_increment: usize) -> BodyReadConfig {
+        BodyReadConfig {
+            growth_increment: growth_increment.max(1),
+            ..self
+        }
+    }
+
+    /// Moves the body into a temporary file once more than `threshold` bytes have been read.
+    ///
+    /// Bytes read after that point are written straight to the file, so the in-memory buffer
+    /// never grows beyond `threshold` plus the size of a single chunk.
+    pub fn spill_to_disk_after(self, threshold: usize) -> BodyReadConfig {
+        BodyReadConfig {
+            spill_threshold: Some(threshold),
+            ..self
+        }
+    }
+
+    /// Sets the directory used for spilled bodies. Defaults to `std::env::temp_dir()`.
+    pub fn with_spill_dir<P>(self, dir: P) -> BodyReadConfig
+    where
+        P: Into<PathBuf>,
+    {
+        BodyReadConfig {
+            spill_dir: Some(dir.into()),
+            ..self
+        }
+    }
+
+    /// Limits the total size of a body, whether it is kept in memory or spilled to disk. Reading
+    /// stops as soon as the limit is exceeded, failing with `BodyReadError::TooLarge`.
+    pub fn with_max_size(self, max_size: usize) -> BodyReadConfig {
+        BodyReadConfig {
+            max_size: Some(max_size),
+            ..self
+        }
+    }
+
+    /// Limits the size of a body after its `Content-Encoding` has been removed by
+    /// `read_decoded_body`, protecting against small payloads which decompress to an enormous
+    /// size. Decoding stops as soon as the limit is exceeded.
+    pub fn with_max_decoded_size(self, max_decoded_size: usize) -> BodyReadConfig {
+        BodyReadConfig {
+            max_decoded_size: Some(max_decoded_size),
+            ..self
+        }
+    }
+
+    /// Limits the size of a body after its `Content-Encoding` has been removed by
+    /// `read_decoded_body` to `ratio` times the size of the encoded body. Combined with
+    /// `with_max_decoded_size`, whichever limit is smaller applies. Decoding stops as soon as the
+    /// limit is exceeded.
+    pub fn with_max_decompression_ratio(self, ratio: usize) -> BodyReadConfig {
+        BodyReadConfig {
+            max_decompression_ratio: Some(ratio),
+            ..self
+        }
+    }
+
+    /// Draws the memory used to buffer each body from the shared `budget`. Bodies which are
+    /// spilled to disk return their share of the budget at the point they are spilled.
+    pub fn with_budget(self, budget: BodyBudget) -> BodyReadConfig {
+        BodyReadConfig {
+            budget: Some(budget),
+            ..self
+        }
+    }
+
+    /// Determines the maximum decoded size of a body which is `encoded_len` bytes when encoded.
+    fn decoded_limit(&self, encoded_len: usize) -> Option<usize> {
+        let ratio_limit = self
+            .max_decompression_ratio
+            .map(|ratio| encoded_len.saturating_mul(ratio));
+
+        match (self.max_decoded_size, ratio_limit) {
+            (Some(max), Some(ratio_limit)) => Some(max.min(ratio_limit)),
+            (max, ratio_limit) => max.or(ratio_limit),
+        }
+    }
+
+    fn spill_path(&self) -> PathBuf {
+        let dir = self.spill_dir.clone().unwrap_or_else(env::temp_dir);
+        dir.join(format!("gotham-body-{}", Uuid::new_v4()))
+    }
+}
+
+/// A limit on the number of bytes buffered in memory by `read_body` at any one time, shared by
+/// every request whose `BodyReadConfig` uses the budget.
+///
+/// Bytes are drawn from the budget as each chunk of a body is buffered, and returned once the
+/// resulting `BufferedBody` is dropped or converted via `BufferedBody::into_bytes`. A body which
+/// would take the total beyond the limit fails with `BodyReadError::BudgetExhausted`, which is
+/// converted into a `503 Service Unavailable` response when returned from a handler via
+/// `HandlerError`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::helpers::http::request::body::{BodyBudget, BodyReadConfig};
+/// # fn main() {
+/// let budget = BodyBudget::new(64 * 1024 * 1024);
+/// let config = BodyReadConfig::default().with_budget(budget.clone());
+/// # drop(config);
+/// assert_eq!(budget.available(), 64 * 1024 * 1024);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BodyBudget {
+    limit: usize,
+    used: Arc<Mutex<usize>>,
+}
+
+impl BodyBudget {
+    /// Creates a budget allowing up to `limit` bytes to be buffered at once.
+    pub fn new(limit: usize) -> BodyBudget {
+        BodyBudget {
+            limit,
+            used: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// The number of bytes which can currently be drawn from the budget.
+    pub fn available(&self) -> usize {
+        self.limit - *self.used.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn reserve(&self, bytes: usize) -> Result<(), BodyReadError> {
+        let mut used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if bytes > self.limit - *used {
+            return Err(BodyReadError::BudgetExhausted(self.limit));
+        }
+
+        *used += bytes;
+        Ok(())
+    }
+
+    fn release(&self, bytes: usize) {
+        let mut used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+        *used -= bytes;
+    }
+}
+
+/// The portion of a `BodyBudget` held by a single body while it is buffered in memory.
+#[derive(Debug)]
+struct Reservation {
+    budget: BodyBudget,
+    bytes: usize,
+}
+
+impl Reservation {
+    fn grow(&mut self, bytes: usize) -> Result<(), BodyReadError> {
+        self.budget.reserve(bytes)?;
+        self.bytes += bytes;
+        Ok(())
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
+
+/// Describes why a body could not be read by `read_body`.
+///
+/// Converting into a `HandlerError` selects an appropriate status code for each variant.
+#[derive(Debug)]
+pub enum BodyReadError {
+    /// Buffering the body would exceed the `BodyBudget` with the given limit. Converts to
+    /// `503 Service Unavailable`.
+    BudgetExhausted(usize),
+
+    /// The body exceeded the limit set via `BodyReadConfig::with_max_size`. Converts to
+    /// `413 Payload Too Large`.
+    TooLarge(usize),
+
+    /// The body could not be read, or a spilled body could not be written to or read back from
+    /// disk. Converts to `500 Internal Server Error`.
+    Io(io::Error),
+}
+
+impl BodyReadError {
+    fn status(&self) -> StatusCode {
+        match *self {
+            BodyReadError::BudgetExhausted(_) => StatusCode::SERVICE_UNAVAILABLE,
+            BodyReadError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            BodyReadError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl Display for BodyReadError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            BodyReadError::BudgetExhausted(limit) => {
+                write!(f, "body buffer budget of {} bytes exhausted", limit)
+            }
+            BodyReadError::TooLarge(limit) => {
+                write!(f, "body exceeds the limit of {} bytes", limit)
+            }
+            BodyReadError::Io(ref e) => write!(f, "unable to read body: {}", e),
+        }
+    }
+}
+
+impl Error for BodyReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            BodyReadError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BodyReadError {
+    fn from(err: io::Error) -> BodyReadError {
+        BodyReadError::Io(err)
+    }
+}
+
+impl From<BodyReadError> for HandlerError {
+    fn from(err: BodyReadError) -> HandlerError {
+        let status = err.status();
+        err.into_handler_error().with_status(status)
+    }
+}
+
+/// A request body which has been fully read by `read_body`.
+#[derive(Debug)]
+pub enum BufferedBody {
+    /// The body was small enough to be kept in memory.
+    Memory(MemoryBody),
+
+    /// The body exceeded the spill threshold and was written to a temporary file.
+    File(SpilledBody),
+}
+
+impl BufferedBody {
+    /// The total number of bytes in the body.
+    pub fn len(&self) -> u64 {
+        match *self {
+            BufferedBody::Memory(ref memory) => memory.len() as u64,
+            BufferedBody::File(ref spilled) => spilled.len,
+        }
+    }
+
+    /// Returns `true` if the body contained no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the body was spilled to a temporary file.
+    pub fn is_spilled(&self) -> bool {
+        match *self {
+            BufferedBody::Memory(_) => false,
+            BufferedBody::File(_) => true,
+        }
+    }
+
+    /// Loads the whole body into memory, reading it back from disk if it was spilled. The
+    /// temporary file is removed once it has been read.
+    ///
+    /// The returned bytes are no longer counted against any `BodyBudget` they were drawn from.
+    pub async fn into_bytes(self) -> Result<Vec<u8>, BodyReadError> {
+        match self {
+            BufferedBody::Memory(memory) => Ok(memory.into_vec()),
+            BufferedBody::File(spilled) => Ok(fs::read(spilled.path()).await?),
+        }
+    }
+}
+
+/// A request body held in memory. Any share of a `BodyBudget` drawn to buffer the body is held
+/// until this value is dropped.
+#[derive(Debug)]
+pub struct MemoryBody {
+    buf: Vec<u8>,
+    reservation: Option<Reservation>,
+}
+
+impl MemoryBody {
+    /// Takes the bytes of the body, returning its share of any `BodyBudget`.
+    pub fn into_vec(self) -> Vec<u8> {
+        let MemoryBody { buf, reservation } = self;
+        drop(reservation);
+        buf
+    }
+}
+
+impl Deref for MemoryBody {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+/// A temporary file holding a spilled request body. The file is removed when this value is
+/// dropped.
+#[derive(Debug)]
+pub struct SpilledBody {
+    path: PathBuf,
+    len: u64,
+}
+
+impl SpilledBody {
+    /// The location of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpilledBody {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            trace!(" failed to remove spilled body {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Reads the entire `Body`, buffering it according to the provided `BodyReadConfig`.
+///
+/// Errors from the underlying stream, and from writing a spilled body to disk, are returned as
+/// `BodyReadError::Io`. When a `BodyBudget` is configured and the body cannot be buffered within
+/// it, `BodyReadError::BudgetExhausted` is returned. When the body exceeds the size set via
+/// `BodyReadConfig::with_max_size`, `BodyReadError::TooLarge` is returned.
+pub async fn read_body(
+    mut body: Body,
+    config: &BodyReadConfig,
+) -> Result<BufferedBody, BodyReadError> {
+    let mut read = 0;
+    let mut buf = Vec::with_capacity(config.initial_capacity);
+    let mut spilled: Option<(File, SpilledBody)> = None;
+    let mut reservation = config.budget.as_ref().map(|budget| Reservation {
+        budget: budget.clone(),
+        bytes: 0,
+    });
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        read += chunk.len();
+        match config.max_size {
+            Some(limit) if read > limit => return Err(BodyReadError::TooLarge(limit)),
+            _ => (),
+        }
+
+        if let Some((ref mut file, ref mut spill)) = spilled {
+            file.write_all(&chunk).await?;
+            spill.len += chunk.len() as u64;
+            continue;
+        }
+
+        if let Some(ref mut reservation) = reservation {
+            reservation.grow(chunk.len())?;
+        }
+
+        if buf.capacity() - buf.len() < chunk.len() {
+            buf.reserve_exact(chunk.len().max(config.growth_increment));
+        }
+        buf.extend_from_slice(&chunk);
+
+        match config.spill_threshold {
+            Some(threshold) if buf.len() > threshold => {
+                let path = config.spill_path();
+                trace!(" spilling request body to {:?}", path);
+
+                let mut file = File::create(&path).await?;
+                let spill = SpilledBody {
+                    path,
+                    len: buf.len() as u64,
+                };
+
+                file.write_all(&buf).await?;
+                buf = Vec::new();
+                spilled = Some((file, spill));
+                reservation = None;
+            }
+            _ => (),
+        }
+    }
+
+    match spilled {
+        Some((mut file, spill)) => {
+            file.flush().await?;
+            Ok(BufferedBody::File(spill))
+        }
+        None => Ok(BufferedBody::Memory(MemoryBody { buf, reservation })),
+    }
+}
+
+/// Describes why a body could not be decoded by `read_decoded_body`.
+///
+/// Converting into a `HandlerError` selects an appropriate status code for each variant.
+#[derive(Debug)]
+pub enum BodyDecodeError {
+    /// The `Content-Encoding` is not supported. Converts to `415 Unsupported Media Type`.
+    UnsupportedEncoding(String),
+
+    /// The decoded body exceeded the configured maximum size. Converts to `413 Payload Too Large`.
+    TooLarge(usize),
+
+    /// The body could not be read by `read_body`. Converts to the status selected for the
+    /// `BodyReadError`.
+    Read(BodyReadError),
+
+    /// The body was not validly encoded. Converts to `400 Bad Request`.
+    Io(io::Error),
+}
+
+impl Display for BodyDecodeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            BodyDecodeError::UnsupportedEncoding(ref encoding) => {
+                write!(f, "unsupported content encoding: {}", encoding)
+            }
+            BodyDecodeError::TooLarge(limit) => {
+                write!(f, "decoded body exceeds the limit of {} bytes", limit)
+            }
+            BodyDecodeError::Read(ref e) => write!(f, "{}", e),
+            BodyDecodeError::Io(ref e) => write!(f, "unable to decode body: {}", e),
+        }
+    }
+}
+
+impl Error for BodyDecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            BodyDecodeError::Read(ref e) => Some(e),
+            BodyDecodeError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BodyDecodeError {
+    fn from(err: io::Error) -> BodyDecodeError {
+        BodyDecodeError::Io(err)
+    }
+}
+
+impl From<BodyReadError> for BodyDecodeError {
+    fn from(err: BodyReadError) -> BodyDecodeError {
+        BodyDecodeError::Read(err)
+    }
+}
+
+impl From<BodyDecodeError> for HandlerError {
+    fn from(err: BodyDecodeError) -> HandlerError {
+        let status = match err {
+            BodyDecodeError::UnsupportedEncoding(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            BodyDecodeError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            BodyDecodeError::Read(ref e) => e.status(),
+            BodyDecodeError::Io(_) => StatusCode::BAD_REQUEST,
+        };
+
+        err.into_handler_error().with_status(status)
+    }
+}
+
+/// Reads the entire `Body` into memory, removing the `Content-Encoding` named in `headers`.
+///
+/// The `identity`, `gzip` (or `x-gzip`) and `deflate` encodings are supported. Any other encoding
+/// results in `BodyDecodeError::UnsupportedEncoding`. When `BodyReadConfig::with_max_decoded_size`
+/// or `BodyReadConfig::with_max_decompression_ratio` has been set, decoding is aborted with
+/// `BodyDecodeError::TooLarge` as soon as the decoded body exceeds the limit.
+///
+/// Compressed bodies are decoded on Tokio's blocking thread pool, so that decompressing a large
+/// body does not stall the other requests handled by the worker thread. This requires the
+/// returned `Future` to be run within a Tokio runtime.
+pub async fn read_decoded_body(
+    body: Body,
+    headers: &HeaderMap,
+    config: &BodyReadConfig,
+) -> Result<Vec<u8>, BodyDecodeError> {
+    let encoding = match headers.get(CONTENT_ENCODING) {
+        Some(value) => value
+            .to_str()
+            .map_err(|_| BodyDecodeError::UnsupportedEncoding("(invalid)".to_owned()))?
+            .trim()
+            .to_ascii_lowercase(),
+        None => "identity".to_owned(),
+    };
+
+    // Check the encoding before reading, so unsupported bodies are rejected cheaply.
+    match encoding.as_str() {
+        "identity" | "gzip" | "x-gzip" | "deflate" => (),
+        _ => return Err(BodyDecodeError::UnsupportedEncoding(encoding)),
+    }
+
+    let raw = read_body(body, config).await?.into_bytes().await?;
+
+    trace!(
+        " decoding {} byte body with encoding {}",
+        raw.len(),
+        encoding
+    );
+    let limit = config.decoded_limit(raw.len());
+    let initial_capacity = config.initial_capacity;
+
+    let decoded = match encoding.as_str() {
+        "gzip" | "x-gzip" => {
+            task::spawn_blocking(move || decode(GzDecoder::new(&raw[..]), initial_capacity, limit))
+                .await
+        }
+        "deflate" => {
+            task::spawn_blocking(move || {
+                decode(ZlibDecoder::new(&raw[..]), initial_capacity, limit)
+            })
+            .await
+        }
+        _ => return decode(&raw[..], initial_capacity, limit),
+    };
+
+    decoded.map_err(|e| BodyDecodeError::Io(io::Error::new(io::ErrorKind::Other, e)))?
+}
+
+fn decode<R>(
+    decoder: R,
+    initial_capacity: usize,
+    limit: Option<usize>,
+) -> Result<Vec<u8>, BodyDecodeError>
+where
+    R: Read,
+{
+    let mut decoded = Vec::with_capacity(initial_capacity);
+
+    match limit {
+        Some(limit) => {
+            // Read a single byte beyond the limit, to detect an oversized body without
+            // decompressing any more of it.
+            decoder.take(limit as u64 + 1).read_to_end(&mut decoded)?;
+
+            if decoded.len() > limit {
+                return Err(BodyDecodeError::TooLarge(limit));
+            }
+        }
+        None => {
+            let mut decoder = decoder;
+            decoder.read_to_end(&mut decoded)?;
+        }
+    }
+
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::runtime::Runtime;
+
+    fn chunked_body(chunks: usize, chunk_size: usize) -> (Body, Vec<u8>) {
+        let data: Vec<Vec<u8>> = (0..chunks)
+            .map(|i| vec![(i % 251) as u8; chunk_size])
+            .collect();
+        let expected = data.concat();
+        let stream = stream::iter(data.into_iter().map(Ok::<_, io::Error>));
+
+        (Body::wrap_stream(stream), expected)
+    }
+
+    #[test]
+    fn small_body_stays_in_memory() {
+        let mut rt = Runtime::new().unwrap();
+        let (body, expected) = chunked_body(4, 16);
+        let config = BodyReadConfig::default()
+            .with_initial_capacity(8)
+            .with_growth_increment(8)
+            .spill_to_disk_after(1024);
+
+        let buffered = rt.block_on(read_body(body, &config)).unwrap();
+        assert!(!buffered.is_spilled());
+        assert_eq!(buffered.len(), expected.len() as u64);
+        assert_eq!(rt.block_on(buffered.into_bytes()).unwrap(), expected);
+    }
+
+    #[test]
+    fn large_body_spills_to_temp_file() {
+        let mut rt = Runtime::new().unwrap();
+        let (body, expected) = chunked_body(64, 1024);
+        let config = BodyReadConfig::default().spill_to_disk_after(4 * 1024);
+
+        let buffered = rt.block_on(read_body(body, &config)).unwrap();
+        assert!(buffered.is_spilled());
+        assert_eq!(buffered.len(), expected.len() as u64);
+
+        let path = match buffered {
+            BufferedBody::File(ref spilled) => spilled.path().to_owned(),
+            BufferedBody::Memory(_) => unreachable!(),
+        };
+        assert!(path.exists());
+
+        assert_eq!(rt.block_on(buffered.into_bytes()).unwrap(), expected);
+        assert!(!path.exists());
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn encoded_headers(encoding: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, encoding.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn gzip_body_is_decoded() {
+        let mut rt = Runtime::new().unwrap();
+        let expected = b"{\"name\": \"gotham\"}".repeat(32);
+        let body = Body::from(gzip(&expected));
+        let config = BodyReadConfig::default().with_max_decoded_size(64 * 1024);
+
+        let decoded = rt
+            .block_on(read_decoded_body(body, &encoded_headers("gzip"), &config))
+            .unwrap();
+
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn decompression_bomb_is_rejected() {
+        let mut rt = Runtime::new().unwrap();
+        let bomb = gzip(&vec![0; 16 * 1024 * 1024]);
+        assert!(bomb.len() < 64 * 1024);
+
+        let config = BodyReadConfig::default().with_max_decoded_size(1024 * 1024);
+        let err = rt
+            .block_on(read_decoded_body(
+                Body::from(bomb),
+                &encoded_headers("gzip"),
+                &config,
+            ))
+            .unwrap_err();
+
+        match err {
+            BodyDecodeError::TooLarge(limit) => assert_eq!(limit, 1024 * 1024),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let handler_error: HandlerError = err.into();
+        assert_eq!(handler_error.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn benign_body_is_within_decompression_ratio() {
+        let mut rt = Runtime::new().unwrap();
+        let expected = b"{\"name\": \"gotham\", \"version\": 5}".to_vec();
+        let body = Body::from(gzip(&expected));
+        let config = BodyReadConfig::default().with_max_decompression_ratio(10);
+
+        let decoded = rt
+            .block_on(read_decoded_body(body, &encoded_headers("gzip"), &config))
+            .unwrap();
+
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn high_ratio_body_is_rejected() {
+        let mut rt = Runtime::new().unwrap();
+        let bomb = gzip(&vec![0; 16 * 1024 * 1024]);
+        let limit = bomb.len() * 100;
+
+        let config = BodyReadConfig::default().with_max_decompression_ratio(100);
+        let err = rt
+            .block_on(read_decoded_body(
+                Body::from(bomb),
+                &encoded_headers("gzip"),
+                &config,
+            ))
+            .unwrap_err();
+
+        match err {
+            BodyDecodeError::TooLarge(l) => assert_eq!(l, limit),
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let handler_error: HandlerError = err.into();
+        assert_eq!(handler_error.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn smaller_of_ratio_and_absolute_limit_applies() {
+        let config = BodyReadConfig::default()
+            .with_max_decoded_size(4096)
+            .with_max_decompression_ratio(10);
+
+        assert_eq!(config.decoded_limit(100), Some(1000));
+        assert_eq!(config.decoded_limit(1000), Some(4096));
+        assert_eq!(BodyReadConfig::default().decoded_limit(1000), None);
+    }
+
+    #[test]
+    fn decoding_stops_before_full_expansion() {
+        // An endless stream of zeros stands in for a body which would never finish expanding.
+        let config = BodyReadConfig::default().with_max_decompression_ratio(1000);
+        let limit = config.decoded_limit(64);
+
+        match decode(io::repeat(0), config.initial_capacity, limit) {
+            Err(BodyDecodeError::TooLarge(l)) => assert_eq!(l, 64_000),
+            _ => panic!("expected the body to be rejected"),
+        }
+    }
+
+    #[test]
+    fn unknown_encoding_is_unsupported() {
+        let mut rt = Runtime::new().unwrap();
+        let config = BodyReadConfig::default();
+
+        let err = rt
+            .block_on(read_decoded_body(
+                Body::from("data"),
+                &encoded_headers("br"),
+                &config,
+            ))
+            .unwrap_err();
+
+        let handler_error: HandlerError = err.into();
+        assert_eq!(handler_error.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn exhausted_budget_rejects_concurrent_body() {
+        let mut rt = Runtime::new().unwrap();
+        let budget = BodyBudget::new(1024);
+        let config = BodyReadConfig::default().with_budget(budget.clone());
+
+        rt.block_on(async {
+            let (mut sender, body) = Body::channel();
+            sender.send_data(vec![1; 800].into()).await.unwrap();
+
+            // The first body is buffered up to the point the client stops sending.
+            let first = read_body(body, &config);
+            futures::pin_mut!(first);
+            assert!(futures::poll!(first.as_mut()).is_pending());
+            assert_eq!(budget.available(), 224);
+
+            let err = read_body(Body::from(vec![2; 400]), &config)
+                .await
+                .unwrap_err();
+            match err {
+                BodyReadError::BudgetExhausted(limit) => assert_eq!(limit, 1024),
+                ref e => panic!("unexpected error: {}", e),
+            }
+
+            let handler_error: HandlerError = err.into();
+            assert_eq!(handler_error.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+            drop(sender);
+            assert_eq!(first.await.unwrap().len(), 800);
+            assert_eq!(budget.available(), 1024);
+
+            let buffered = read_body(Body::from(vec![2; 400]), &config).await.unwrap();
+            assert_eq!(buffered.len(), 400);
+        });
+    }
+
+    #[test]
+    fn oversized_body_is_rejected_while_streaming() {
+        let mut rt = Runtime::new().unwrap();
+        let config = BodyReadConfig::default()
+            .with_max_size(4 * 1024)
+            .spill_to_disk_after(1024);
+
+        let (body, _) = chunked_body(4, 1024);
+        assert_eq!(rt.block_on(read_body(body, &config)).unwrap().len(), 4096);
+
+        let (body, _) = chunked_body(5, 1024);
+        let err = rt.block_on(read_body(body, &config)).unwrap_err();
+        match err {
+            BodyReadError::TooLarge(limit) => assert_eq!(limit, 4 * 1024),
+            ref e => panic!("unexpected error: {}", e),
+        }
+
+        let handler_error: HandlerError = err.into();
+        assert_eq!(handler_error.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn buffered_bodies_hold_budget_until_dropped() {
+        let mut rt = Runtime::new().unwrap();
+        let budget = BodyBudget::new(1024);
+        let config = BodyReadConfig::default().with_budget(budget.clone());
+
+        rt.block_on(async {
+            let first = read_body(Body::from(vec![1; 800]), &config).await.unwrap();
+            assert_eq!(budget.available(), 224);
+
+            let err = read_body(Body::from(vec![2; 400]), &config)
+                .await
+                .unwrap_err();
+            match err {
+                BodyReadError::BudgetExhausted(limit) => assert_eq!(limit, 1024),
+                ref e => panic!("unexpected error: {}", e),
+            }
+
+            drop(first);
+            assert_eq!(budget.available(), 1024);
+
+            let second = read_body(Body::from(vec![2; 400]), &config).await.unwrap();
+            assert_eq!(budget.available(), 624);
+            assert_eq!(second.into_bytes().await.unwrap(), vec![2; 400]);
+            assert_eq!(budget.available(), 1024);
+        });
+    }
+}
@@ -0,0 +1,253 @@
+//! Defines a helper for handling a request differently based on the media type of its body.
+//!
+//! Where a single route accepts bodies in more than one format, `dispatch_by_content_type`
+//! selects a branch of the handler using the request `Content-Type` header, rather than requiring
+//! a separate route for each format.
+
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+
+use futures::prelude::*;
+use hyper::header::{HeaderMap, CONTENT_TYPE};
+use hyper::StatusCode;
+use log::trace;
+use mime::Mime;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::state::{request_id, FromState, State};
+
+type Branch = Box<dyn Fn(State) -> Pin<Box<HandlerFuture>> + Send + Sync + RefUnwindSafe>;
+
+/// The branches of a handler passed to `dispatch_by_content_type`, each keyed by the media type
+/// of the request bodies it handles.
+///
+/// Media types are compared by type and subtype only, so a request body of
+/// `application/json; charset=utf-8` is passed to the branch registered for
+/// `mime::APPLICATION_JSON`. Where more than one branch is registered for a media type, the first
+/// is used.
+pub struct ContentTypeHandlers {
+    branches: Vec<(Mime, Branch)>,
+}
+
+impl ContentTypeHandlers {
+    /// Creates a new set of branches, which is empty.
+    pub fn new() -> ContentTypeHandlers {
+        ContentTypeHandlers {
+            branches: Vec::new(),
+        }
+    }
+
+    /// Adds a branch invoked for request bodies of the media type `mime`.
+    pub fn on<F>(mut self, mime: Mime, branch: F) -> ContentTypeHandlers
+    where
+        F: Fn(State) -> Pin<Box<HandlerFuture>> + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.branches.push((mime, Box::new(branch)));
+        self
+    }
+
+    fn find(&self, content_type: &Mime) -> Option<&Branch> {
+        self.branches
+            .iter()
+            .find(|(mime, _)| {
+                mime.type_() == content_type.type_() && mime.subtype() == content_type.subtype()
+            })
+            .map(|(_, branch)| branch)
+    }
+}
+
+impl Default for ContentTypeHandlers {
+    fn default() -> ContentTypeHandlers {
+        ContentTypeHandlers::new()
+    }
+}
+
+/// Invokes the branch of `handlers` registered for the media type given by the request
+/// `Content-Type` header, passing it the `State`.
+///
+/// A request without a `Content-Type` header, or with one for which no branch is registered,
+/// receives a `415 Unsupported Media Type` response without any branch being invoked.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate futures;
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use std::pin::Pin;
+/// #
+/// # use futures::prelude::*;
+/// # use hyper::StatusCode;
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::helpers::http::request::content_type::{
+/// #     dispatch_by_content_type, ContentTypeHandlers,
+/// # };
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn respond(state: State, format: &'static str) -> Pin<Box<HandlerFuture>> {
+///     let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, format);
+///     future::ok((state, res)).boxed()
+/// }
+///
+/// fn import(state: State) -> Pin<Box<HandlerFuture>> {
+///     let handlers = ContentTypeHandlers::new()
+///         .on(mime::APPLICATION_JSON, |state| respond(state, "json"))
+///         .on(mime::TEXT_CSV, |state| respond(state, "csv"));
+///
+///     dispatch_by_content_type(state, &handlers)
+/// }
+/// #
+/// # fn main() {
+/// #   let router = build_simple_router(|route| {
+/// #       route.post("/import").to(import);
+/// #   });
+/// #
+/// #   let test_server = TestServer::new(router).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .post("http://example.com/import", "a,b", mime::TEXT_CSV)
+/// #       .perform()
+/// #       .unwrap();
+/// #
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "csv");
+/// # }
+/// ```
+pub fn dispatch_by_content_type(
+    state: State,
+    handlers: &ContentTypeHandlers,
+) -> Pin<Box<HandlerFuture>> {
+    let branch = HeaderMap::try_borrow_from(&state)
+        .and_then(|headers| headers.get(CONTENT_TYPE))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Mime>().ok())
+        .and_then(|content_type| handlers.find(&content_type));
+
+    match branch {
+        Some(branch) => branch(state),
+        None => {
+            trace!(
+                "[{}] no handler for the Content-Type of the request body",
+                request_id(&state)
+            );
+
+            let res = create_empty_response(&state, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            future::ok((state, res)).boxed()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{body, Body, Response};
+
+    use crate::handler::{HandlerError, IntoHandlerError};
+    use crate::helpers::http::response::create_response;
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    async fn read_name<F>(
+        mut state: State,
+        format: &'static str,
+        parse: F,
+    ) -> Result<(State, Response<Body>), (State, HandlerError)>
+    where
+        F: FnOnce(&str) -> Option<String>,
+    {
+        let body = match body::to_bytes(Body::take_from(&mut state)).await {
+            Ok(body) => body,
+            Err(e) => return Err((state, e.into_handler_error())),
+        };
+
+        let name = std::str::from_utf8(&body).ok().and_then(parse);
+
+        let res = match name {
+            Some(name) => create_response(
+                &state,
+                StatusCode::OK,
+                mime::TEXT_PLAIN,
+                format!("{}: {}", format, name),
+            ),
+            None => create_empty_response(&state, StatusCode::BAD_REQUEST),
+        };
+
+        Ok((state, res))
+    }
+
+    fn parse_json(body: &str) -> Option<String> {
+        let value: serde_json::Value = serde_json::from_str(body).ok()?;
+        value["name"].as_str().map(str::to_owned)
+    }
+
+    fn parse_xml(body: &str) -> Option<String> {
+        let start = body.find("<name>")? + "<name>".len();
+        let end = body[start..].find("</name>")? + start;
+        Some(body[start..end].to_owned())
+    }
+
+    fn create(state: State) -> Pin<Box<HandlerFuture>> {
+        let handlers = ContentTypeHandlers::new()
+            .on(mime::APPLICATION_JSON, |state| {
+                read_name(state, "json", parse_json).boxed()
+            })
+            .on("application/xml".parse().unwrap(), |state| {
+                read_name(state, "xml", parse_xml).boxed()
+            });
+
+        dispatch_by_content_type(state, &handlers)
+    }
+
+    fn test_server() -> TestServer {
+        TestServer::new(build_simple_router(|route| {
+            route.post("/users").to(create);
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn bodies_are_parsed_by_content_type() {
+        let test_server = test_server();
+
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/users",
+                r#"{"name":"Alice"}"#,
+                "application/json; charset=utf-8".parse::<Mime>().unwrap(),
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "json: Alice");
+
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/users",
+                "<user><name>Bob</name></user>",
+                "application/xml".parse::<Mime>().unwrap(),
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "xml: Bob");
+    }
+
+    #[test]
+    fn unsupported_content_types_are_rejected() {
+        let response = test_server()
+            .client()
+            .post("http://localhost/users", "name\nCarol\n", mime::TEXT_CSV)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
@@ -0,0 +1,551 @@
+//! Defines a validator for JSON request bodies, using a subset of JSON Schema.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::panic::AssertUnwindSafe;
+
+use regex::Regex;
+use serde_derive::Serialize;
+use serde_json::{Map, Value};
+
+use crate::helpers::http::request::body::BodyReadConfig;
+use crate::state::StateData;
+
+/// The default limit on the size of request bodies validated against a `JsonSchema`.
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// The keywords which are checked when validating a value.
+const VALIDATION_KEYWORDS: &[&str] = &[
+    "type",
+    "enum",
+    "const",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "items",
+    "minItems",
+    "maxItems",
+    "properties",
+    "required",
+    "additionalProperties",
+];
+
+/// The keywords which only annotate a schema, and have no effect on validation.
+const ANNOTATION_KEYWORDS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "readOnly",
+    "writeOnly",
+    "deprecated",
+];
+
+/// A JSON Schema, used to validate JSON request bodies via `DefineSingleRoute::validate_json`.
+///
+/// The following keywords are supported:
+///
+/// * `type` (including `integer`), `enum` and `const`;
+/// * `minLength`, `maxLength` and `pattern` for strings;
+/// * `minimum`, `maximum`, `exclusiveMinimum` and `exclusiveMaximum` for numbers;
+/// * `items`, `minItems` and `maxItems` for arrays;
+/// * `properties`, `required` and `additionalProperties` for objects;
+/// * the annotations `$schema`, `$id`, `$comment`, `title`, `description`, `default`,
+///   `examples`, `readOnly`, `writeOnly` and `deprecated`, which do not affect validation.
+///
+/// A schema using any other keyword (such as `$ref`, `allOf` or `format`) is rejected by
+/// `JsonSchema::new`, rather than validating less than the schema describes.
+///
+/// Request bodies larger than 1MiB are rejected with `413 Payload Too Large` before they are
+/// validated. The limit, and how the body is buffered, can be changed via
+/// `JsonSchema::with_body_config`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// #
+/// # use gotham::helpers::http::request::json_schema::JsonSchema;
+/// #
+/// # fn main() {
+/// let schema = JsonSchema::new(json!({
+///     "type": "object",
+///     "required": ["name"],
+///     "properties": {
+///         "name": { "type": "string", "minLength": 1 },
+///         "age": { "type": "integer", "minimum": 0 }
+///     }
+/// }))
+/// .unwrap();
+///
+/// assert!(schema.validate(&json!({ "name": "Alice", "age": 30 })).is_ok());
+///
+/// let errors = schema.validate(&json!({ "age": -1 })).unwrap_err();
+/// assert_eq!(errors.len(), 2);
+/// assert_eq!(errors[0].path(), "");
+/// assert_eq!(errors[1].path(), "/age");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct JsonSchema {
+    schema: Value,
+    patterns: HashMap<String, AssertUnwindSafe<Regex>>,
+    body_config: BodyReadConfig,
+}
+
+impl JsonSchema {
+    /// Creates a new `JsonSchema`, returning an error if the schema is malformed or uses an
+    /// unsupported keyword.
+    pub fn new(schema: Value) -> Result<JsonSchema, InvalidSchema> {
+        let mut patterns = HashMap::new();
+        compile(&schema, &mut patterns)?;
+        Ok(JsonSchema {
+            schema,
+            patterns,
+            body_config: BodyReadConfig::default().with_max_size(DEFAULT_MAX_BODY_SIZE),
+        })
+    }
+
+    /// Sets the `BodyReadConfig` used to read request bodies validated against this schema via
+    /// `DefineSingleRoute::validate_json`. A limit on the body size should be set via
+    /// `BodyReadConfig::with_max_size`, as the whole body is read before it is validated.
+    pub fn with_body_config(self, body_config: BodyReadConfig) -> JsonSchema {
+        JsonSchema {
+            body_config,
+            ..self
+        }
+    }
+
+    pub(crate) fn body_config(&self) -> &BodyReadConfig {
+        &self.body_config
+    }
+
+    /// Validates `value` against the schema, returning every violation found.
+    pub fn validate(&self, value: &Value) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        self.check(&self.schema, value, "", &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn check(&self, schema: &Value, value: &Value, path: &str, errors: &mut Vec<ValidationError>) {
+        let schema = match *schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                return errors.push(ValidationError::new(path, "no value is allowed".to_owned()))
+            }
+            Value::Object(ref schema) => schema,
+            _ => return,
+        };
+
+        let mut fail = |message: String| errors.push(ValidationError::new(path, message));
+
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match *expected {
+                Value::String(ref t) => vec![t.as_str()],
+                Value::Array(ref ts) => ts.iter().filter_map(Value::as_str).collect(),
+                _ => vec![],
+            };
+
+            if !types.iter().any(|t| is_type(value, t)) {
+                return fail(format!(
+                    "expected {}, found {}",
+                    types.join(" or "),
+                    type_name(value)
+                ));
+            }
+        }
+
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            if !values.contains(value) {
+                fail(format!("must be one of {}", Value::Array(values.clone())));
+            }
+        }
+
+        if let Some(constant) = schema.get("const") {
+            if value != constant {
+                fail(format!("must be {}", constant));
+            }
+        }
+
+        match *value {
+            Value::String(ref s) => {
+                let len = s.chars().count() as u64;
+
+                if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                    if len < min {
+                        fail(format!("must be at least {} characters long", min));
+                    }
+                }
+
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                    if len > max {
+                        fail(format!("must be at most {} characters long", max));
+                    }
+                }
+
+                if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                    if !self.patterns[pattern].is_match(s) {
+                        fail(format!("must match the pattern {}", pattern));
+                    }
+                }
+            }
+            Value::Number(ref n) => {
+                let n = n.as_f64().unwrap_or(0.0);
+                let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+
+                if let Some(min) = bound("minimum") {
+                    if n < min {
+                        fail(format!("must be at least {}", min));
+                    }
+                }
+
+                if let Some(max) = bound("maximum") {
+                    if n > max {
+                        fail(format!("must be at most {}", max));
+                    }
+                }
+
+                if let Some(min) = bound("exclusiveMinimum") {
+                    if n <= min {
+                        fail(format!("must be greater than {}", min));
+                    }
+                }
+
+                if let Some(max) = bound("exclusiveMaximum") {
+                    if n >= max {
+                        fail(format!("must be less than {}", max));
+                    }
+                }
+            }
+            Value::Array(ref items) => {
+                let len = items.len() as u64;
+
+                if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                    if len < min {
+                        fail(format!("must have at least {} items", min));
+                    }
+                }
+
+                if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                    if len > max {
+                        fail(format!("must have at most {} items", max));
+                    }
+                }
+
+                if let Some(item_schema) = schema.get("items") {
+                    for (i, item) in items.iter().enumerate() {
+                        let item_path = format!("{}/{}", path, i);
+                        self.check(item_schema, item, &item_path, errors);
+                    }
+                }
+            }
+            Value::Object(ref object) => self.check_object(schema, object, path, errors),
+            _ => {}
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    errors.push(ValidationError::new(
+                        path,
+                        format!("missing required property {}", name),
+                    ));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties");
+
+        for (name, value) in object {
+            let property_path = format!("{}/{}", path, escape_pointer(name));
+
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property_schema) => self.check(property_schema, value, &property_path, errors),
+                None => match additional {
+                    Some(&Value::Bool(false)) => errors.push(ValidationError::new(
+                        &property_path,
+                        "additional properties are not allowed".to_owned(),
+                    )),
+                    Some(additional) => self.check(additional, value, &property_path, errors),
+                    None => {}
+                },
+            }
+        }
+    }
+}
+
+/// Compiles the patterns used within `schema`, returning an error if the schema is malformed or
+/// uses an unsupported keyword.
+fn compile(
+    schema: &Value,
+    patterns: &mut HashMap<String, AssertUnwindSafe<Regex>>,
+) -> Result<(), InvalidSchema> {
+    let schema = match *schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(ref schema) => schema,
+        _ => {
+            return Err(InvalidSchema::new(
+                "a schema must be an object or a boolean",
+            ))
+        }
+    };
+
+    for keyword in schema.keys() {
+        if !VALIDATION_KEYWORDS.contains(&keyword.as_str())
+            && !ANNOTATION_KEYWORDS.contains(&keyword.as_str())
+        {
+            return Err(InvalidSchema::new(&format!(
+                "unsupported keyword {}",
+                keyword
+            )));
+        }
+    }
+
+    if let Some(pattern) = schema.get("pattern") {
+        let pattern = pattern
+            .as_str()
+            .ok_or_else(|| InvalidSchema::new("pattern must be a string"))?;
+
+        let regex = Regex::new(pattern).map_err(|e| InvalidSchema::new(&e.to_string()))?;
+        patterns.insert(pattern.to_owned(), AssertUnwindSafe(regex));
+    }
+
+    if let Some(items) = schema.get("items") {
+        compile(items, patterns)?;
+    }
+
+    if let Some(properties) = schema.get("properties") {
+        let properties = properties
+            .as_object()
+            .ok_or_else(|| InvalidSchema::new("properties must be an object"))?;
+
+        for property in properties.values() {
+            compile(property, patterns)?;
+        }
+    }
+
+    if let Some(additional) = schema.get("additionalProperties") {
+        compile(additional, patterns)?;
+    }
+
+    Ok(())
+}
+
+fn is_type(value: &Value, t: &str) -> bool {
+    match (t, value) {
+        ("null", &Value::Null)
+        | ("boolean", &Value::Bool(_))
+        | ("number", &Value::Number(_))
+        | ("string", &Value::String(_))
+        | ("array", &Value::Array(_))
+        | ("object", &Value::Object(_)) => true,
+        ("integer", &Value::Number(ref n)) => {
+            n.is_i64() || n.is_u64() || n.as_f64().map(|f| f.fract() == 0.0).unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match *value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Escapes a property name for use in a JSON pointer.
+fn escape_pointer(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+/// Describes a value which does not conform to a `JsonSchema`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ValidationError {
+    path: String,
+    message: String,
+}
+
+impl ValidationError {
+    pub(crate) fn new(path: &str, message: String) -> ValidationError {
+        ValidationError {
+            path: path.to_owned(),
+            message,
+        }
+    }
+
+    /// The location of the invalid value, as a JSON pointer (e.g. `/users/0/name`). The document
+    /// itself is located at the empty pointer.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Describes why the value is invalid.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Returned from `JsonSchema::new` when the schema is malformed.
+#[derive(Debug)]
+pub struct InvalidSchema {
+    reason: String,
+}
+
+impl InvalidSchema {
+    fn new(reason: &str) -> InvalidSchema {
+        InvalidSchema {
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+impl Display for InvalidSchema {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "invalid JSON schema: {}", self.reason)
+    }
+}
+
+impl Error for InvalidSchema {}
+
+/// A JSON request body which has been validated against a `JsonSchema`, stored in `State` by
+/// `DefineSingleRoute::validate_json` before the handler is invoked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidatedJson {
+    value: Value,
+}
+
+impl StateData for ValidatedJson {}
+
+impl ValidatedJson {
+    pub(crate) fn new(value: Value) -> ValidatedJson {
+        ValidatedJson { value }
+    }
+
+    /// The parsed request body.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Takes ownership of the parsed request body.
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn paths(schema: &Value, value: &Value) -> Vec<String> {
+        JsonSchema::new(schema.clone())
+            .unwrap()
+            .validate(value)
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|e| e.path().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn validates_types() {
+        let schema = json!({ "type": ["integer", "null"] });
+
+        assert!(paths(&schema, &json!(1)).is_empty());
+        assert!(paths(&schema, &json!(1.0)).is_empty());
+        assert!(paths(&schema, &json!(null)).is_empty());
+        assert_eq!(paths(&schema, &json!(1.5)), vec![""]);
+        assert_eq!(paths(&schema, &json!("1")), vec![""]);
+    }
+
+    #[test]
+    fn validates_nested_values() {
+        let schema = json!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "tags": {
+                    "type": "array",
+                    "maxItems": 2,
+                    "items": { "type": "string", "pattern": "^[a-z]+$" }
+                },
+                "a/b": { "enum": [1, 2] }
+            }
+        });
+
+        assert!(paths(&schema, &json!({ "tags": ["x", "y"], "a/b": 2 })).is_empty());
+        assert_eq!(
+            paths(
+                &schema,
+                &json!({ "tags": ["x", "Y", "z"], "a/b": 3, "other": true })
+            ),
+            vec!["/a~1b", "/other", "/tags", "/tags/1"]
+        );
+    }
+
+    #[test]
+    fn validates_bounds() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "minLength": 2, "maxLength": 3 },
+                "age": { "minimum": 0, "exclusiveMaximum": 150 }
+            }
+        });
+
+        assert!(paths(&schema, &json!({ "name": "Al", "age": 0 })).is_empty());
+        assert_eq!(
+            paths(&schema, &json!({ "name": "A", "age": 150 })),
+            vec!["/age", "/name"]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_schemas() {
+        assert!(JsonSchema::new(json!("string")).is_err());
+        assert!(JsonSchema::new(json!({ "pattern": "(" })).is_err());
+        assert!(JsonSchema::new(json!({ "properties": { "a": { "pattern": 1 } } })).is_err());
+        assert!(JsonSchema::new(json!(true)).is_ok());
+    }
+
+    #[test]
+    fn rejects_unsupported_keywords() {
+        assert!(JsonSchema::new(json!({ "$ref": "#/definitions/user" })).is_err());
+        assert!(JsonSchema::new(json!({ "items": { "anyOf": [true, false] } })).is_err());
+        assert!(
+            JsonSchema::new(json!({ "properties": { "email": { "format": "email" } } })).is_err()
+        );
+        assert!(JsonSchema::new(
+            json!({ "title": "User", "description": "A user", "type": "object" })
+        )
+        .is_ok());
+    }
+}
@@ -1,4 +1,7 @@
 //! Helpers for HTTP request handling
 
+pub mod body;
+pub mod content_type;
+pub mod json_schema;
 pub mod path;
 pub mod query_string;
@@ -35,6 +35,14 @@ pub(crate) fn split<'r>(query: Option<&'r str>) -> QueryStringMapping {
     query_string_mapping
 }
 
+/// Counts the parameters in a query string, including those provided without a value.
+pub(crate) fn count(query: &str) -> usize {
+    query
+        .split(is_separator)
+        .filter(|pair| !pair.is_empty())
+        .count()
+}
+
 fn is_separator(c: char) -> bool {
     c == '&' || c == ';'
 }
@@ -84,4 +92,12 @@ mod tests {
         let qsm = split(Some("a=b=c&d=e"));
         assert_eq!(to_pairs(&qsm), vec![("a", vec!["b=c"]), ("d", vec!["e"])],);
     }
+
+    #[test]
+    fn query_string_count_tests() {
+        assert_eq!(count(""), 0);
+        assert_eq!(count("a=b&c=d;e=f"), 3);
+        assert_eq!(count("a=b&a=d"), 2);
+        assert_eq!(count("a&b=&&c"), 3);
+    }
 }
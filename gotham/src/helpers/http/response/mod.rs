@@ -6,7 +6,77 @@ use mime::Mime;
 use std::borrow::Cow;
 
 use crate::helpers::http::header::X_REQUEST_ID;
-use crate::state::{request_id, FromState, State};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// Whether the body of a text response created by `create_text_response` ends with a newline.
+///
+/// Some clients and tools expect text, such as `robots.txt` or plain text error messages, to be
+/// terminated by a newline. The behaviour can be chosen per call via `create_text_response_with`,
+/// or for every request passing through a pipeline by storing a value in `State` via
+/// `StateMiddleware`:
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::{create_text_response, TrailingNewline};
+/// # use gotham::middleware::state::StateMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn robots(state: State) -> (State, Response<Body>) {
+///     let res = create_text_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "User-agent: *");
+///     (state, res)
+/// }
+///
+/// # fn main() {
+/// let pipeline = new_pipeline()
+///     .add(StateMiddleware::new(TrailingNewline::Ensure))
+///     .build();
+/// let (chain, pipelines) = single_pipeline(pipeline);
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/robots.txt").to(robots);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://example.com/robots.txt")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.read_utf8_body().unwrap(), "User-agent: *\n");
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrailingNewline {
+    /// Appends a newline to bodies which do not already end with one.
+    Ensure,
+    /// Leaves bodies unchanged. This is the default.
+    Preserve,
+}
+
+impl StateData for TrailingNewline {}
+
+impl Default for TrailingNewline {
+    fn default() -> TrailingNewline {
+        TrailingNewline::Preserve
+    }
+}
+
+impl TrailingNewline {
+    fn apply(self, mut body: String) -> String {
+        if self == TrailingNewline::Ensure && !body.ends_with('\n') {
+            body.push('\n');
+        }
+        body
+    }
+}
 
 /// Creates a `Response` object and populates it with a set of default headers that help to improve
 /// security and conformance to best practice.
@@ -82,6 +152,79 @@ where
     res
 }
 
+/// Creates a `Response` with a text body, as `create_response` does, ending the body with a
+/// newline if the `TrailingNewline` value in `State` is `Ensure`.
+///
+/// When no `TrailingNewline` value is present in `State`, the body is left unchanged. The
+/// `Content-Length` header reflects the final body.
+pub fn create_text_response<B>(
+    state: &State,
+    status: StatusCode,
+    mime: Mime,
+    body: B,
+) -> Response<Body>
+where
+    B: Into<String>,
+{
+    let trailing_newline = TrailingNewline::try_borrow_from(state)
+        .copied()
+        .unwrap_or_default();
+
+    create_text_response_with(state, status, mime, body, trailing_newline)
+}
+
+/// Creates a `Response` with a text body, as `create_text_response` does, but with the provided
+/// `TrailingNewline` behaviour in place of any value in `State`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::{create_text_response_with, TrailingNewline};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let res = create_text_response_with(
+///         &state,
+///         StatusCode::NOT_FOUND,
+///         mime::TEXT_PLAIN,
+///         "no such widget",
+///         TrailingNewline::Ensure,
+///     );
+///
+///     (state, res)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::NOT_FOUND);
+/// #     assert_eq!(response.read_utf8_body().unwrap(), "no such widget\n");
+/// # }
+/// ```
+pub fn create_text_response_with<B>(
+    state: &State,
+    status: StatusCode,
+    mime: Mime,
+    body: B,
+    trailing_newline: TrailingNewline,
+) -> Response<Body>
+where
+    B: Into<String>,
+{
+    create_response(state, status, mime, trailing_newline.apply(body.into()))
+}
+
 /// Produces a simple empty `Response` with a provided status.
 ///
 /// # Examples
@@ -210,3 +353,87 @@ pub fn create_temporary_redirect<L: Into<Cow<'static, str>>>(
         .insert(LOCATION, location.into().to_string().parse().unwrap());
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+    use hyper::body::{self, HttpBody};
+
+    use crate::state::set_request_id;
+
+    fn state_with(trailing_newline: Option<TrailingNewline>) -> State {
+        let mut state = State::new();
+        state.put(Method::GET);
+        set_request_id(&mut state);
+
+        if let Some(trailing_newline) = trailing_newline {
+            state.put(trailing_newline);
+        }
+
+        state
+    }
+
+    fn body_of(res: Response<Body>) -> (Option<u64>, String) {
+        let len = res.body().size_hint().exact();
+        let body = block_on(body::to_bytes(res.into_body())).unwrap();
+        (len, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn text(state: &State, body: &str) -> (Option<u64>, String) {
+        body_of(create_text_response(
+            state,
+            StatusCode::OK,
+            mime::TEXT_PLAIN,
+            body,
+        ))
+    }
+
+    #[test]
+    fn trailing_newline_added_when_enabled() {
+        let state = state_with(Some(TrailingNewline::Ensure));
+        assert_eq!(
+            text(&state, "Disallow: /"),
+            (Some(12), "Disallow: /\n".into())
+        );
+
+        let state = state_with(None);
+        let res = create_text_response_with(
+            &state,
+            StatusCode::OK,
+            mime::TEXT_PLAIN,
+            "Disallow: /",
+            TrailingNewline::Ensure,
+        );
+        assert_eq!(body_of(res), (Some(12), "Disallow: /\n".into()));
+    }
+
+    #[test]
+    fn body_unchanged_when_disabled() {
+        let state = state_with(None);
+        assert_eq!(
+            text(&state, "Disallow: /"),
+            (Some(11), "Disallow: /".into())
+        );
+
+        let state = state_with(Some(TrailingNewline::Ensure));
+        let res = create_text_response_with(
+            &state,
+            StatusCode::OK,
+            mime::TEXT_PLAIN,
+            "Disallow: /",
+            TrailingNewline::Preserve,
+        );
+        assert_eq!(body_of(res), (Some(11), "Disallow: /".into()));
+    }
+
+    #[test]
+    fn body_unchanged_when_newline_terminated() {
+        let state = state_with(Some(TrailingNewline::Ensure));
+        assert_eq!(
+            text(&state, "Disallow: /\n"),
+            (Some(12), "Disallow: /\n".into())
+        );
+    }
+}
@@ -1,4 +1,5 @@
 //! Helpers, e.g. for HTTP request handling and response generation
 
+pub mod eviction;
 pub mod http;
 pub(crate) mod timing;
@@ -25,13 +25,17 @@
 // TODO: Remove this when it's a hard error by default (error E0446).
 // See Rust issue #34537 <https://github.com/rust-lang/rust/issues/34537>
 #![deny(private_in_public)]
+pub mod connection;
 pub mod error;
 pub mod extractor;
 pub mod handler;
 pub mod helpers;
+#[cfg(unix)]
+pub mod listener;
 pub mod middleware;
 pub mod pipeline;
 pub mod router;
+pub mod server;
 mod service;
 pub mod state;
 
@@ -49,15 +53,13 @@ pub mod tls;
 pub use hyper;
 
 use futures::prelude::*;
-use hyper::server::conn::Http;
 use std::net::ToSocketAddrs;
-use std::sync::Arc;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream};
 
 use tokio::runtime::{self, Runtime};
 
-use crate::{handler::NewHandler, service::GothamService};
+use crate::{handler::NewHandler, server::ServerConfig};
 
 pub use plain::*;
 #[cfg(feature = "rustls")]
@@ -93,7 +95,7 @@ where
 /// the socket as necessary. Errors returned by this function will be ignored and the connection
 /// will be dropped if the future returned by the wrapper resolves to an error.
 pub async fn bind_server<'a, NH, F, Wrapped, Wrap>(
-    mut listener: TcpListener,
+    listener: TcpListener,
     new_handler: NH,
     wrap: Wrap,
 ) -> Result<(), ()>
@@ -103,29 +105,7 @@ where
     Wrapped: Unpin + AsyncRead + AsyncWrite + Send + 'static,
     Wrap: Fn(TcpStream) -> F,
 {
-    let protocol = Arc::new(Http::new());
-    let gotham_service = GothamService::new(new_handler);
-
-    listener
-        .incoming()
-        .map_err(|e| panic!("socket error = {:?}", e))
-        .try_for_each_concurrent(None, |socket| {
-            let addr = socket.peer_addr().unwrap();
-            let service = gotham_service.connect(addr);
-            let accepted_protocol = protocol.clone();
-            let wrapper = wrap(socket);
-
-            async move {
-                // NOTE: HTTP protocol errors and handshake errors are ignored here (i.e. so the socket
-                // will be dropped).
-                let socket = wrapper.await?;
-                accepted_protocol
-                    .serve_connection(socket, service)
-                    .map_err(|_| ())
-                    .await?;
-
-                Ok(())
-            }
-        })
+    ServerConfig::default()
+        .bind_server(listener, new_handler, wrap)
         .await
 }
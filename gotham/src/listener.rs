@@ -0,0 +1,202 @@
+//! Defines support for serving from a listening socket inherited from another process.
+//!
+//! This allows a new version of an application to take over the listening socket of the running
+//! version (or of a supervisor such as systemd), so that connections are not refused while the
+//! application is restarted. The socket is passed using the `LISTEN_FDS` protocol, where the
+//! parent process leaves the socket open as file descriptor `3`, and sets the `LISTEN_FDS`
+//! environment variable to the number of sockets passed and `LISTEN_PID` to the process ID of the
+//! child.
+
+use std::env;
+use std::io;
+use std::mem;
+use std::net::TcpListener;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The first file descriptor passed via the `LISTEN_FDS` protocol.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Whether the inherited socket has been taken, so that it is only ever owned once.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Returns the listening socket inherited from the parent process, or `None` if no socket was
+/// passed to this process.
+///
+/// The socket is only returned by the first call; the `LISTEN_FDS` and `LISTEN_PID` environment
+/// variables are removed once it has been taken, so that it is not passed on to child processes.
+/// An error is returned if the inherited descriptor is not a stream socket.
+///
+/// The returned listener can be served using `gotham::server::ServerConfig::start_with_listener`.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::server::ServerConfig;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #     (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let listener = match gotham::listener::inherited().unwrap() {
+///     Some(listener) => listener,
+///     None => std::net::TcpListener::bind("127.0.0.1:7878").unwrap(),
+/// };
+///
+/// ServerConfig::default().start_with_listener(listener, || Ok(handler));
+/// # }
+/// ```
+pub fn inherited() -> io::Result<Option<TcpListener>> {
+    let fds = env::var("LISTEN_FDS").ok();
+    let pid = env::var("LISTEN_PID").ok();
+
+    let fd = match listen_fd(
+        fds.as_ref().map(String::as_str),
+        pid.as_ref().map(String::as_str),
+    )? {
+        Some(fd) => fd,
+        None => return Ok(None),
+    };
+
+    if !is_stream_socket(fd)? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "inherited file descriptor is not a stream socket",
+        ));
+    }
+
+    if TAKEN.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_PID");
+
+    // the descriptor was passed to this process, and `TAKEN` ensures it has a single owner
+    Ok(Some(unsafe { TcpListener::from_raw_fd(fd) }))
+}
+
+/// Determines whether `fd` refers to a stream socket, failing if it is not a socket at all.
+fn is_stream_socket(fd: RawFd) -> io::Result<bool> {
+    let mut socket_type: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            &mut socket_type as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(socket_type == libc::SOCK_STREAM)
+}
+
+/// Determines the file descriptor of the inherited socket from the values of the `LISTEN_FDS` and
+/// `LISTEN_PID` environment variables.
+fn listen_fd(fds: Option<&str>, pid: Option<&str>) -> io::Result<Option<RawFd>> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+    let pid: u32 = match pid {
+        Some(pid) => pid.parse().map_err(|_| invalid("invalid LISTEN_PID"))?,
+        None => return Ok(None),
+    };
+
+    // the sockets were passed to another process, which has passed on its environment
+    if pid != process::id() {
+        return Ok(None);
+    }
+
+    let fds: u32 = match fds {
+        Some(fds) => fds.parse().map_err(|_| invalid("invalid LISTEN_FDS"))?,
+        None => return Ok(None),
+    };
+
+    match fds {
+        0 => Ok(None),
+        1 => Ok(Some(LISTEN_FDS_START)),
+        _ => Err(invalid("only a single inherited socket is supported")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::net::{TcpStream, UdpSocket};
+    use std::os::unix::io::AsRawFd;
+
+    use hyper::{Body, Response, StatusCode};
+
+    use crate::helpers::http::response::create_response;
+    use crate::server::ServerConfig;
+    use crate::state::State;
+
+    #[test]
+    fn listen_fd_follows_protocol() {
+        let pid = process::id().to_string();
+
+        assert_eq!(listen_fd(None, None).unwrap(), None);
+        assert_eq!(listen_fd(Some("0"), Some(&pid)).unwrap(), None);
+        assert_eq!(listen_fd(Some("1"), Some(&pid)).unwrap(), Some(3));
+        assert_eq!(listen_fd(Some("1"), None).unwrap(), None);
+
+        let other = (process::id() + 1).to_string();
+        assert_eq!(listen_fd(Some("1"), Some(&other)).unwrap(), None);
+
+        assert!(listen_fd(Some("2"), Some(&pid)).is_err());
+        assert!(listen_fd(Some("one"), Some(&pid)).is_err());
+        assert!(listen_fd(Some("1"), Some("me")).is_err());
+    }
+
+    #[test]
+    fn only_stream_sockets_are_accepted() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(is_stream_socket(listener.as_raw_fd()).unwrap());
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(!is_stream_socket(socket.as_raw_fd()).unwrap());
+
+        let file = File::open("Cargo.toml").unwrap();
+        assert!(is_stream_socket(file.as_raw_fd()).is_err());
+    }
+
+    #[test]
+    fn serves_requests_from_pre_bound_listener() {
+        fn handler(state: State) -> (State, Response<Body>) {
+            let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "handed over");
+            (state, res)
+        }
+
+        // stands in for the socket passed by the parent process
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.spawn(ServerConfig::default().init_server_with_listener(listener, || Ok(handler)));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("handed over"));
+    }
+}
@@ -0,0 +1,476 @@
+//! Defines a middleware which provides each request with an `Arena` of reusable buffers.
+//!
+//! Handlers which build many short-lived strings or byte buffers can take them from the `Arena`,
+//! rather than the global allocator. The buffers are cleared when the request completes, and the
+//! `Arena` is kept by the worker thread for the next request, so their capacity is reused.
+//!
+//! Despite the name, this is a pool of buffers rather than a bump allocator: each buffer is still
+//! allocated by the global allocator the first time it is needed, and the saving comes from
+//! reusing it for later requests.
+use std::cell::RefCell;
+use std::io;
+use std::pin::Pin;
+
+use futures::prelude::*;
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, State, StateData};
+
+/// The default capacity of a buffer allocated by an `Arena`.
+const DEFAULT_BUFFER_CAPACITY: usize = 64;
+
+/// The default number of buffers retained by an `Arena` between requests.
+const DEFAULT_MAX_RETAINED: usize = 256;
+
+/// The default capacity above which a recycled buffer is freed rather than retained.
+const DEFAULT_MAX_BUFFER_CAPACITY: usize = 4 * 1024;
+
+/// The maximum number of arenas kept by each worker thread. A request may complete on a different
+/// thread to the one it started on, so without a limit a thread could accumulate arenas
+/// indefinitely.
+const MAX_POOLED_ARENAS: usize = 16;
+
+thread_local! {
+    static ARENAS: RefCell<Vec<Arena>> = RefCell::new(Vec::new());
+}
+
+/// A pool of reusable buffers for transient allocations, stored in `State` by `ArenaMiddleware`.
+///
+/// Buffers taken with `string` or `bytes` are empty, but retain the capacity they had when they
+/// were last returned with `recycle_string` or `recycle_bytes`. Returning buffers is optional;
+/// buffers which are not returned are freed as normal, as are returned buffers which have grown
+/// beyond the configured maximum capacity.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use std::fmt::Write;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::middleware::arena::{Arena, ArenaMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(mut state: State) -> (State, Response<Body>) {
+///     let mut body = String::new();
+///     {
+///         let arena = Arena::borrow_mut_from(&mut state);
+///         for i in 0..3 {
+///             let mut line = arena.string();
+///             writeln!(line, "line {}", i).unwrap();
+///             body.push_str(&line);
+///             arena.recycle_string(line);
+///         }
+///     }
+///
+///     let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+///     (state, res)
+/// }
+///
+/// # fn main() {
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(ArenaMiddleware::new()).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("https://example.com/")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.read_utf8_body().unwrap(), "line 0\nline 1\nline 2\n");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Arena {
+    strings: Vec<String>,
+    bytes: Vec<Vec<u8>>,
+    buffer_capacity: usize,
+    max_retained: usize,
+    max_buffer_capacity: usize,
+    allocated: usize,
+}
+
+impl StateData for Arena {}
+
+impl Arena {
+    fn new(buffer_capacity: usize, max_retained: usize, max_buffer_capacity: usize) -> Arena {
+        Arena {
+            strings: Vec::new(),
+            bytes: Vec::new(),
+            buffer_capacity,
+            max_retained,
+            max_buffer_capacity,
+            allocated: 0,
+        }
+    }
+
+    /// Takes an empty `String` from the arena, allocating a new one if none are available.
+    pub fn string(&mut self) -> String {
+        match self.strings.pop() {
+            Some(s) => s,
+            None => {
+                self.allocated += 1;
+                String::with_capacity(self.buffer_capacity)
+            }
+        }
+    }
+
+    /// Takes an empty `Vec<u8>` from the arena, allocating a new one if none are available.
+    pub fn bytes(&mut self) -> Vec<u8> {
+        match self.bytes.pop() {
+            Some(b) => b,
+            None => {
+                self.allocated += 1;
+                Vec::with_capacity(self.buffer_capacity)
+            }
+        }
+    }
+
+    /// Returns a `String` to the arena, so that its capacity can be reused.
+    pub fn recycle_string(&mut self, mut s: String) {
+        if self.strings.len() < self.max_retained && s.capacity() <= self.max_buffer_capacity {
+            s.clear();
+            self.strings.push(s);
+        }
+    }
+
+    /// Returns a `Vec<u8>` to the arena, so that its capacity can be reused.
+    pub fn recycle_bytes(&mut self, mut b: Vec<u8>) {
+        if self.bytes.len() < self.max_retained && b.capacity() <= self.max_buffer_capacity {
+            b.clear();
+            self.bytes.push(b);
+        }
+    }
+
+    /// Resets the arena at the end of a request, retaining its buffers for the next request.
+    fn reset(&mut self) {
+        self.allocated = 0;
+        self.strings.truncate(self.max_retained);
+        self.bytes.truncate(self.max_retained);
+    }
+}
+
+/// Middleware binding which stores an `Arena` in `State` for each request.
+///
+/// Each worker thread keeps the `Arena` of a completed request for use by its next request, so
+/// buffers recycled by one request are available to those which follow. A worker thread keeps at
+/// most 16 arenas, and frees any others.
+#[derive(Clone)]
+pub struct ArenaMiddleware {
+    buffer_capacity: usize,
+    max_retained: usize,
+    max_buffer_capacity: usize,
+}
+
+impl ArenaMiddleware {
+    /// Creates a new middleware binding, with buffers of 64 bytes and up to 256 buffers of each
+    /// type retained between requests. Buffers which grow beyond 4KiB are not retained.
+    pub fn new() -> ArenaMiddleware {
+        ArenaMiddleware {
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            max_retained: DEFAULT_MAX_RETAINED,
+            max_buffer_capacity: DEFAULT_MAX_BUFFER_CAPACITY,
+        }
+    }
+
+    /// Sets the capacity of newly allocated buffers.
+    pub fn buffer_capacity(self, buffer_capacity: usize) -> ArenaMiddleware {
+        ArenaMiddleware {
+            buffer_capacity,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of buffers of each type retained between requests.
+    pub fn max_retained(self, max_retained: usize) -> ArenaMiddleware {
+        ArenaMiddleware {
+            max_retained,
+            ..self
+        }
+    }
+
+    /// Sets the capacity above which a recycled buffer is freed rather than retained, so that a
+    /// single large request doesn't pin its memory for the lifetime of the worker.
+    pub fn max_buffer_capacity(self, max_buffer_capacity: usize) -> ArenaMiddleware {
+        ArenaMiddleware {
+            max_buffer_capacity,
+            ..self
+        }
+    }
+
+    fn acquire(&self) -> Arena {
+        let arena = ARENAS.with(|arenas| arenas.borrow_mut().pop());
+
+        match arena {
+            Some(arena) => Arena {
+                buffer_capacity: self.buffer_capacity,
+                max_retained: self.max_retained,
+                max_buffer_capacity: self.max_buffer_capacity,
+                ..arena
+            },
+            None => Arena::new(
+                self.buffer_capacity,
+                self.max_retained,
+                self.max_buffer_capacity,
+            ),
+        }
+    }
+}
+
+impl Default for ArenaMiddleware {
+    fn default() -> ArenaMiddleware {
+        ArenaMiddleware::new()
+    }
+}
+
+fn release(state: &mut State) {
+    if let Some(mut arena) = state.try_take::<Arena>() {
+        trace!(
+            "[{}] releasing arena, {} buffers allocated",
+            request_id(state),
+            arena.allocated
+        );
+
+        arena.reset();
+        ARENAS.with(|arenas| {
+            let mut arenas = arenas.borrow_mut();
+            if arenas.len() < MAX_POOLED_ARENAS {
+                arenas.push(arena);
+            }
+        });
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for ArenaMiddleware {
+    /// Stores an `Arena` in `State` before the chain is invoked, and keeps it for the next
+    /// request once the response is complete.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        state.put(self.acquire());
+
+        chain(state)
+            .then(|mut result| {
+                match result {
+                    Ok((ref mut state, _)) | Err((ref mut state, _)) => release(state),
+                }
+                future::ready(result)
+            })
+            .boxed()
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for ArenaMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::fmt::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use hyper::{HeaderMap, StatusCode};
+
+    use crate::helpers::http::response::create_empty_response;
+    use crate::state::{set_request_id, FromState};
+
+    /// Wraps the system allocator, counting the allocations made by each thread while counting
+    /// is enabled via `count_allocations`.
+    struct CountingAllocator;
+
+    thread_local! {
+        static COUNTING: Cell<bool> = Cell::new(false);
+        static ALLOCATIONS: Cell<usize> = Cell::new(0);
+    }
+
+    fn record_allocation() {
+        let _ = COUNTING.try_with(|counting| {
+            if counting.get() {
+                ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+            }
+        });
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            record_allocation();
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            record_allocation();
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Runs `f`, returning its result and the number of allocations made by the current thread.
+    fn count_allocations<F, T>(f: F) -> (T, usize)
+    where
+        F: FnOnce() -> T,
+    {
+        ALLOCATIONS.with(|allocations| allocations.set(0));
+        COUNTING.with(|counting| counting.set(true));
+        let result = f();
+        COUNTING.with(|counting| counting.set(false));
+        (result, ALLOCATIONS.with(Cell::get))
+    }
+
+    fn clear_arenas() {
+        ARENAS.with(|arenas| arenas.borrow_mut().clear());
+    }
+
+    /// Builds the 100 strings written by `build_strings`, taking each from `next`.
+    fn write_lines<F>(mut next: F) -> Vec<String>
+    where
+        F: FnMut() -> String,
+    {
+        let mut lines = Vec::with_capacity(100);
+        for i in 0..100 {
+            let mut line = next();
+            write!(line, "item {} of 100", i).unwrap();
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Handles a request which builds many small strings, taking each from the arena, and returns
+    /// the number of heap allocations made to do so.
+    fn build_strings(middleware: &ArenaMiddleware) -> usize {
+        let allocated = Arc::new(AtomicUsize::new(0));
+        let allocated_by_handler = allocated.clone();
+
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+
+        let result =
+            futures::executor::block_on(middleware.clone().call(state, move |mut state| {
+                {
+                    let arena = Arena::borrow_mut_from(&mut state);
+                    let (lines, allocations) = count_allocations(|| write_lines(|| arena.string()));
+
+                    for line in lines {
+                        arena.recycle_string(line);
+                    }
+
+                    allocated_by_handler.store(allocations, Ordering::SeqCst);
+                }
+
+                let res = create_empty_response(&state, StatusCode::OK);
+                future::ok((state, res)).boxed()
+            }));
+
+        match result {
+            Ok((state, _)) => assert!(!state.has::<Arena>()),
+            Err(_) => panic!("handler failed"),
+        }
+
+        allocated.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn buffers_are_reused_across_requests() {
+        clear_arenas();
+        let middleware = ArenaMiddleware::new();
+
+        // Building the strings without the arena allocates each of them (and grows most), as well
+        // as the `Vec` holding them.
+        let (_, baseline) = count_allocations(|| write_lines(String::new));
+        assert!(baseline > 100);
+
+        // Only the first request allocates buffers, which are large enough to never be grown;
+        // those which follow on the same worker reuse them, only allocating the `Vec`.
+        assert_eq!(build_strings(&middleware), 101);
+        for _ in 0..10 {
+            assert_eq!(build_strings(&middleware), 1);
+        }
+
+        let mut arena = middleware.acquire();
+        let s = arena.string();
+        assert!(s.is_empty());
+        assert!(s.capacity() >= DEFAULT_BUFFER_CAPACITY);
+    }
+
+    #[test]
+    fn retained_buffers_are_limited() {
+        let mut arena = Arena::new(8, 2, 64);
+        let buffers: Vec<Vec<u8>> = (0..5).map(|_| arena.bytes()).collect();
+
+        for b in buffers {
+            arena.recycle_bytes(b);
+        }
+
+        assert_eq!(arena.bytes.len(), 2);
+        assert_eq!(arena.allocated, 5);
+    }
+
+    #[test]
+    fn grown_buffers_are_not_retained() {
+        let mut arena = Arena::new(8, 2, 64);
+
+        let mut s = arena.string();
+        s.push_str(&"a".repeat(65));
+        arena.recycle_string(s);
+        assert!(arena.strings.is_empty());
+
+        let mut s = arena.string();
+        s.push_str(&"a".repeat(64));
+        arena.recycle_string(s);
+        assert_eq!(arena.strings.len(), 1);
+    }
+
+    #[test]
+    fn arenas_kept_by_each_thread_are_limited() {
+        clear_arenas();
+        let middleware = ArenaMiddleware::new();
+
+        // Many requests in flight at once each take an arena, and return it to this thread.
+        let mut states: Vec<State> = (0..MAX_POOLED_ARENAS * 2)
+            .map(|_| {
+                let mut state = State::new();
+                state.put(middleware.acquire());
+                set_request_id(&mut state);
+                state
+            })
+            .collect();
+
+        for state in states.iter_mut() {
+            release(state);
+        }
+
+        assert_eq!(
+            ARENAS.with(|arenas| arenas.borrow().len()),
+            MAX_POOLED_ARENAS
+        );
+    }
+}
@@ -0,0 +1,327 @@
+//! Defines a middleware which enforces separate size limits on request and response bodies.
+use std::io;
+use std::pin::Pin;
+
+use futures::prelude::*;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, CONTENT_LENGTH};
+use hyper::{body, Body, StatusCode};
+use log::{trace, warn};
+
+use crate::handler::{HandlerFuture, IntoHandlerError};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+/// Determines how `BodyLimitMiddleware` treats a response body which exceeds the configured
+/// limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OversizedResponse {
+    /// The response is sent unchanged, and a warning is logged.
+    Log,
+
+    /// The response body is truncated to the limit, and a warning is logged.
+    Truncate,
+}
+
+/// Middleware binding which limits the size of request bodies, and of buffered response bodies.
+///
+/// Requests with a body larger than the request limit receive `413 Payload Too Large` without the
+/// handler being invoked. Where the request declares a `Content-Length` it is checked without
+/// reading the body; otherwise the body is read up to the limit before the handler is invoked.
+///
+/// Response bodies of a known length which exceed the response limit are treated according to
+/// the `OversizedResponse` policy, which defaults to `OversizedResponse::Log`. Streamed response
+/// bodies of an unknown length are not affected.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::middleware::body_limit::{BodyLimitMiddleware, OversizedResponse};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #     (state, Response::new(Body::from("created")))
+/// # }
+/// #
+/// # fn main() {
+/// let middleware = BodyLimitMiddleware::new()
+///     .max_request_size(1024 * 1024)
+///     .max_response_size(64 * 1024)
+///     .oversized_response(OversizedResponse::Truncate);
+///
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.post("/upload").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .post("https://example.com/upload", vec![0; 2 * 1024 * 1024], mime::APPLICATION_OCTET_STREAM)
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BodyLimitMiddleware {
+    max_request_size: Option<usize>,
+    max_response_size: Option<usize>,
+    oversized_response: OversizedResponse,
+}
+
+impl BodyLimitMiddleware {
+    /// Creates a new middleware binding, with no limits set.
+    pub fn new() -> BodyLimitMiddleware {
+        BodyLimitMiddleware {
+            max_request_size: None,
+            max_response_size: None,
+            oversized_response: OversizedResponse::Log,
+        }
+    }
+
+    /// Sets the maximum size of a request body, in bytes.
+    pub fn max_request_size(self, max_request_size: usize) -> BodyLimitMiddleware {
+        BodyLimitMiddleware {
+            max_request_size: Some(max_request_size),
+            ..self
+        }
+    }
+
+    /// Sets the maximum size of a response body of a known length, in bytes.
+    pub fn max_response_size(self, max_response_size: usize) -> BodyLimitMiddleware {
+        BodyLimitMiddleware {
+            max_response_size: Some(max_response_size),
+            ..self
+        }
+    }
+
+    /// Sets how response bodies which exceed the limit are treated.
+    pub fn oversized_response(self, oversized_response: OversizedResponse) -> BodyLimitMiddleware {
+        BodyLimitMiddleware {
+            oversized_response,
+            ..self
+        }
+    }
+}
+
+impl Default for BodyLimitMiddleware {
+    fn default() -> BodyLimitMiddleware {
+        BodyLimitMiddleware::new()
+    }
+}
+
+/// Reads the body, returning `None` as soon as more than `limit` bytes have been read.
+async fn read_limited(mut body: Body, limit: usize) -> Result<Option<Vec<u8>>, hyper::Error> {
+    let mut buf = Vec::new();
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if buf.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+
+    Ok(Some(buf))
+}
+
+fn declared_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+fn too_large(state: State) -> Pin<Box<HandlerFuture>> {
+    trace!("[{}] request body exceeds limit", request_id(&state));
+    let res = create_empty_response(&state, StatusCode::PAYLOAD_TOO_LARGE);
+    future::ok((state, res)).boxed()
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for BodyLimitMiddleware {
+    /// Checks the size of the request body before the chain is invoked, and the size of the
+    /// response body once it completes.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        let max_response_size = self.max_response_size;
+        let oversized_response = self.oversized_response;
+
+        let request = match self.max_request_size {
+            Some(limit) => match HeaderMap::try_borrow_from(&state).and_then(declared_length) {
+                Some(length) if length > limit as u64 => return too_large(state),
+                Some(_) => future::ok(state).boxed(),
+                None => {
+                    let body = state.try_take::<Body>().unwrap_or_else(Body::empty);
+                    async move {
+                        match read_limited(body, limit).await {
+                            Ok(Some(buf)) => {
+                                state.put(Body::from(buf));
+                                Ok(state)
+                            }
+                            Ok(None) => Err(Ok(state)),
+                            Err(e) => Err(Err((state, e.into_handler_error()))),
+                        }
+                    }
+                    .boxed()
+                }
+            },
+            None => future::ok(state).boxed(),
+        };
+
+        async move {
+            let state = match request.await {
+                Ok(state) => state,
+                Err(Ok(state)) => return too_large(state).await,
+                Err(Err(e)) => return Err(e),
+            };
+
+            let (state, mut response) = match chain(state).await {
+                Ok(result) => result,
+                Err(e) => return Err(e),
+            };
+
+            let limit = match max_response_size {
+                Some(limit) => limit,
+                None => return Ok((state, response)),
+            };
+
+            match response.body().size_hint().exact() {
+                Some(size) if size > limit as u64 => {
+                    warn!(
+                        "[{}] response body of {} bytes exceeds limit of {} bytes",
+                        request_id(&state),
+                        size,
+                        limit
+                    );
+
+                    if oversized_response == OversizedResponse::Truncate {
+                        let body = std::mem::replace(response.body_mut(), Body::empty());
+                        let bytes = match body::to_bytes(body).await {
+                            Ok(bytes) => bytes,
+                            Err(e) => return Err((state, e.into_handler_error())),
+                        };
+
+                        response.headers_mut().insert(CONTENT_LENGTH, limit.into());
+                        *response.body_mut() = Body::from(bytes.slice(..limit));
+                    }
+
+                    Ok((state, response))
+                }
+                _ => Ok((state, response)),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for BodyLimitMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+    use hyper::Response;
+
+    use crate::helpers::http::response::create_response;
+    use crate::state::set_request_id;
+
+    fn request_state(body: Body, content_length: Option<usize>) -> State {
+        let mut headers = HeaderMap::new();
+        if let Some(length) = content_length {
+            headers.insert(CONTENT_LENGTH, length.into());
+        }
+
+        let mut state = State::new();
+        state.put(headers);
+        state.put(body);
+        set_request_id(&mut state);
+        state
+    }
+
+    fn respond_with(size: usize) -> impl FnOnce(State) -> Pin<Box<HandlerFuture>> + Send {
+        move |state| {
+            let res = create_response(
+                &state,
+                StatusCode::OK,
+                mime::APPLICATION_OCTET_STREAM,
+                vec![1; size],
+            );
+            future::ok((state, res)).boxed()
+        }
+    }
+
+    fn call(middleware: BodyLimitMiddleware, state: State, response_size: usize) -> Response<Body> {
+        match block_on(middleware.call(state, respond_with(response_size))) {
+            Ok((_, res)) => res,
+            Err(_) => panic!("request failed"),
+        }
+    }
+
+    fn body_len(res: Response<Body>) -> usize {
+        block_on(body::to_bytes(res.into_body())).unwrap().len()
+    }
+
+    #[test]
+    fn oversized_requests_are_rejected() {
+        let middleware = BodyLimitMiddleware::new()
+            .max_request_size(16)
+            .max_response_size(1024);
+
+        // declared length
+        let state = request_state(Body::from(vec![0; 32]), Some(32));
+        let res = call(middleware.clone(), state, 0);
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // chunked, with no declared length
+        let chunks: Vec<Result<_, io::Error>> = vec![Ok(vec![0; 10]), Ok(vec![0; 10])];
+        let state = request_state(Body::wrap_stream(stream::iter(chunks)), None);
+        let res = call(middleware.clone(), state, 0);
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // within the limit; the response limit is independent of the request limit
+        let state = request_state(Body::from(vec![0; 16]), None);
+        let res = call(middleware, state, 512);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body_len(res), 512);
+    }
+
+    #[test]
+    fn oversized_responses_follow_policy() {
+        let middleware = BodyLimitMiddleware::new()
+            .max_request_size(1024)
+            .max_response_size(16);
+
+        let state = request_state(Body::from(vec![0; 512]), Some(512));
+        let res = call(middleware.clone(), state, 32);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(body_len(res), 32);
+
+        let middleware = middleware.oversized_response(OversizedResponse::Truncate);
+        let state = request_state(Body::from(vec![0; 512]), Some(512));
+        let res = call(middleware, state, 32);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[CONTENT_LENGTH], "16");
+        assert_eq!(body_len(res), 16);
+    }
+}
@@ -0,0 +1,443 @@
+//! Defines a middleware which negotiates the charset of text responses, based on the
+//! `Accept-Charset` request header.
+//!
+//! Handlers produce text responses encoded as UTF-8. When a client prefers a different charset
+//! which has been enabled on the middleware, the response body is transcoded and the `charset`
+//! parameter of the `Content-Type` header updated to match. Where the client accepts none of the
+//! enabled charsets, and has not provided a `*` wildcard, the response is replaced with
+//! `406 Not Acceptable`.
+use std::io;
+use std::pin::Pin;
+
+use futures::prelude::*;
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT_CHARSET, CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{body, Body, Response, StatusCode};
+use log::trace;
+use mime::Mime;
+
+use crate::handler::{HandlerFuture, IntoHandlerError};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+/// A charset which text responses may be transcoded into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Charset {
+    /// `UTF-8`, the charset produced by handlers. This charset is always enabled.
+    Utf8,
+    /// `ISO-8859-1`, also known as Latin-1.
+    Iso88591,
+    /// `US-ASCII`.
+    UsAscii,
+}
+
+impl Charset {
+    /// The canonical name of the charset, as used in the `Content-Type` header.
+    pub fn name(self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Iso88591 => "iso-8859-1",
+            Charset::UsAscii => "us-ascii",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Charset> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Charset::Utf8),
+            "iso-8859-1" | "latin1" | "l1" => Some(Charset::Iso88591),
+            "us-ascii" | "ascii" => Some(Charset::UsAscii),
+            _ => None,
+        }
+    }
+
+    /// Encodes the text into the charset, returning `None` if any character cannot be
+    /// represented.
+    fn encode(self, text: &str) -> Option<Vec<u8>> {
+        let max = match self {
+            Charset::Utf8 => return Some(text.as_bytes().to_vec()),
+            Charset::Iso88591 => 0xFF,
+            Charset::UsAscii => 0x7F,
+        };
+
+        text.chars()
+            .map(|c| if c as u32 <= max { Some(c as u8) } else { None })
+            .collect()
+    }
+}
+
+/// A single charset accepted by the client, with its quality value.
+struct AcceptedCharset {
+    name: String,
+    quality: f32,
+}
+
+/// Parses the `Accept-Charset` headers, sorted by quality with the preferred charset first.
+///
+/// Charsets with a quality value outside of the range `0` to `1` are ignored.
+fn accepted_charsets(headers: &HeaderMap) -> Vec<AcceptedCharset> {
+    let mut accepted: Vec<AcceptedCharset> = headers
+        .get_all(ACCEPT_CHARSET)
+        .iter()
+        .filter_map(|val| val.to_str().ok())
+        .flat_map(|val| val.split(','))
+        .filter_map(|val| {
+            let mut iter = val.split(';');
+            let name = iter.next().map(str::trim).filter(|name| !name.is_empty())?;
+            let quality = iter
+                .next()
+                .and_then(|qval| qval.trim().trim_start_matches("q=").parse::<f32>().ok())
+                .unwrap_or(1f32);
+
+            if !(0f32..=1f32).contains(&quality) {
+                return None;
+            }
+
+            Some(AcceptedCharset {
+                name: name.to_string(),
+                quality,
+            })
+        })
+        .collect();
+
+    accepted.sort_by(|a, b| b.quality.partial_cmp(&a.quality).unwrap());
+    accepted
+}
+
+/// Middleware binding which negotiates the charset of text responses.
+///
+/// Only responses with a `text/*` content type, and without an existing non UTF-8 `charset`, are
+/// negotiated. When the request has no `Accept-Charset` header the response is left as UTF-8,
+/// with the `charset` parameter added to the `Content-Type` header.
+///
+/// Transcoding requires the response body to be buffered in memory.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use gotham::middleware::charset::{Charset, CharsetMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use hyper::header::{ACCEPT_CHARSET, CONTENT_TYPE};
+/// # use hyper::StatusCode;
+/// #
+/// fn greet(state: State) -> (State, (StatusCode, mime::Mime, &'static str)) {
+///     (state, (StatusCode::OK, mime::TEXT_PLAIN, "café"))
+/// }
+///
+/// # fn main() {
+/// let (chain, pipelines) = single_pipeline(
+///     new_pipeline()
+///         .add(CharsetMiddleware::new().with_charset(Charset::Iso88591))
+///         .build(),
+/// );
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(greet);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/")
+/// #     .with_header(ACCEPT_CHARSET, "iso-8859-1".parse().unwrap())
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.headers()[CONTENT_TYPE], "text/plain; charset=iso-8859-1");
+/// # assert_eq!(response.read_body().unwrap(), b"caf\xe9");
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CharsetMiddleware {
+    charsets: Vec<Charset>,
+}
+
+impl CharsetMiddleware {
+    /// Creates a new middleware binding, with only UTF-8 enabled.
+    pub fn new() -> Self {
+        CharsetMiddleware {
+            charsets: vec![Charset::Utf8],
+        }
+    }
+
+    /// Enables an additional charset which responses may be transcoded into.
+    pub fn with_charset(mut self, charset: Charset) -> Self {
+        if !self.charsets.contains(&charset) {
+            self.charsets.push(charset);
+        }
+        self
+    }
+
+    /// Determines the enabled charsets acceptable to the client, in order of preference.
+    fn negotiate(&self, accepted: &[AcceptedCharset]) -> Vec<Charset> {
+        if accepted.is_empty() {
+            return vec![Charset::Utf8];
+        }
+
+        let explicit = |charset: Charset| {
+            accepted
+                .iter()
+                .find(|a| Charset::from_name(&a.name) == Some(charset))
+        };
+
+        let mut candidates = Vec::new();
+        for a in accepted.iter().filter(|a| a.quality > 0.0) {
+            if a.name == "*" {
+                // the wildcard matches any enabled charset not explicitly listed
+                candidates.extend(
+                    self.charsets
+                        .iter()
+                        .cloned()
+                        .filter(|c| explicit(*c).is_none()),
+                );
+            } else if let Some(charset) = Charset::from_name(&a.name) {
+                if self.charsets.contains(&charset) {
+                    candidates.push(charset);
+                }
+            }
+        }
+
+        candidates.dedup();
+        candidates
+    }
+}
+
+impl Default for CharsetMiddleware {
+    fn default() -> Self {
+        CharsetMiddleware::new()
+    }
+}
+
+/// Returns the content type of a text response which is eligible for negotiation.
+fn negotiable_mime(response: &Response<Body>) -> Option<Mime> {
+    let mime = response
+        .headers()
+        .get(CONTENT_TYPE)?
+        .to_str()
+        .ok()?
+        .parse::<Mime>()
+        .ok()?;
+
+    if mime.type_() != mime::TEXT {
+        return None;
+    }
+
+    match mime.get_param(mime::CHARSET) {
+        Some(charset) if charset != mime::UTF_8 => None,
+        _ => Some(mime),
+    }
+}
+
+/// Renders the content type with the given charset, retaining any other parameters.
+fn with_charset(mime: &Mime, charset: Charset) -> HeaderValue {
+    let mut value = format!("{}/{}", mime.type_(), mime.subtype());
+
+    if let Some(suffix) = mime.suffix() {
+        value.push('+');
+        value.push_str(suffix.as_str());
+    }
+
+    for (name, val) in mime.params().filter(|(name, _)| *name != mime::CHARSET) {
+        value.push_str(&format!("; {}={}", name, val));
+    }
+
+    value.push_str("; charset=");
+    value.push_str(charset.name());
+    value.parse().unwrap()
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for CharsetMiddleware {
+    /// Transcodes text responses into the charset preferred by the client.
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        let candidates = self.negotiate(&accepted_charsets(HeaderMap::borrow_from(&state)));
+
+        chain(state)
+            .and_then(move |(state, mut response)| {
+                async move {
+                    let mime = match negotiable_mime(&response) {
+                        Some(mime) => mime,
+                        None => return Ok((state, response)),
+                    };
+
+                    // UTF-8 is produced by handlers, so the body can be left as is
+                    if candidates.first() == Some(&Charset::Utf8) {
+                        response
+                            .headers_mut()
+                            .insert(CONTENT_TYPE, with_charset(&mime, Charset::Utf8));
+                        return Ok((state, response));
+                    }
+
+                    let (mut parts, body) = response.into_parts();
+                    let body = match body::to_bytes(body).await {
+                        Ok(body) => body,
+                        Err(e) => return Err((state, e.into_handler_error())),
+                    };
+
+                    let text = match std::str::from_utf8(&body) {
+                        Ok(text) => text,
+                        Err(_) => return Ok((state, Response::from_parts(parts, body.into()))),
+                    };
+
+                    let encoded = candidates
+                        .iter()
+                        .find_map(|charset| charset.encode(text).map(|body| (*charset, body)));
+
+                    match encoded {
+                        Some((charset, body)) => {
+                            trace!(
+                                "[{}] transcoded response into {}",
+                                request_id(&state),
+                                charset.name()
+                            );
+
+                            parts
+                                .headers
+                                .insert(CONTENT_TYPE, with_charset(&mime, charset));
+                            if parts.headers.contains_key(CONTENT_LENGTH) {
+                                parts.headers.insert(CONTENT_LENGTH, body.len().into());
+                            }
+
+                            Ok((state, Response::from_parts(parts, body.into())))
+                        }
+                        None => {
+                            trace!(
+                                "[{}] no acceptable charset for response",
+                                request_id(&state)
+                            );
+
+                            let response =
+                                create_empty_response(&state, StatusCode::NOT_ACCEPTABLE);
+                            Ok((state, response))
+                        }
+                    }
+                }
+            })
+            .boxed()
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for CharsetMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::state::set_request_id;
+
+    fn respond(accept_charset: Option<&str>, middleware: CharsetMiddleware) -> Response<Body> {
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        if let Some(accept_charset) = accept_charset {
+            headers.insert(ACCEPT_CHARSET, accept_charset.parse().unwrap());
+        }
+        state.put(headers);
+        set_request_id(&mut state);
+
+        let handler = |state: State| {
+            let response = Response::builder()
+                .header(CONTENT_TYPE, "text/plain")
+                .body(Body::from("café"))
+                .unwrap();
+            future::ok((state, response)).boxed()
+        };
+
+        match futures::executor::block_on(middleware.call(state, handler)) {
+            Ok((_, response)) => response,
+            Err((_, e)) => panic!("error: {:?}", e),
+        }
+    }
+
+    fn read_body(response: Response<Body>) -> Vec<u8> {
+        let body = futures::executor::block_on(body::to_bytes(response.into_body())).unwrap();
+        body.to_vec()
+    }
+
+    #[test]
+    fn defaults_to_utf8() {
+        let middleware = CharsetMiddleware::new().with_charset(Charset::Iso88591);
+        let response = respond(None, middleware);
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(read_body(response), "café".as_bytes());
+    }
+
+    #[test]
+    fn transcodes_to_supported_charset() {
+        let middleware = CharsetMiddleware::new().with_charset(Charset::Iso88591);
+        let response = respond(Some("iso-8859-1, utf-8;q=0.5"), middleware);
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "text/plain; charset=iso-8859-1"
+        );
+        assert_eq!(read_body(response), b"caf\xe9");
+    }
+
+    #[test]
+    fn invalid_quality_values_are_ignored() {
+        let middleware = CharsetMiddleware::new().with_charset(Charset::Iso88591);
+        let response = respond(
+            Some("iso-8859-1;q=nan, us-ascii;q=2, utf-8;q=0.5"),
+            middleware,
+        );
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+    }
+
+    #[test]
+    fn falls_back_when_text_cannot_be_represented() {
+        let middleware = CharsetMiddleware::new().with_charset(Charset::UsAscii);
+        let response = respond(Some("us-ascii, *;q=0.1"), middleware);
+
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(read_body(response), "café".as_bytes());
+    }
+
+    #[test]
+    fn unsupported_charset_is_not_acceptable() {
+        let middleware = CharsetMiddleware::new().with_charset(Charset::Iso88591);
+        let response = respond(Some("shift_jis"), middleware);
+
+        assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[test]
+    fn wildcard_accepts_utf8() {
+        let response = respond(Some("shift_jis, *;q=0.5"), CharsetMiddleware::new());
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+    }
+}
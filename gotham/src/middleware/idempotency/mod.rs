@@ -0,0 +1,655 @@
+//! Defines a middleware which makes requests idempotent, based on the `Idempotency-Key` header.
+//!
+//! The first response produced for each key is stored in memory, and replayed to any request
+//! which repeats the key within the configured TTL. This allows clients to safely retry requests
+//! such as payments, without the request being processed more than once.
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use hyper::header::{HeaderMap, HeaderValue, SET_COOKIE};
+use hyper::{body, Body, Method, Response, StatusCode, Uri};
+use log::{debug, trace};
+
+use crate::handler::{HandlerFuture, IntoHandlerError};
+use crate::helpers::eviction::{EvictingMap, EvictionPolicy};
+use crate::helpers::http::header::X_REQUEST_ID;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+/// The request header holding the client supplied idempotency key.
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// The response header added to responses which have been replayed from the store.
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+/// Identifies the request a response is stored for: the client supplied key, scoped to the
+/// method and path of the request, so that a key reused for a different endpoint is processed as
+/// new.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct Key {
+    method: Method,
+    path: String,
+    key: String,
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} for {} {}", self.key, self.method, self.path)
+    }
+}
+
+/// A response which has been stored, to be replayed for repeated keys.
+#[derive(Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl StoredResponse {
+    /// Stores a response, without the headers which are specific to the request it was produced
+    /// for: any cookies it sets, and its request ID.
+    fn new(status: StatusCode, headers: &HeaderMap, body: Bytes) -> StoredResponse {
+        let mut headers = headers.clone();
+        headers.remove(SET_COOKIE);
+        headers.remove(X_REQUEST_ID);
+
+        StoredResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    fn to_response(&self, state: &State) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+
+        let headers = response.headers_mut();
+        headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+        if let Ok(id) = HeaderValue::from_str(request_id(state)) {
+            headers.insert(X_REQUEST_ID, id);
+        }
+
+        response
+    }
+}
+
+struct Completed {
+    expires: Instant,
+    response: StoredResponse,
+}
+
+/// The keys known to the middleware.
+///
+/// Keys which are still being processed are held apart from the stored responses, so that they
+/// are never expired or evicted by the `EvictionPolicy`, and are only removed once their request
+/// completes.
+struct Entries {
+    in_flight: HashSet<Key>,
+    completed: EvictingMap<Key, Completed>,
+}
+
+type Store = Arc<Mutex<Entries>>;
+
+/// Removes the in-flight entry for a key if the request does not complete, e.g. because the
+/// handler failed or the client disconnected, so that the request may be retried.
+struct InFlightGuard {
+    store: Store,
+    key: Option<Key>,
+}
+
+impl InFlightGuard {
+    fn complete(mut self, response: StoredResponse, ttl: Duration) {
+        if let Some(key) = self.key.take() {
+            let completed = Completed {
+                expires: Instant::now() + ttl,
+                response,
+            };
+
+            let mut entries = lock(&self.store);
+            entries.in_flight.remove(&key);
+            entries.completed.insert(key, completed);
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            lock(&self.store).in_flight.remove(&key);
+        }
+    }
+}
+
+fn lock(store: &Store) -> std::sync::MutexGuard<Entries> {
+    store.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Middleware binding which stores the first response for each `Idempotency-Key`, and replays it
+/// for subsequent requests with the same key.
+///
+/// Requests without an `Idempotency-Key` header are passed through untouched. Keys are scoped to
+/// the method and path of the request, so only a request to the same endpoint is treated as a
+/// duplicate. While the first request for a key is still being processed, any duplicate request
+/// receives a `409 Conflict` response. Responses are only stored when the handler completes
+/// successfully without a `5xx` status, so a failed request may be retried with the same key.
+///
+/// Replayed responses include an `Idempotent-Replayed: true` header. Headers which belong to the
+/// original request are not replayed: `Set-Cookie` headers are dropped, and the `X-Request-ID`
+/// header holds the ID of the replaying request.
+///
+/// Response bodies are buffered in memory in order to be stored, so this middleware should only be
+/// applied to routes with modestly sized responses. The number of stored responses can be bounded
+/// via `with_eviction`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use std::time::Duration;
+/// #
+/// # use gotham::middleware::idempotency::IdempotencyMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use hyper::StatusCode;
+/// #
+/// fn create_payment(state: State) -> (State, (StatusCode, mime::Mime, &'static str)) {
+///     (state, (StatusCode::CREATED, mime::TEXT_PLAIN, "payment created"))
+/// }
+///
+/// # fn main() {
+/// let (chain, pipelines) = single_pipeline(
+///     new_pipeline()
+///         .add(IdempotencyMiddleware::new(Duration::from_secs(24 * 60 * 60)))
+///         .build(),
+/// );
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.post("/payments").to(create_payment);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .post("http://localhost/payments", "", mime::TEXT_PLAIN)
+/// #     .with_header("idempotency-key", "abc123".parse().unwrap())
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::CREATED);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct IdempotencyMiddleware {
+    ttl: Duration,
+    store: Store,
+}
+
+impl IdempotencyMiddleware {
+    /// Creates a new middleware binding, which replays stored responses for `ttl` after they were
+    /// first produced.
+    pub fn new(ttl: Duration) -> Self {
+        IdempotencyMiddleware::new_with_eviction(ttl, EvictionPolicy::default())
+    }
+
+    /// Bounds the stored responses by the provided `EvictionPolicy`. The least recently used
+    /// responses are evicted beyond the policy's maximum number of entries, after which their
+    /// keys are processed as new. Where the policy has no TTL, stored responses are swept once
+    /// the TTL of the middleware has passed. Keys whose request is still being processed are not
+    /// subject to the policy.
+    pub fn with_eviction(self, policy: EvictionPolicy) -> Self {
+        IdempotencyMiddleware::new_with_eviction(self.ttl, policy)
+    }
+
+    fn new_with_eviction(ttl: Duration, policy: EvictionPolicy) -> Self {
+        let policy = policy.with_ttl(policy.ttl().unwrap_or(ttl));
+
+        IdempotencyMiddleware {
+            ttl,
+            store: Arc::new(Mutex::new(Entries {
+                in_flight: HashSet::new(),
+                completed: EvictingMap::new(policy),
+            })),
+        }
+    }
+}
+
+enum Lookup {
+    Replay(StoredResponse),
+    InFlight,
+    Proceed(InFlightGuard),
+}
+
+impl IdempotencyMiddleware {
+    fn lookup(&self, key: Key) -> Lookup {
+        let mut entries = lock(&self.store);
+
+        if entries.in_flight.contains(&key) {
+            return Lookup::InFlight;
+        }
+
+        let now = Instant::now();
+        if let Some(Completed { expires, response }) = entries.completed.get(&key) {
+            if *expires > now {
+                return Lookup::Replay(response.clone());
+            }
+        }
+
+        entries.in_flight.insert(key.clone());
+        Lookup::Proceed(InFlightGuard {
+            store: self.store.clone(),
+            key: Some(key),
+        })
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for IdempotencyMiddleware {
+    /// Replays the stored response for a repeated `Idempotency-Key`, or stores the response
+    /// produced by the chain for a new key.
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        let key = HeaderMap::borrow_from(&state)
+            .get(IDEMPOTENCY_KEY)
+            .and_then(|value| value.to_str().ok())
+            .map(|key| Key {
+                method: Method::borrow_from(&state).clone(),
+                path: Uri::borrow_from(&state).path().to_owned(),
+                key: key.to_owned(),
+            });
+
+        let key = match key {
+            Some(key) => key,
+            None => return chain(state),
+        };
+
+        let guard = match self.lookup(key.clone()) {
+            Lookup::Replay(stored) => {
+                debug!(
+                    "[{}] replaying stored response for idempotency key {}",
+                    request_id(&state),
+                    key
+                );
+                let response = stored.to_response(&state);
+                return future::ok((state, response)).boxed();
+            }
+            Lookup::InFlight => {
+                debug!(
+                    "[{}] request with idempotency key {} is already in progress",
+                    request_id(&state),
+                    key
+                );
+                let response = create_empty_response(&state, StatusCode::CONFLICT);
+                return future::ok((state, response)).boxed();
+            }
+            Lookup::Proceed(guard) => guard,
+        };
+
+        trace!(
+            "[{}] processing request with new idempotency key {}",
+            request_id(&state),
+            key
+        );
+
+        let ttl = self.ttl;
+        chain(state)
+            .and_then(move |(state, response)| async move {
+                if response.status().is_server_error() {
+                    trace!(
+                        "[{}] not storing {} response for idempotency key {}",
+                        request_id(&state),
+                        response.status(),
+                        key
+                    );
+                    drop(guard);
+                    return Ok((state, response));
+                }
+
+                let (parts, body) = response.into_parts();
+
+                let body = match body::to_bytes(body).await {
+                    Ok(body) => body,
+                    Err(e) => return Err((state, e.into_handler_error())),
+                };
+
+                guard.complete(
+                    StoredResponse::new(parts.status, &parts.headers, body.clone()),
+                    ttl,
+                );
+
+                Ok((state, Response::from_parts(parts, Body::from(body))))
+            })
+            .boxed()
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for IdempotencyMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance, sharing the stored responses.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::channel::oneshot;
+
+    fn request_state(key: &str) -> State {
+        request_state_for(Method::POST, "/payments", key)
+    }
+
+    fn request_state_for(method: Method, path: &str, key: &str) -> State {
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(IDEMPOTENCY_KEY, key.parse().unwrap());
+        state.put(headers);
+        state.put(method);
+        state.put(path.parse::<Uri>().unwrap());
+        crate::state::set_request_id(&mut state);
+        state
+    }
+
+    fn counting_handler(calls: Arc<AtomicUsize>) -> impl FnOnce(State) -> Pin<Box<HandlerFuture>> {
+        move |state| {
+            let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let mut response = Response::new(Body::from(format!("payment {}", n)));
+            *response.status_mut() = StatusCode::CREATED;
+            future::ok((state, response)).boxed()
+        }
+    }
+
+    fn read_body(response: Response<Body>) -> Bytes {
+        futures::executor::block_on(body::to_bytes(response.into_body())).unwrap()
+    }
+
+    #[test]
+    fn replayed_key_returns_stored_response() {
+        let middleware = IdempotencyMiddleware::new(Duration::from_secs(60));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let call = |key: &str| {
+            let m = middleware.new_middleware().unwrap();
+            let f = m.call(request_state(key), counting_handler(calls.clone()));
+            match futures::executor::block_on(f) {
+                Ok((_, response)) => response,
+                Err((_, e)) => panic!("error: {:?}", e),
+            }
+        };
+
+        let first = call("abc");
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert!(first.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        assert_eq!(read_body(first), "payment 1");
+
+        let replayed = call("abc");
+        assert_eq!(replayed.status(), StatusCode::CREATED);
+        assert_eq!(replayed.headers()[IDEMPOTENT_REPLAYED], "true");
+        assert_eq!(read_body(replayed), "payment 1");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let other = call("def");
+        assert_eq!(read_body(other), "payment 2");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn keys_are_scoped_to_method_and_path() {
+        let middleware = IdempotencyMiddleware::new(Duration::from_secs(60));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let call = |method: Method, path: &str| {
+            let m = middleware.new_middleware().unwrap();
+            let f = m.call(
+                request_state_for(method, path, "abc"),
+                counting_handler(calls.clone()),
+            );
+            match futures::executor::block_on(f) {
+                Ok((_, response)) => read_body(response),
+                Err((_, e)) => panic!("error: {:?}", e),
+            }
+        };
+
+        assert_eq!(call(Method::POST, "/payments"), "payment 1");
+        assert_eq!(call(Method::POST, "/refunds"), "payment 2");
+        assert_eq!(call(Method::PUT, "/payments"), "payment 3");
+        assert_eq!(call(Method::POST, "/payments"), "payment 1");
+    }
+
+    #[test]
+    fn server_errors_are_not_stored() {
+        let middleware = IdempotencyMiddleware::new(Duration::from_secs(60));
+
+        let unavailable = |state: State| {
+            let response = create_empty_response(&state, StatusCode::SERVICE_UNAVAILABLE);
+            future::ok((state, response)).boxed()
+        };
+
+        let m = middleware.new_middleware().unwrap();
+        match futures::executor::block_on(m.call(request_state("abc"), unavailable)) {
+            Ok((_, response)) => assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE),
+            Err((_, e)) => panic!("error: {:?}", e),
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let m = middleware.new_middleware().unwrap();
+        let f = m.call(request_state("abc"), counting_handler(calls.clone()));
+        match futures::executor::block_on(f) {
+            Ok((_, response)) => assert_eq!(response.status(), StatusCode::CREATED),
+            Err((_, e)) => panic!("error: {:?}", e),
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn request_specific_headers_are_not_replayed() {
+        let middleware = IdempotencyMiddleware::new(Duration::from_secs(60));
+
+        let handler = |state: State| {
+            let mut response = create_empty_response(&state, StatusCode::CREATED);
+            response
+                .headers_mut()
+                .insert(SET_COOKIE, HeaderValue::from_static("session=secret"));
+            future::ok((state, response)).boxed()
+        };
+
+        let m = middleware.new_middleware().unwrap();
+        let (first_id, first) =
+            match futures::executor::block_on(m.call(request_state("abc"), handler)) {
+                Ok((state, response)) => (request_id(&state).to_owned(), response),
+                Err((_, e)) => panic!("error: {:?}", e),
+            };
+        assert_eq!(first.headers()[SET_COOKIE], "session=secret");
+        assert_eq!(first.headers()[X_REQUEST_ID], first_id.as_str());
+
+        let m = middleware.new_middleware().unwrap();
+        let f = m.call(request_state("abc"), counting_handler(Arc::default()));
+        let (replay_id, replayed) = match futures::executor::block_on(f) {
+            Ok((state, response)) => (request_id(&state).to_owned(), response),
+            Err((_, e)) => panic!("error: {:?}", e),
+        };
+        assert_ne!(replay_id, first_id);
+        assert_eq!(replayed.headers()[IDEMPOTENT_REPLAYED], "true");
+        assert!(replayed.headers().get(SET_COOKIE).is_none());
+        assert_eq!(replayed.headers()[X_REQUEST_ID], replay_id.as_str());
+    }
+
+    #[test]
+    fn stored_response_expires_after_ttl() {
+        let middleware = IdempotencyMiddleware::new(Duration::from_millis(10));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..2 {
+            let m = middleware.new_middleware().unwrap();
+            let f = m.call(request_state("abc"), counting_handler(calls.clone()));
+            futures::executor::block_on(f).unwrap_or_else(|_| panic!("request failed"));
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn in_flight_duplicate_is_rejected() {
+        let middleware = IdempotencyMiddleware::new(Duration::from_secs(60));
+        let (tx, rx) = oneshot::channel::<()>();
+
+        // The first request does not complete until the duplicate has been rejected.
+        let slow_handler = move |state: State| {
+            async move {
+                rx.await.unwrap();
+                Ok((state, Response::new(Body::from("done"))))
+            }
+            .boxed()
+        };
+
+        let m = middleware.new_middleware().unwrap();
+        let mut first = m.call(request_state("abc"), slow_handler);
+        assert!((&mut first).now_or_never().is_none());
+
+        let m = middleware.new_middleware().unwrap();
+        let duplicate = m.call(request_state("abc"), counting_handler(Arc::default()));
+        match futures::executor::block_on(duplicate) {
+            Ok((_, response)) => assert_eq!(response.status(), StatusCode::CONFLICT),
+            Err((_, e)) => panic!("error: {:?}", e),
+        }
+
+        tx.send(()).unwrap();
+        match futures::executor::block_on(first) {
+            Ok((_, response)) => assert_eq!(read_body(response), "done"),
+            Err((_, e)) => panic!("error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn in_flight_keys_are_not_expired_or_evicted() {
+        let policy = EvictionPolicy::default()
+            .with_max_entries(1)
+            .with_sweep_interval(Duration::from_millis(0));
+        let middleware =
+            IdempotencyMiddleware::new(Duration::from_millis(10)).with_eviction(policy);
+        let (tx, rx) = oneshot::channel::<()>();
+
+        let slow_handler = move |state: State| {
+            async move {
+                rx.await.unwrap();
+                Ok((state, Response::new(Body::from("done"))))
+            }
+            .boxed()
+        };
+
+        let m = middleware.new_middleware().unwrap();
+        let mut first = m.call(request_state("abc"), slow_handler);
+        assert!((&mut first).now_or_never().is_none());
+
+        // outlive the TTL, and fill the store beyond its maximum size with other keys
+        std::thread::sleep(Duration::from_millis(20));
+        for key in &["a", "b"] {
+            let m = middleware.new_middleware().unwrap();
+            let f = m.call(request_state(key), counting_handler(Arc::default()));
+            futures::executor::block_on(f).unwrap_or_else(|_| panic!("request failed"));
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let m = middleware.new_middleware().unwrap();
+        let duplicate = m.call(request_state("abc"), counting_handler(calls.clone()));
+        match futures::executor::block_on(duplicate) {
+            Ok((_, response)) => assert_eq!(response.status(), StatusCode::CONFLICT),
+            Err((_, e)) => panic!("error: {:?}", e),
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        tx.send(()).unwrap();
+        futures::executor::block_on(first).unwrap_or_else(|_| panic!("request failed"));
+        assert!(lock(&middleware.store).in_flight.is_empty());
+    }
+
+    #[test]
+    fn failed_request_may_be_retried() {
+        let middleware = IdempotencyMiddleware::new(Duration::from_secs(60));
+
+        let failing_handler = |state: State| {
+            let e = io::Error::new(io::ErrorKind::Other, "payment provider unavailable");
+            future::err((state, e.into_handler_error())).boxed()
+        };
+
+        let m = middleware.new_middleware().unwrap();
+        let f = m.call(request_state("abc"), failing_handler);
+        assert!(futures::executor::block_on(f).is_err());
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let m = middleware.new_middleware().unwrap();
+        let f = m.call(request_state("abc"), counting_handler(calls.clone()));
+        assert!(futures::executor::block_on(f).is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn least_recently_used_responses_are_evicted() {
+        let middleware = IdempotencyMiddleware::new(Duration::from_secs(60))
+            .with_eviction(EvictionPolicy::default().with_max_entries(2));
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let call = |key: &str| {
+            let m = middleware.new_middleware().unwrap();
+            let f = m.call(request_state(key), counting_handler(calls.clone()));
+            match futures::executor::block_on(f) {
+                Ok((_, response)) => read_body(response),
+                Err((_, e)) => panic!("error: {:?}", e),
+            }
+        };
+
+        assert_eq!(call("a"), "payment 1");
+        assert_eq!(call("b"), "payment 2");
+        assert_eq!(call("a"), "payment 1");
+
+        // "b" is now the least recently used, so is evicted to make room for "c"
+        assert_eq!(call("c"), "payment 3");
+        assert_eq!(lock(&middleware.store).completed.len(), 2);
+        assert_eq!(call("a"), "payment 1");
+        assert_eq!(call("b"), "payment 4");
+    }
+
+    #[test]
+    fn expired_responses_are_swept() {
+        let policy = EvictionPolicy::default().with_sweep_interval(Duration::from_millis(0));
+        let middleware =
+            IdempotencyMiddleware::new(Duration::from_millis(20)).with_eviction(policy);
+
+        for key in &["a", "b"] {
+            let m = middleware.new_middleware().unwrap();
+            let f = m.call(request_state(key), counting_handler(Arc::default()));
+            futures::executor::block_on(f).unwrap_or_else(|_| panic!("request failed"));
+        }
+        assert_eq!(lock(&middleware.store).completed.len(), 2);
+
+        std::thread::sleep(Duration::from_millis(40));
+
+        let m = middleware.new_middleware().unwrap();
+        let f = m.call(request_state("c"), counting_handler(Arc::default()));
+        futures::executor::block_on(f).unwrap_or_else(|_| panic!("request failed"));
+
+        // the stale responses for "a" and "b" were swept when "c" was stored
+        assert_eq!(lock(&middleware.store).completed.len(), 1);
+    }
+}
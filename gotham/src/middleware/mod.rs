@@ -8,13 +8,23 @@ use std::pin::Pin;
 use crate::handler::HandlerFuture;
 use crate::state::State;
 
+pub mod arena;
+pub mod body_limit;
 pub mod chain;
+pub mod charset;
 pub mod cookie;
+pub mod idempotency;
 pub mod logger;
+pub mod rate_limit;
+pub mod registry;
+pub mod request_id;
+pub mod required_headers;
 pub mod security;
 pub mod session;
 pub mod state;
+pub mod strip_prefix;
 pub mod timer;
+pub mod trace_context;
 
 /// `Middleware` has the opportunity to provide additional behaviour to the `Request` / `Response`
 /// interaction. For example:
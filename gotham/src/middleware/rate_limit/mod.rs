@@ -0,0 +1,599 @@
+//! Defines a middleware which limits the rate of requests from each client.
+//!
+//! Clients are identified by their IP address by default, by an API key held in a configured
+//! request header, or by the authenticated user stored in `State`. Each client is allowed a burst
+//! of requests up to the configured limit, which is replenished evenly over the configured period
+//! (i.e. a token bucket).
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use futures::prelude::*;
+use hyper::header::{HeaderMap, HeaderName, RETRY_AFTER};
+use hyper::StatusCode;
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::eviction::{EvictingMap, EvictionPolicy};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{client_addr, request_id, FromState, State, StateData};
+
+/// A limit of `requests` per `period` for a single client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limit {
+    requests: u32,
+    period: Duration,
+}
+
+impl Limit {
+    /// Creates a limit allowing `requests` requests per `period`.
+    ///
+    /// # Panics
+    ///
+    /// If `requests` is zero, or `period` is zero.
+    pub fn new(requests: u32, period: Duration) -> Limit {
+        assert!(requests > 0, "a rate limit must allow at least one request");
+        assert!(
+            period > Duration::from_secs(0),
+            "a rate limit must have a non-zero period"
+        );
+
+        Limit { requests, period }
+    }
+
+    /// The rate at which requests are replenished, per second.
+    fn rate(&self) -> f64 {
+        f64::from(self.requests) / self.period.as_secs_f64()
+    }
+}
+
+/// The identity of the authenticated user making a request, stored in `State` by an
+/// authentication middleware. Implementing this allows `RateLimitMiddleware::per_user` to limit
+/// each user independently.
+pub trait UserIdentity: StateData {
+    /// A stable identifier for the user, such as their ID or username.
+    fn user_id(&self) -> &str;
+}
+
+/// Identifies the client a bucket belongs to.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum ClientKey {
+    User(String),
+    ApiKey(String),
+    Ip(IpAddr),
+    Unknown,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// Replenishes the bucket for the time elapsed since it was last updated.
+    fn refill(&mut self, limit: &Limit, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.rate()).min(f64::from(limit.requests));
+        self.updated = now;
+    }
+}
+
+/// Middleware binding which limits the rate of requests from each client, responding with
+/// `429 Too Many Requests` and a `Retry-After` header once a client exceeds its limit.
+///
+/// By default clients are identified by their IP address. Using `key_header`, clients can instead
+/// be identified by an API key held in a request header, with each key given its own limit via
+/// `with_key_limit`. Only keys with a limit are honoured, so that clients cannot escape their limit
+/// by sending a new key with each request. Requests with any other key, or without the header, are
+/// identified by their IP address.
+///
+/// Using `per_user`, clients are instead identified by the authenticated user stored in `State`
+/// by an earlier middleware, with individual users given their own limits via `with_user_limit`.
+/// Requests without an authenticated user fall back to the API key or IP address.
+///
+/// Each `RateLimitMiddleware` holds its own buckets, which are shared by its clones (and so by
+/// every pipeline it is added to). To apply different limits to different routes, add separately
+/// created instances to the pipelines of those routes. Buckets which have been idle for the
+/// longest configured period are discarded, and the number of buckets can be bounded via
+/// `with_eviction`.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use std::time::Duration;
+/// #
+/// # use gotham::middleware::rate_limit::{Limit, RateLimitMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use hyper::StatusCode;
+/// #
+/// fn handler(state: State) -> (State, (StatusCode, mime::Mime, &'static str)) {
+///     (state, (StatusCode::OK, mime::TEXT_PLAIN, "ok"))
+/// }
+///
+/// # fn main() {
+/// let rate_limit = RateLimitMiddleware::new(Limit::new(1, Duration::from_secs(60)))
+///     .key_header("x-api-key".parse().unwrap())
+///     .with_key_limit("premium", Limit::new(100, Duration::from_secs(60)));
+///
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(rate_limit).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let get = || {
+/// #     test_server
+/// #         .client()
+/// #         .get("http://localhost/")
+/// #         .with_header("x-api-key", "basic".parse().unwrap())
+/// #         .perform()
+/// #         .unwrap()
+/// # };
+/// # assert_eq!(get().status(), StatusCode::OK);
+/// # assert_eq!(get().status(), StatusCode::TOO_MANY_REQUESTS);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RateLimitMiddleware {
+    limit: Limit,
+    key_header: Option<HeaderName>,
+    key_limits: HashMap<String, Limit>,
+    user_id: Option<fn(&State) -> Option<String>>,
+    user_limits: HashMap<String, Limit>,
+    eviction: EvictionPolicy,
+    buckets: Arc<Mutex<EvictingMap<ClientKey, Bucket>>>,
+}
+
+fn user_id<U>(state: &State) -> Option<String>
+where
+    U: UserIdentity,
+{
+    U::try_borrow_from(state).map(|user| user.user_id().to_owned())
+}
+
+impl RateLimitMiddleware {
+    /// Creates a new middleware binding, applying `limit` to each client IP address.
+    pub fn new(limit: Limit) -> RateLimitMiddleware {
+        RateLimitMiddleware {
+            limit,
+            key_header: None,
+            key_limits: HashMap::new(),
+            user_id: None,
+            user_limits: HashMap::new(),
+            eviction: EvictionPolicy::default(),
+            buckets: Arc::new(Mutex::new(EvictingMap::new(EvictionPolicy::default()))),
+        }
+        .with_new_buckets()
+    }
+
+    /// Identifies clients by the API key held in the `header`, rather than their IP address. Where
+    /// the header is absent, or holds a key without a limit configured via `with_key_limit`, the
+    /// client is identified by its IP address.
+    pub fn key_header(self, header: HeaderName) -> RateLimitMiddleware {
+        RateLimitMiddleware {
+            key_header: Some(header),
+            ..self
+        }
+    }
+
+    /// Applies a `limit` to requests with the given API key, in place of the default limit.
+    pub fn with_key_limit(mut self, key: &str, limit: Limit) -> RateLimitMiddleware {
+        self.key_limits.insert(key.to_owned(), limit);
+        self.with_new_buckets()
+    }
+
+    /// Identifies clients by the authenticated user stored in `State` as `U`. Where there is no
+    /// authenticated user, the client is identified as it would be otherwise.
+    pub fn per_user<U>(self) -> RateLimitMiddleware
+    where
+        U: UserIdentity,
+    {
+        RateLimitMiddleware {
+            user_id: Some(user_id::<U>),
+            ..self
+        }
+    }
+
+    /// Applies a `limit` to requests from the given user, in place of the default limit.
+    pub fn with_user_limit(mut self, user_id: &str, limit: Limit) -> RateLimitMiddleware {
+        self.user_limits.insert(user_id.to_owned(), limit);
+        self.with_new_buckets()
+    }
+
+    /// Bounds the buckets held by the provided `EvictionPolicy`. The least recently used buckets
+    /// are evicted beyond the policy's maximum number of entries, after which their clients start
+    /// again with a full bucket. The policy's TTL is extended to the longest configured period
+    /// where it is shorter, as a bucket idle for that long has been fully replenished.
+    pub fn with_eviction(self, eviction: EvictionPolicy) -> RateLimitMiddleware {
+        RateLimitMiddleware { eviction, ..self }.with_new_buckets()
+    }
+
+    /// Replaces the buckets with an empty set, evicted according to the current configuration.
+    fn with_new_buckets(self) -> RateLimitMiddleware {
+        let longest = self
+            .key_limits
+            .values()
+            .chain(self.user_limits.values())
+            .map(|limit| limit.period)
+            .fold(self.limit.period, Duration::max);
+
+        let ttl = self.eviction.ttl().map_or(longest, |ttl| ttl.max(longest));
+        let buckets = EvictingMap::new(self.eviction.with_ttl(ttl));
+
+        RateLimitMiddleware {
+            buckets: Arc::new(Mutex::new(buckets)),
+            ..self
+        }
+    }
+
+    fn client_key(&self, state: &State) -> ClientKey {
+        if let Some(user) = self.user_id.and_then(|f| f(state)) {
+            return ClientKey::User(user);
+        }
+
+        let api_key = self.key_header.as_ref().and_then(|header| {
+            HeaderMap::borrow_from(state)
+                .get(header)
+                .and_then(|value| value.to_str().ok())
+                .filter(|key| self.key_limits.contains_key(*key))
+        });
+
+        match (api_key, client_addr(state)) {
+            (Some(key), _) => ClientKey::ApiKey(key.to_owned()),
+            (None, Some(addr)) => ClientKey::Ip(addr.ip()),
+            (None, None) => ClientKey::Unknown,
+        }
+    }
+
+    fn limit_for(&self, key: &ClientKey) -> Limit {
+        match *key {
+            ClientKey::User(ref user) => self.user_limits.get(user).cloned().unwrap_or(self.limit),
+            ClientKey::ApiKey(ref key) => self.key_limits.get(key).cloned().unwrap_or(self.limit),
+            _ => self.limit,
+        }
+    }
+
+    /// Takes a request from the client's bucket, returning the time until a request will be
+    /// available if the bucket is empty.
+    fn acquire(&self, key: ClientKey) -> Result<(), Duration> {
+        let limit = self.limit_for(&key);
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let bucket = buckets.get_or_insert_with(key, || Bucket {
+            tokens: f64::from(limit.requests),
+            updated: now,
+        });
+
+        bucket.refill(&limit, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - bucket.tokens) / limit.rate();
+            Err(Duration::from_secs_f64(wait))
+        }
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for RateLimitMiddleware {
+    /// Invokes the chain if the client is within its limit, and responds with
+    /// `429 Too Many Requests` otherwise.
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        let key = self.client_key(&state);
+
+        match self.acquire(key.clone()) {
+            Ok(()) => chain(state),
+            Err(wait) => {
+                trace!("[{}] rate limit exceeded for {:?}", request_id(&state), key);
+
+                let mut response = create_empty_response(&state, StatusCode::TOO_MANY_REQUESTS);
+                let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, retry_after.into());
+
+                future::ok((state, response)).boxed()
+            }
+        }
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for RateLimitMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance, sharing the buckets.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::SocketAddr;
+
+    use hyper::{Body, Response};
+
+    use crate::state::client_addr::put_client_addr;
+    use crate::state::set_request_id;
+
+    fn status(middleware: &RateLimitMiddleware, key: Option<&str>, ip: [u8; 4]) -> StatusCode {
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        if let Some(key) = key {
+            headers.insert("x-api-key", key.parse().unwrap());
+        }
+        state.put(headers);
+        put_client_addr(&mut state, SocketAddr::from((ip, 10000)));
+        set_request_id(&mut state);
+
+        let handler = |state: State| future::ok((state, Response::new(Body::empty()))).boxed();
+        let m = middleware.new_middleware().unwrap();
+
+        match futures::executor::block_on(m.call(state, handler)) {
+            Ok((_, response)) => response.status(),
+            Err((_, e)) => panic!("error: {:?}", e),
+        }
+    }
+
+    fn keyed_middleware() -> RateLimitMiddleware {
+        RateLimitMiddleware::new(Limit::new(2, Duration::from_secs(60)))
+            .key_header("x-api-key".parse().unwrap())
+            .with_key_limit("a", Limit::new(2, Duration::from_secs(60)))
+            .with_key_limit("b", Limit::new(2, Duration::from_secs(60)))
+    }
+
+    #[test]
+    fn requests_sharing_a_key_share_a_bucket() {
+        let middleware = keyed_middleware();
+
+        // different addresses, but the same key
+        assert_eq!(
+            status(&middleware, Some("a"), [10, 0, 0, 1]),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&middleware, Some("a"), [10, 0, 0, 2]),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&middleware, Some("a"), [10, 0, 0, 3]),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[test]
+    fn requests_with_different_keys_have_independent_buckets() {
+        let middleware = keyed_middleware();
+        let ip = [10, 0, 0, 1];
+
+        assert_eq!(status(&middleware, Some("a"), ip), StatusCode::OK);
+        assert_eq!(status(&middleware, Some("a"), ip), StatusCode::OK);
+        assert_eq!(status(&middleware, Some("b"), ip), StatusCode::OK);
+        assert_eq!(status(&middleware, Some("b"), ip), StatusCode::OK);
+        assert_eq!(status(&middleware, None, ip), StatusCode::OK);
+
+        assert_eq!(
+            status(&middleware, Some("a"), ip),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            status(&middleware, Some("b"), ip),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[test]
+    fn falls_back_to_client_address() {
+        let middleware = keyed_middleware();
+
+        assert_eq!(status(&middleware, None, [10, 0, 0, 1]), StatusCode::OK);
+        assert_eq!(status(&middleware, None, [10, 0, 0, 1]), StatusCode::OK);
+        assert_eq!(
+            status(&middleware, None, [10, 0, 0, 1]),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(status(&middleware, None, [10, 0, 0, 2]), StatusCode::OK);
+    }
+
+    #[test]
+    fn unknown_keys_fall_back_to_client_address() {
+        let middleware = keyed_middleware();
+        let ip = [10, 0, 0, 1];
+
+        assert_eq!(status(&middleware, Some("x1"), ip), StatusCode::OK);
+        assert_eq!(status(&middleware, Some("x2"), ip), StatusCode::OK);
+        assert_eq!(
+            status(&middleware, Some("x3"), ip),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(status(&middleware, Some("a"), ip), StatusCode::OK);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one request")]
+    fn zero_request_limit_is_rejected() {
+        Limit::new(0, Duration::from_secs(30));
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero period")]
+    fn zero_period_limit_is_rejected() {
+        Limit::new(1, Duration::from_secs(0));
+    }
+
+    #[test]
+    fn keys_can_have_their_own_limits() {
+        let middleware =
+            keyed_middleware().with_key_limit("premium", Limit::new(3, Duration::from_secs(60)));
+        let ip = [10, 0, 0, 1];
+
+        for _ in 0..3 {
+            assert_eq!(status(&middleware, Some("premium"), ip), StatusCode::OK);
+        }
+        assert_eq!(
+            status(&middleware, Some("premium"), ip),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[test]
+    fn throttled_responses_include_retry_after() {
+        let middleware = RateLimitMiddleware::new(Limit::new(1, Duration::from_secs(30)));
+
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+
+        let handler = |state: State| future::ok((state, Response::new(Body::empty()))).boxed();
+        let first = middleware.clone().call(state, handler);
+        let (state, _) = futures::executor::block_on(first).unwrap_or_else(|_| panic!());
+
+        let second = middleware.call(state, handler);
+        let (_, response) = futures::executor::block_on(second).unwrap_or_else(|_| panic!());
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = response.headers()[RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retry_after > 0 && retry_after <= 30);
+    }
+
+    fn bucket_count(middleware: &RateLimitMiddleware) -> usize {
+        middleware
+            .buckets
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    #[test]
+    fn least_recently_used_buckets_are_evicted() {
+        let middleware = RateLimitMiddleware::new(Limit::new(2, Duration::from_secs(60)))
+            .with_eviction(EvictionPolicy::default().with_max_entries(1));
+
+        assert_eq!(status(&middleware, None, [10, 0, 0, 1]), StatusCode::OK);
+        assert_eq!(status(&middleware, None, [10, 0, 0, 1]), StatusCode::OK);
+        assert_eq!(
+            status(&middleware, None, [10, 0, 0, 1]),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // a second client evicts the bucket of the first
+        assert_eq!(status(&middleware, None, [10, 0, 0, 2]), StatusCode::OK);
+        assert_eq!(bucket_count(&middleware), 1);
+
+        // so the first client starts again with a full bucket
+        assert_eq!(status(&middleware, None, [10, 0, 0, 1]), StatusCode::OK);
+        assert_eq!(status(&middleware, None, [10, 0, 0, 1]), StatusCode::OK);
+        assert_eq!(
+            status(&middleware, None, [10, 0, 0, 1]),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[test]
+    fn idle_buckets_are_swept() {
+        let policy = EvictionPolicy::default().with_sweep_interval(Duration::from_millis(0));
+        let middleware = RateLimitMiddleware::new(Limit::new(1, Duration::from_millis(20)))
+            .with_eviction(policy);
+
+        for ip in &[[10, 0, 0, 1], [10, 0, 0, 2]] {
+            assert_eq!(status(&middleware, None, *ip), StatusCode::OK);
+            assert_eq!(
+                status(&middleware, None, *ip),
+                StatusCode::TOO_MANY_REQUESTS
+            );
+        }
+        assert_eq!(bucket_count(&middleware), 2);
+
+        std::thread::sleep(Duration::from_millis(40));
+
+        // the idle buckets were swept when the third client's bucket was stored
+        assert_eq!(status(&middleware, None, [10, 0, 0, 3]), StatusCode::OK);
+        assert_eq!(bucket_count(&middleware), 1);
+
+        assert_eq!(status(&middleware, None, [10, 0, 0, 1]), StatusCode::OK);
+        assert_eq!(
+            status(&middleware, None, [10, 0, 0, 1]),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    struct User(String);
+
+    impl StateData for User {}
+
+    impl UserIdentity for User {
+        fn user_id(&self) -> &str {
+            &self.0
+        }
+    }
+
+    fn user_status(middleware: &RateLimitMiddleware, user: &str) -> StatusCode {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        state.put(User(user.to_owned()));
+        put_client_addr(&mut state, SocketAddr::from(([10, 0, 0, 1], 10000)));
+        set_request_id(&mut state);
+
+        let handler = |state: State| future::ok((state, Response::new(Body::empty()))).boxed();
+        let m = middleware.new_middleware().unwrap();
+
+        match futures::executor::block_on(m.call(state, handler)) {
+            Ok((_, response)) => response.status(),
+            Err((_, e)) => panic!("error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn users_have_independent_quotas() {
+        let middleware = RateLimitMiddleware::new(Limit::new(2, Duration::from_secs(60)))
+            .per_user::<User>()
+            .with_user_limit("carol", Limit::new(3, Duration::from_secs(60)));
+
+        // alice and bob share an address, but not a bucket
+        assert_eq!(user_status(&middleware, "alice"), StatusCode::OK);
+        assert_eq!(user_status(&middleware, "alice"), StatusCode::OK);
+        assert_eq!(
+            user_status(&middleware, "alice"),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        assert_eq!(user_status(&middleware, "bob"), StatusCode::OK);
+        assert_eq!(user_status(&middleware, "bob"), StatusCode::OK);
+
+        for _ in 0..3 {
+            assert_eq!(user_status(&middleware, "carol"), StatusCode::OK);
+        }
+        assert_eq!(
+            user_status(&middleware, "carol"),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // requests without a user fall back to the client address
+        assert_eq!(status(&middleware, None, [10, 0, 0, 1]), StatusCode::OK);
+    }
+}
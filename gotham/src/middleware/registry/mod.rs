@@ -0,0 +1,268 @@
+//! Defines a small dependency injection container, resolvable from request state.
+//!
+//! Services are registered once with a `ServiceRegistry` while the application is being built,
+//! and the registry is attached to each request by `ServiceRegistryMiddleware`. Handlers and
+//! later middleware can then resolve services by type, rather than relying on global singletons.
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{State, StateData};
+
+type Service = Arc<dyn Any + Send + Sync>;
+
+enum ServiceEntry {
+    Ready(Service),
+    Lazy {
+        init: Box<dyn Fn() -> Service + Send + Sync>,
+        value: Mutex<Option<Service>>,
+    },
+}
+
+impl ServiceEntry {
+    fn get(&self) -> Service {
+        match *self {
+            ServiceEntry::Ready(ref service) => service.clone(),
+            ServiceEntry::Lazy {
+                ref init,
+                ref value,
+            } => {
+                let mut value = value.lock().unwrap_or_else(PoisonError::into_inner);
+                value.get_or_insert_with(init).clone()
+            }
+        }
+    }
+}
+
+/// A collection of shared services, keyed by their type.
+///
+/// The registry is cheap to clone, with every clone sharing the same services. Services are
+/// handed out as `Arc<T>`, so any mutability must be provided by the service itself.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use gotham::middleware::registry::{ServiceRegistry, ServiceRegistryMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// # use hyper::StatusCode;
+/// #
+/// struct Greeter {
+///     greeting: &'static str,
+/// }
+///
+/// fn handler(state: State) -> (State, String) {
+///     let greeter = ServiceRegistry::borrow_from(&state)
+///         .resolve::<Greeter>()
+///         .expect("Greeter is registered at startup");
+///
+///     let body = format!("{}, world!", greeter.greeting);
+///     (state, body)
+/// }
+///
+/// # fn main() {
+/// let registry = ServiceRegistry::new().register(Greeter { greeting: "Hello" });
+///
+/// let (chain, pipelines) = single_pipeline(
+///     new_pipeline()
+///         .add(ServiceRegistryMiddleware::new(registry))
+///         .build(),
+/// );
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server.client().get("http://localhost/").perform().unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "Hello, world!");
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct ServiceRegistry {
+    services: Arc<HashMap<TypeId, ServiceEntry>>,
+}
+
+// Services are only ever handed out behind an `Arc`, and lazily constructed services are guarded
+// by a `Mutex`, so a panic during a request cannot leave the registry in an inconsistent state.
+impl RefUnwindSafe for ServiceRegistry {}
+
+impl StateData for ServiceRegistry {}
+
+impl ServiceRegistry {
+    /// Creates a new, empty `ServiceRegistry`.
+    pub fn new() -> ServiceRegistry {
+        ServiceRegistry::default()
+    }
+
+    /// Registers a service instance, replacing any service previously registered for `T`.
+    ///
+    /// Registration must happen before the registry is shared with `ServiceRegistryMiddleware`.
+    pub fn register<T>(self, service: T) -> ServiceRegistry
+    where
+        T: Any + Send + Sync,
+    {
+        self.insert::<T>(ServiceEntry::Ready(Arc::new(service)))
+    }
+
+    /// Registers a service which is constructed by `init` the first time it is resolved. The same
+    /// instance is returned for every subsequent resolution.
+    pub fn register_lazy<T, F>(self, init: F) -> ServiceRegistry
+    where
+        T: Any + Send + Sync,
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        self.insert::<T>(ServiceEntry::Lazy {
+            init: Box::new(move || Arc::new(init())),
+            value: Mutex::new(None),
+        })
+    }
+
+    /// Resolves the service registered for `T`, if any.
+    pub fn resolve<T>(&self) -> Option<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        let type_id = TypeId::of::<T>();
+        trace!(" resolving service for type_id `{:?}`", type_id);
+
+        self.services
+            .get(&type_id)
+            .and_then(|entry| entry.get().downcast::<T>().ok())
+    }
+
+    fn insert<T>(mut self, entry: ServiceEntry) -> ServiceRegistry
+    where
+        T: Any,
+    {
+        Arc::get_mut(&mut self.services)
+            .expect("services must be registered before the ServiceRegistry is shared")
+            .insert(TypeId::of::<T>(), entry);
+        self
+    }
+}
+
+/// Middleware binding which attaches a `ServiceRegistry` to the request state.
+#[derive(Clone)]
+pub struct ServiceRegistryMiddleware {
+    registry: ServiceRegistry,
+}
+
+impl ServiceRegistryMiddleware {
+    /// Creates a new middleware binding, sharing the provided registry with every request.
+    pub fn new(registry: ServiceRegistry) -> Self {
+        ServiceRegistryMiddleware { registry }
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for ServiceRegistryMiddleware {
+    /// Attaches the `ServiceRegistry` to the request state.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        state.put(self.registry);
+        chain(state)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for ServiceRegistryMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use hyper::StatusCode;
+
+    use crate::pipeline::new_pipeline;
+    use crate::pipeline::single::single_pipeline;
+    use crate::router::builder::*;
+    use crate::state::FromState;
+    use crate::test::TestServer;
+
+    trait UserStore: Send + Sync {
+        fn name(&self, id: u32) -> String;
+    }
+
+    struct MockUserStore;
+
+    impl UserStore for MockUserStore {
+        fn name(&self, id: u32) -> String {
+            format!("user-{}", id)
+        }
+    }
+
+    fn handler(state: State) -> (State, String) {
+        let store = ServiceRegistry::borrow_from(&state)
+            .resolve::<Box<dyn UserStore>>()
+            .unwrap();
+
+        let body = store.name(42);
+        (state, body)
+    }
+
+    #[test]
+    fn resolves_registered_service_in_handler() {
+        let registry =
+            ServiceRegistry::new().register::<Box<dyn UserStore>>(Box::new(MockUserStore));
+
+        let (chain, pipelines) = single_pipeline(
+            new_pipeline()
+                .add(ServiceRegistryMiddleware::new(registry))
+                .build(),
+        );
+
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/").to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "user-42");
+    }
+
+    #[test]
+    fn lazy_services_are_constructed_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let registry = {
+            let calls = calls.clone();
+            ServiceRegistry::new().register_lazy(move || calls.fetch_add(1, Ordering::SeqCst))
+        };
+
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(*registry.resolve::<usize>().unwrap(), 0);
+        assert_eq!(*registry.resolve::<usize>().unwrap(), 0);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert!(registry.resolve::<String>().is_none());
+    }
+}
@@ -0,0 +1,277 @@
+//! Defines a middleware which sets the format of request IDs.
+//!
+//! Gotham assigns each request a UUID v4 as its ID when it is received, unless the client provides
+//! one via the `X-Request-ID` header. Adding `RequestIdMiddleware` to the pipelines used by every
+//! route replaces the UUID with an ID from the configured `RequestIdGenerator`. Log lines written
+//! by Gotham before the middleware is reached carry the original UUID; to avoid this, the
+//! generator can instead be applied to the whole server via
+//! `gotham::server::ServerConfig::with_request_id_generator`.
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use rand::Rng;
+use uuid::Uuid;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{replace_generated_request_id, State};
+
+/// The characters used by `Base62`.
+const BASE62: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Generates the ID of a request.
+///
+/// This is implemented for closures, so that a custom format can be used without a new type.
+pub trait RequestIdGenerator: Send + Sync + RefUnwindSafe {
+    /// Returns the ID for a new request.
+    fn generate(&self) -> String;
+}
+
+impl<F> RequestIdGenerator for F
+where
+    F: Fn() -> String + Send + Sync + RefUnwindSafe,
+{
+    fn generate(&self) -> String {
+        self()
+    }
+}
+
+/// Generates hyphenated UUID v4 IDs, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`. This is the
+/// format used when no `RequestIdMiddleware` is present.
+#[derive(Clone, Copy, Debug)]
+pub struct UuidV4;
+
+impl RequestIdGenerator for UuidV4 {
+    fn generate(&self) -> String {
+        Uuid::new_v4().to_hyphenated().to_string()
+    }
+}
+
+/// Generates random IDs of `[0-9A-Za-z]` characters, e.g. `4fJ0qLz8`.
+#[derive(Clone, Copy, Debug)]
+pub struct Base62 {
+    len: usize,
+}
+
+impl Base62 {
+    /// Creates a generator of random IDs of `len` characters.
+    pub fn new(len: usize) -> Base62 {
+        Base62 { len }
+    }
+}
+
+impl RequestIdGenerator for Base62 {
+    fn generate(&self) -> String {
+        let mut rng = rand::thread_rng();
+        (0..self.len)
+            .map(|_| char::from(BASE62[rng.gen_range(0, BASE62.len())]))
+            .collect()
+    }
+}
+
+/// Generates IDs from a counter which increases with each request, preceded by a prefix
+/// identifying the node, e.g. `node-a-1`, `node-a-2`.
+///
+/// The counter is shared by clones of the `Counter`, and starts from 1 when it is created.
+#[derive(Clone, Debug)]
+pub struct Counter {
+    prefix: String,
+    next: Arc<AtomicU64>,
+}
+
+impl Counter {
+    /// Creates a generator of IDs preceded by `prefix` and a hyphen.
+    pub fn new(prefix: &str) -> Counter {
+        Counter {
+            prefix: prefix.to_owned(),
+            next: Arc::new(AtomicU64::new(1)),
+        }
+    }
+}
+
+impl RequestIdGenerator for Counter {
+    fn generate(&self) -> String {
+        format!(
+            "{}-{}",
+            self.prefix,
+            self.next.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+/// Middleware binding which replaces the request ID assigned by Gotham with one from a
+/// `RequestIdGenerator`. A request ID provided by the client via the `X-Request-ID` header, or
+/// already replaced by an earlier `RequestIdMiddleware`, is retained.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response};
+/// # use gotham::middleware::request_id::{Counter, RequestIdMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{request_id, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let res = Response::new(Body::from(request_id(&state).to_owned()));
+///     (state, res)
+/// }
+///
+/// # fn main() {
+/// let middleware = RequestIdMiddleware::new(Counter::new("node-a"));
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let get = || {
+/// #     test_server
+/// #         .client()
+/// #         .get("https://example.com/")
+/// #         .perform()
+/// #         .unwrap()
+/// #         .read_utf8_body()
+/// #         .unwrap()
+/// # };
+/// # assert_eq!(get(), "node-a-1");
+/// # assert_eq!(get(), "node-a-2");
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RequestIdMiddleware {
+    generator: Arc<dyn RequestIdGenerator>,
+}
+
+impl RequestIdMiddleware {
+    /// Creates a new middleware binding, generating request IDs with `generator`.
+    pub fn new<G>(generator: G) -> RequestIdMiddleware
+    where
+        G: RequestIdGenerator + 'static,
+    {
+        RequestIdMiddleware {
+            generator: Arc::new(generator),
+        }
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for RequestIdMiddleware {
+    /// Replaces the generated request ID before the chain is invoked.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        replace_generated_request_id(&mut state, self.generator.generate());
+        chain(state)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for RequestIdMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::prelude::*;
+    use hyper::{Body, HeaderMap, Response};
+
+    use crate::state::{request_id, set_request_id};
+
+    fn request_id_with(middleware: &RequestIdMiddleware, headers: HeaderMap) -> String {
+        let mut state = State::new();
+        state.put(headers);
+        set_request_id(&mut state);
+
+        let handler = |state: State| future::ok((state, Response::new(Body::empty()))).boxed();
+        let m = middleware.new_middleware().unwrap();
+
+        match futures::executor::block_on(m.call(state, handler)) {
+            Ok((state, _)) => request_id(&state).to_owned(),
+            Err(_) => panic!("request failed"),
+        }
+    }
+
+    fn generated_id(middleware: &RequestIdMiddleware) -> String {
+        request_id_with(middleware, HeaderMap::new())
+    }
+
+    #[test]
+    fn uuid_v4_ids() {
+        let middleware = RequestIdMiddleware::new(UuidV4);
+        let id = generated_id(&middleware);
+
+        assert_eq!(id.len(), 36);
+        assert_eq!(Uuid::parse_str(&id).unwrap().get_version_num(), 4);
+        assert_ne!(generated_id(&middleware), id);
+    }
+
+    #[test]
+    fn base62_ids() {
+        let middleware = RequestIdMiddleware::new(Base62::new(12));
+
+        for _ in 0..10 {
+            let id = generated_id(&middleware);
+            assert_eq!(id.len(), 12);
+            assert!(id.bytes().all(|b| b.is_ascii_alphanumeric()));
+        }
+    }
+
+    #[test]
+    fn counter_ids() {
+        let middleware = RequestIdMiddleware::new(Counter::new("node-a"));
+
+        assert_eq!(generated_id(&middleware), "node-a-1");
+        assert_eq!(generated_id(&middleware), "node-a-2");
+        assert_eq!(generated_id(&middleware.clone()), "node-a-3");
+    }
+
+    #[test]
+    fn closure_ids() {
+        let middleware = RequestIdMiddleware::new(|| "fixed".to_owned());
+        assert_eq!(generated_id(&middleware), "fixed");
+    }
+
+    #[test]
+    fn external_ids_are_retained() {
+        let middleware = RequestIdMiddleware::new(Counter::new("node-a"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Request-ID", "client-id".parse().unwrap());
+
+        assert_eq!(request_id_with(&middleware, headers), "client-id");
+    }
+
+    #[test]
+    fn replaced_ids_are_retained() {
+        let outer = RequestIdMiddleware::new(|| "outer".to_owned());
+        let inner = RequestIdMiddleware::new(|| "inner".to_owned());
+
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+
+        let handler = |state: State| future::ok((state, Response::new(Body::empty()))).boxed();
+        let chain = move |state: State| inner.call(state, handler);
+
+        match futures::executor::block_on(outer.call(state, chain)) {
+            Ok((state, _)) => assert_eq!(request_id(&state), "outer"),
+            Err(_) => panic!("request failed"),
+        }
+    }
+}
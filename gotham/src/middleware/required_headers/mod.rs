@@ -0,0 +1,199 @@
+//! Defines a middleware which rejects requests missing any of a configured set of headers.
+use std::io;
+use std::pin::Pin;
+
+use futures::prelude::*;
+use hyper::header::{HeaderMap, HeaderName};
+use hyper::StatusCode;
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+/// Middleware binding which responds with `400 Bad Request` to requests which do not carry each
+/// of a configured set of headers, without invoking the handler.
+///
+/// The response body is plain text naming every missing header, in the order they were
+/// configured.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use hyper::header::HeaderName;
+/// # use gotham::middleware::required_headers::RequiredHeadersMiddleware;
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #     (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let middleware = RequiredHeadersMiddleware::new()
+///     .require(HeaderName::from_static("x-api-key"))
+///     .require(HeaderName::from_static("accept-version"));
+///
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("https://example.com/")
+/// #     .with_header("x-api-key", "secret".parse().unwrap())
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+/// # assert_eq!(
+/// #     response.read_utf8_body().unwrap(),
+/// #     "missing required headers: accept-version"
+/// # );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RequiredHeadersMiddleware {
+    headers: Vec<HeaderName>,
+}
+
+impl RequiredHeadersMiddleware {
+    /// Creates a new middleware binding, with no headers required.
+    pub fn new() -> RequiredHeadersMiddleware {
+        RequiredHeadersMiddleware {
+            headers: Vec::new(),
+        }
+    }
+
+    /// Adds a header which must be present on every request.
+    pub fn require(mut self, header: HeaderName) -> RequiredHeadersMiddleware {
+        if !self.headers.contains(&header) {
+            self.headers.push(header);
+        }
+        self
+    }
+
+    fn missing<'a>(&'a self, headers: Option<&HeaderMap>) -> Vec<&'a str> {
+        self.headers
+            .iter()
+            .filter(|name| headers.map_or(true, |headers| !headers.contains_key(*name)))
+            .map(HeaderName::as_str)
+            .collect()
+    }
+}
+
+impl Default for RequiredHeadersMiddleware {
+    fn default() -> RequiredHeadersMiddleware {
+        RequiredHeadersMiddleware::new()
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for RequiredHeadersMiddleware {
+    /// Invokes the chain only if each required header is present on the request.
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        let missing = self.missing(HeaderMap::try_borrow_from(&state));
+
+        if missing.is_empty() {
+            return chain(state);
+        }
+
+        let missing = missing.join(", ");
+        trace!(
+            "[{}] missing required headers: {}",
+            request_id(&state),
+            missing
+        );
+
+        let res = create_response(
+            &state,
+            StatusCode::BAD_REQUEST,
+            mime::TEXT_PLAIN,
+            format!("missing required headers: {}", missing),
+        );
+
+        future::ok((state, res)).boxed()
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for RequiredHeadersMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+    use hyper::{body, Body, Response};
+
+    use crate::helpers::http::response::create_empty_response;
+    use crate::state::set_request_id;
+
+    fn middleware() -> RequiredHeadersMiddleware {
+        RequiredHeadersMiddleware::new()
+            .require(HeaderName::from_static("x-api-key"))
+            .require(HeaderName::from_static("accept-version"))
+            .require(HeaderName::from_static("x-tenant"))
+    }
+
+    fn call(headers: &[(&'static str, &'static str)]) -> Response<Body> {
+        let mut map = HeaderMap::new();
+        for &(name, value) in headers {
+            map.insert(HeaderName::from_static(name), value.parse().unwrap());
+        }
+
+        let mut state = State::new();
+        state.put(map);
+        set_request_id(&mut state);
+
+        let result = block_on(middleware().call(state, |state| {
+            let res = create_empty_response(&state, StatusCode::OK);
+            future::ok((state, res)).boxed()
+        }));
+
+        match result {
+            Ok((_, res)) => res,
+            Err(_) => panic!("request failed"),
+        }
+    }
+
+    #[test]
+    fn requests_with_required_headers_pass() {
+        let res = call(&[
+            ("x-api-key", "secret"),
+            ("accept-version", "2"),
+            ("x-tenant", "acme"),
+        ]);
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn requests_missing_headers_are_rejected() {
+        let res = call(&[("accept-version", "2")]);
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body = block_on(body::to_bytes(res.into_body())).unwrap();
+        assert_eq!(
+            &body[..],
+            &b"missing required headers: x-api-key, x-tenant"[..]
+        );
+    }
+}
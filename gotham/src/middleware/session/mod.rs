@@ -51,6 +51,37 @@ pub enum SessionError {
     __NonExhaustive,
 }
 
+/// Determines how the session middleware responds when the `Backend` is unavailable, and fails to
+/// read or persist a session.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # use gotham::middleware::session::{NewSessionMiddleware, SessionFailurePolicy};
+/// # fn main() {
+/// NewSessionMiddleware::default()
+///     .with_failure_policy(SessionFailurePolicy::FailOpen)
+///     .with_session_type::<Option<String>>()
+/// # ;}
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionFailurePolicy {
+    /// Logs the failure and continues processing the request. When the session cannot be read,
+    /// the handler receives an empty session which is never persisted, so that the session held by
+    /// the `Backend` is not overwritten once it recovers. When the session cannot be persisted,
+    /// the response is sent without the session changes being saved.
+    FailOpen,
+
+    /// Logs the failure and responds with `500 Internal Server Error`. When the session cannot be
+    /// read, the handler is not invoked. This is the default policy.
+    FailClosed,
+}
+
+impl Default for SessionFailurePolicy {
+    fn default() -> SessionFailurePolicy {
+        SessionFailurePolicy::FailClosed
+    }
+}
+
 enum SessionCookieState {
     New,
     Existing,
@@ -59,6 +90,7 @@ enum SessionCookieState {
 enum SessionDataState {
     Clean,
     Dirty,
+    Unavailable,
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -276,6 +308,7 @@ where
     identifier: SessionIdentifier,
     backend: Box<dyn Backend + Send>,
     cookie_config: Arc<SessionCookieConfig>,
+    failure_policy: SessionFailurePolicy,
 }
 
 struct SessionDropData {
@@ -307,6 +340,7 @@ where
         let value = T::default();
         let backend = Box::new(middleware.backend);
         let cookie_config = middleware.cookie_config.clone();
+        let failure_policy = middleware.failure_policy;
 
         trace!(
             " no existing session, assigning new identifier ({})",
@@ -320,6 +354,27 @@ where
             identifier,
             backend,
             cookie_config,
+            failure_policy,
+        }
+    }
+
+    // Create a blank `SessionData<T>` for an existing session which could not be read from the
+    // `Backend`. The session is never persisted, even if it is modified.
+    fn unavailable<B>(
+        middleware: SessionMiddleware<B, T>,
+        identifier: SessionIdentifier,
+    ) -> SessionData<T>
+    where
+        B: Backend + Send + 'static,
+    {
+        SessionData {
+            value: T::default(),
+            cookie_state: SessionCookieState::Existing,
+            state: SessionDataState::Unavailable,
+            identifier,
+            backend: Box::new(middleware.backend),
+            cookie_config: middleware.cookie_config.clone(),
+            failure_policy: middleware.failure_policy,
         }
     }
 
@@ -341,6 +396,7 @@ where
                     Ok(value) => {
                         let backend = Box::new(middleware.backend);
                         let cookie_config = middleware.cookie_config.clone();
+                        let failure_policy = middleware.failure_policy;
 
                         trace!(
                             " successfully deserialized session data ({})",
@@ -354,6 +410,7 @@ where
                             identifier,
                             backend,
                             cookie_config,
+                            failure_policy,
                         }
                     }
                     Err(_) => {
@@ -393,7 +450,9 @@ where
     T: Default + Serialize + for<'de> Deserialize<'de> + Send + 'static,
 {
     fn deref_mut(&mut self) -> &mut T {
-        self.state = SessionDataState::Dirty;
+        if let SessionDataState::Clean = self.state {
+            self.state = SessionDataState::Dirty;
+        }
         &mut self.value
     }
 }
@@ -464,6 +523,7 @@ where
     new_backend: B,
     identifier_rng: Arc<Mutex<rng::SessionIdentifierRng>>,
     cookie_config: Arc<SessionCookieConfig>,
+    failure_policy: SessionFailurePolicy,
     phantom: PhantomData<dyn SessionTypePhantom<T>>,
 }
 
@@ -478,6 +538,7 @@ where
     backend: B,
     identifier_rng: Arc<Mutex<rng::SessionIdentifierRng>>,
     cookie_config: Arc<SessionCookieConfig>,
+    failure_policy: SessionFailurePolicy,
     phantom: PhantomData<T>,
 }
 
@@ -495,6 +556,7 @@ where
                 backend,
                 identifier_rng: self.identifier_rng.clone(),
                 cookie_config: self.cookie_config.clone(),
+                failure_policy: self.failure_policy,
                 phantom: PhantomData,
             })
     }
@@ -510,6 +572,7 @@ where
             new_backend: self.new_backend.clone(),
             identifier_rng: self.identifier_rng.clone(),
             cookie_config: self.cookie_config.clone(),
+            failure_policy: self.failure_policy,
             phantom: PhantomData,
         }
     }
@@ -532,6 +595,7 @@ where
             new_backend: b,
             identifier_rng: Arc::new(Mutex::new(rng::session_identifier_rng())),
             cookie_config: Arc::new(SessionCookieConfig::default()),
+            failure_policy: SessionFailurePolicy::default(),
             phantom: PhantomData,
         }
     }
@@ -759,6 +823,36 @@ where
         self.rebuild_new_session_middleware(cookie_config)
     }
 
+    /// Configures how the middleware responds when the `Backend` fails to read or persist a
+    /// session. Defaults to `SessionFailurePolicy::FailClosed`.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # #[macro_use]
+    /// # extern crate serde_derive;
+    /// #
+    /// # use gotham::middleware::session::{NewSessionMiddleware, SessionFailurePolicy};
+    /// #
+    /// # #[derive(Default, Serialize, Deserialize)]
+    /// # struct MySessionType {
+    /// #   items: Vec<String>,
+    /// # }
+    /// #
+    /// # fn main() {
+    /// NewSessionMiddleware::default()
+    ///     .with_failure_policy(SessionFailurePolicy::FailOpen)
+    ///     // ...
+    ///     .with_session_type::<MySessionType>()
+    /// # ;
+    /// # }
+    /// ```
+    pub fn with_failure_policy(self, failure_policy: SessionFailurePolicy) -> Self {
+        NewSessionMiddleware {
+            failure_policy,
+            ..self
+        }
+    }
+
     /// Changes the session type to the provided type parameter. This is required to override the
     /// default (unusable) session type of `()`.
     ///
@@ -786,6 +880,7 @@ where
             new_backend: self.new_backend,
             identifier_rng: self.identifier_rng,
             cookie_config: self.cookie_config,
+            failure_policy: self.failure_policy,
             phantom: PhantomData,
         }
     }
@@ -897,6 +992,14 @@ where
                     write_session(state, response, session_data).left_future()
                 }
                 SessionDataState::Clean => future::ok((state, response)).right_future(),
+                SessionDataState::Unavailable => {
+                    trace!(
+                        "[{}] session ({}) was unavailable, discarding changes",
+                        state::request_id(&state),
+                        session_data.identifier.value
+                    );
+                    future::ok((state, response)).right_future()
+                }
             }
         }
         // Session was discarded with `SessionData::discard`, or otherwise removed
@@ -971,10 +1074,21 @@ where
 
             future::ok((state, response))
         }
-        Err(_) => {
-            let response = create_empty_response(&state, StatusCode::INTERNAL_SERVER_ERROR);
+        Err(e) => {
+            error!(
+                "[{}] failed to persist session ({}) to backend: {:?}",
+                state::request_id(&state),
+                identifier.value,
+                e
+            );
 
-            future::ok((state, response))
+            match session_data.failure_policy {
+                SessionFailurePolicy::FailOpen => future::ok((state, response)),
+                SessionFailurePolicy::FailClosed => {
+                    let response = create_empty_response(&state, StatusCode::INTERNAL_SERVER_ERROR);
+                    future::ok((state, response))
+                }
+            }
         }
     }
 }
@@ -1012,12 +1126,28 @@ where
                     e
                 );
 
-                let e = io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("backend failed to return session: {:?}", e),
-                );
+                match self.failure_policy {
+                    SessionFailurePolicy::FailOpen => {
+                        warn!(
+                            "[{}] continuing with an empty session ({})",
+                            state::request_id(&state),
+                            identifier.value
+                        );
 
-                future::err((state, e.into_handler_error()))
+                        let session_data = SessionData::<T>::unavailable(self, identifier);
+
+                        state.put(session_data);
+                        future::ok(state)
+                    }
+                    SessionFailurePolicy::FailClosed => {
+                        let e = io::Error::new(
+                            io::ErrorKind::Other,
+                            format!("backend failed to return session: {:?}", e),
+                        );
+
+                        future::err((state, e.into_handler_error()))
+                    }
+                }
             }
         }
     }
@@ -1043,6 +1173,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::middleware::session::backend::SessionFuture;
     use cookie::Cookie;
     use hyper::header::{HeaderMap, COOKIE};
     use hyper::{Response, StatusCode};
@@ -1224,4 +1355,108 @@ mod tests {
 
         assert_eq!(updated.val, session.val + 1);
     }
+
+    #[derive(Clone)]
+    struct UnavailableBackend {
+        persisted: Arc<Mutex<bool>>,
+    }
+
+    impl NewBackend for UnavailableBackend {
+        type Instance = UnavailableBackend;
+
+        fn new_backend(&self) -> io::Result<Self::Instance> {
+            Ok(self.clone())
+        }
+    }
+
+    impl Backend for UnavailableBackend {
+        fn persist_session(&self, _: SessionIdentifier, _: &[u8]) -> Result<(), SessionError> {
+            *self.persisted.lock().unwrap() = true;
+            Err(SessionError::Backend("connection refused".to_owned()))
+        }
+
+        fn read_session(&self, _: SessionIdentifier) -> Pin<Box<SessionFuture>> {
+            future::err(SessionError::Backend("connection refused".to_owned())).boxed()
+        }
+
+        fn drop_session(&self, _: SessionIdentifier) -> Result<(), SessionError> {
+            Err(SessionError::Backend("connection refused".to_owned()))
+        }
+    }
+
+    fn call_with_unavailable_backend(
+        failure_policy: SessionFailurePolicy,
+        modify_session: bool,
+    ) -> (StatusCode, bool) {
+        let persisted = Arc::new(Mutex::new(false));
+        let backend = UnavailableBackend {
+            persisted: persisted.clone(),
+        };
+        let nm = NewSessionMiddleware::new(backend)
+            .with_failure_policy(failure_policy)
+            .with_session_type::<TestSession>();
+        let m = nm.new_middleware().unwrap();
+
+        let called = Arc::new(Mutex::new(false));
+        let c = called.clone();
+
+        let handler = move |mut state: State| {
+            {
+                let session_data = state.borrow_mut::<SessionData<TestSession>>();
+                assert_eq!(session_data.val, 0);
+                if modify_session {
+                    session_data.val += 1;
+                }
+                *c.lock().unwrap() = true;
+            }
+
+            future::ok((
+                state,
+                Response::builder()
+                    .status(StatusCode::ACCEPTED)
+                    .body(Body::empty())
+                    .unwrap(),
+            ))
+            .boxed()
+        };
+
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        let cookie = Cookie::build("_gotham_session", "existing").finish();
+        headers.insert(COOKIE, cookie.to_string().parse().unwrap());
+        state.put(headers);
+
+        let status = match futures::executor::block_on(m.call(state, handler)) {
+            Ok((_, response)) => response.status(),
+            Err((_, e)) => e.status(),
+        };
+
+        // A session which could not be read must never overwrite the stored session.
+        assert!(!*persisted.lock().unwrap());
+
+        let called = *called.lock().unwrap();
+        (status, called)
+    }
+
+    #[test]
+    fn unavailable_backend_fails_open() {
+        assert_eq!(
+            call_with_unavailable_backend(SessionFailurePolicy::FailOpen, false),
+            (StatusCode::ACCEPTED, true)
+        );
+
+        // Failing to persist the modified session does not affect the response either.
+        assert_eq!(
+            call_with_unavailable_backend(SessionFailurePolicy::FailOpen, true),
+            (StatusCode::ACCEPTED, true)
+        );
+    }
+
+    #[test]
+    fn unavailable_backend_fails_closed() {
+        assert_eq!(
+            call_with_unavailable_backend(SessionFailurePolicy::FailClosed, false),
+            (StatusCode::INTERNAL_SERVER_ERROR, false)
+        );
+    }
 }
@@ -0,0 +1,279 @@
+//! Defines a middleware which removes a path prefix from requests before they are routed.
+//!
+//! This is useful when an application is served behind a proxy which forwards requests under a
+//! path prefix (e.g. `/app`), while the application's routes are defined without it.
+use std::io;
+use std::pin::Pin;
+
+use futures::prelude::*;
+use hyper::{StatusCode, Uri};
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::request::path::RequestPathSegments;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// The prefix removed from the request path by `StripPrefixMiddleware`, stored in `State` so
+/// that links generated by the application can have it added again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrippedPrefix {
+    prefix: String,
+}
+
+impl StateData for StrippedPrefix {}
+
+impl StrippedPrefix {
+    /// The prefix which was removed from the request path, e.g. `/app`.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+}
+
+/// Middleware binding which removes a configured prefix from the request path, so that requests
+/// are routed as though the prefix was not present.
+///
+/// The `Uri` in `State` is rewritten without the prefix, and the removed prefix is stored as
+/// `StrippedPrefix`. The prefix only matches whole path segments, so a prefix of `/app` matches
+/// `/app` and `/app/users`, but not `/application`. By default, requests which do not start with
+/// the prefix are passed on unchanged; use `reject_unmatched` to respond with `404 Not Found`
+/// instead.
+///
+/// As middleware is invoked after routing, the router which holds the application's routes must
+/// be delegated to at the root of the tree, with this middleware in the pipeline of the
+/// delegating router.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::create_empty_response;
+/// # use gotham::middleware::strip_prefix::{StripPrefixMiddleware, StrippedPrefix};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn users(state: State) -> (State, Response<Body>) {
+///     assert_eq!(StrippedPrefix::borrow_from(&state).prefix(), "/app");
+///     let response = create_empty_response(&state, StatusCode::OK);
+///     (state, response)
+/// }
+///
+/// # fn main() {
+/// let app = build_simple_router(|route| {
+///     route.get("/users").to(users);
+/// });
+///
+/// let (chain, pipelines) = single_pipeline(
+///     new_pipeline()
+///         .add(StripPrefixMiddleware::new("/app"))
+///         .build(),
+/// );
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.delegate("/").to_router(app);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/app/users")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct StripPrefixMiddleware {
+    prefix: String,
+    reject_unmatched: bool,
+}
+
+impl StripPrefixMiddleware {
+    /// Creates a new middleware binding which removes `prefix` from request paths.
+    pub fn new(prefix: &str) -> Self {
+        StripPrefixMiddleware {
+            prefix: format!("/{}", prefix.trim_matches('/')),
+            reject_unmatched: false,
+        }
+    }
+
+    /// Responds with `404 Not Found` to requests which do not start with the prefix, rather than
+    /// passing them on unchanged.
+    pub fn reject_unmatched(self) -> Self {
+        StripPrefixMiddleware {
+            reject_unmatched: true,
+            ..self
+        }
+    }
+
+    /// Removes the prefix from the path, returning `None` if the path does not start with it.
+    fn strip<'a>(&self, path: &'a str) -> Option<&'a str> {
+        if self.prefix == "/" {
+            return Some(path);
+        }
+
+        if !path.starts_with(self.prefix.as_str()) {
+            return None;
+        }
+
+        match &path[self.prefix.len()..] {
+            "" => Some("/"),
+            rest if rest.starts_with('/') => Some(rest),
+            _ => None,
+        }
+    }
+}
+
+/// Rebuilds the `Uri` with a new path, retaining the query string.
+fn with_path(uri: &Uri, path: &str) -> Option<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_owned(),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for StripPrefixMiddleware {
+    /// Rewrites the request path without the prefix before the chain is invoked.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        let rewritten = {
+            let uri = Uri::borrow_from(&state);
+            self.strip(uri.path())
+                .and_then(|path| with_path(uri, path).map(|uri| (path.to_owned(), uri)))
+        };
+
+        match rewritten {
+            Some((path, uri)) => {
+                trace!(
+                    "[{}] stripped prefix {} from request path",
+                    request_id(&state),
+                    self.prefix
+                );
+
+                state.put(RequestPathSegments::new(&path));
+                state.put(uri);
+                state.put(StrippedPrefix {
+                    prefix: self.prefix,
+                });
+                chain(state)
+            }
+            None if self.reject_unmatched => {
+                trace!(
+                    "[{}] request path does not start with prefix {}",
+                    request_id(&state),
+                    self.prefix
+                );
+
+                let response = create_empty_response(&state, StatusCode::NOT_FOUND);
+                future::ok((state, response)).boxed()
+            }
+            None => chain(state),
+        }
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for StripPrefixMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response};
+
+    use crate::pipeline::new_pipeline;
+    use crate::pipeline::single::single_pipeline;
+    use crate::router::builder::*;
+    use crate::router::Router;
+    use crate::test::TestServer;
+
+    fn users(state: State) -> (State, Response<Body>) {
+        let uri = Uri::borrow_from(&state);
+        let body = format!(
+            "{} {} {}",
+            StrippedPrefix::try_borrow_from(&state)
+                .map(StrippedPrefix::prefix)
+                .unwrap_or("none"),
+            uri.path(),
+            uri.query().unwrap_or("")
+        );
+        let response = Response::new(Body::from(body));
+        (state, response)
+    }
+
+    fn router(middleware: StripPrefixMiddleware) -> Router {
+        let app = build_simple_router(|route| {
+            route.get("/users").to(users);
+        });
+
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+
+        build_router(chain, pipelines, |route| {
+            route.delegate("/").to_router(app);
+        })
+    }
+
+    fn get(router: Router, uri: &str) -> (StatusCode, String) {
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server.client().get(uri).perform().unwrap();
+        let status = response.status();
+        (status, response.read_utf8_body().unwrap())
+    }
+
+    #[test]
+    fn matching_prefix_is_stripped() {
+        let (status, body) = get(
+            router(StripPrefixMiddleware::new("/app/")),
+            "http://localhost/app/users?page=2",
+        );
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "/app /users page=2");
+    }
+
+    #[test]
+    fn non_matching_prefix_passes_unchanged() {
+        let (status, body) = get(
+            router(StripPrefixMiddleware::new("/app")),
+            "http://localhost/users",
+        );
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "none /users ");
+
+        let (status, _) = get(
+            router(StripPrefixMiddleware::new("/app")),
+            "http://localhost/application/users",
+        );
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn non_matching_prefix_is_rejected_if_configured() {
+        let (status, _) = get(
+            router(StripPrefixMiddleware::new("/app").reject_unmatched()),
+            "http://localhost/users",
+        );
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
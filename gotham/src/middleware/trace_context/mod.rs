@@ -0,0 +1,400 @@
+//! Defines a middleware which propagates W3C Trace Context (`traceparent` and `tracestate`)
+//! headers, so that requests can be correlated with a distributed trace.
+//!
+//! Each request is given a new span, which is a child of the span described by an incoming
+//! `traceparent` header, or the root of a new trace when the header is absent or invalid. The
+//! resulting `TraceContext` is stored in `State` for propagation to outbound requests, and echoed
+//! in the response headers.
+use std::fmt::Write;
+use std::io;
+use std::pin::Pin;
+
+use futures::prelude::*;
+use hyper::header::{HeaderMap, HeaderValue};
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// The header describing the trace and parent span of a request.
+pub const TRACEPARENT: &str = "traceparent";
+
+/// The header holding vendor specific trace data, which is propagated unchanged.
+pub const TRACESTATE: &str = "tracestate";
+
+/// The only version of the `traceparent` format which is generated.
+const VERSION: &str = "00";
+
+/// The `sampled` bit of the trace flags.
+const SAMPLED: u8 = 0x01;
+
+/// The trace context of the current request, stored in `State` by `TraceContextMiddleware`.
+///
+/// The `traceparent` and `tracestate` values should be sent on outbound requests made while
+/// handling the request, so that downstream services join the same trace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceContext {
+    trace_id: String,
+    span_id: String,
+    parent_id: Option<String>,
+    flags: u8,
+    tracestate: Option<String>,
+}
+
+impl StateData for TraceContext {}
+
+impl TraceContext {
+    /// The ID of the trace, as 32 lowercase hex characters.
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// The ID of the span created for this request, as 16 lowercase hex characters.
+    pub fn span_id(&self) -> &str {
+        &self.span_id
+    }
+
+    /// The ID of the span which made the request, if the request was part of an existing trace.
+    pub fn parent_id(&self) -> Option<&str> {
+        self.parent_id.as_ref().map(String::as_str)
+    }
+
+    /// Whether the trace has been sampled (i.e. recorded) by the caller.
+    pub fn sampled(&self) -> bool {
+        self.flags & SAMPLED != 0
+    }
+
+    /// The `tracestate` received with the request, if any.
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_ref().map(String::as_str)
+    }
+
+    /// The `traceparent` value identifying the span of this request, to be sent on outbound
+    /// requests.
+    pub fn traceparent(&self) -> String {
+        format!(
+            "{}-{}-{}-{:02x}",
+            VERSION, self.trace_id, self.span_id, self.flags
+        )
+    }
+
+    /// Creates the context for a new trace.
+    fn root(flags: u8) -> TraceContext {
+        TraceContext {
+            trace_id: random_id::<[u8; 16]>(),
+            span_id: random_id::<[u8; 8]>(),
+            parent_id: None,
+            flags,
+            tracestate: None,
+        }
+    }
+
+    /// Creates the context for a child of the span described by `traceparent`, returning `None` if
+    /// the value is invalid.
+    fn child(traceparent: &str, tracestate: Option<&str>) -> Option<TraceContext> {
+        let mut parts = traceparent.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let parent_id = parts.next()?;
+        let flags = parts.next()?;
+
+        // later versions may append fields, but version 00 has exactly four
+        let valid_version = is_hex(version, 2) && version != "ff";
+        if !valid_version || (version == VERSION && parts.next().is_some()) {
+            return None;
+        }
+
+        if !is_id(trace_id, 32) || !is_id(parent_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+
+        Some(TraceContext {
+            trace_id: trace_id.to_owned(),
+            span_id: random_id::<[u8; 8]>(),
+            parent_id: Some(parent_id.to_owned()),
+            flags: u8::from_str_radix(flags, 16).ok()?,
+            tracestate: tracestate.map(ToOwned::to_owned),
+        })
+    }
+}
+
+/// Whether `value` consists of `len` lowercase hex characters.
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Whether `value` is a valid trace or span ID, which must not be all zeros.
+fn is_id(value: &str, len: usize) -> bool {
+    is_hex(value, len) && value.bytes().any(|b| b != b'0')
+}
+
+/// Generates a random, non-zero ID, rendered as lowercase hex.
+fn random_id<T>() -> String
+where
+    T: AsRef<[u8]>,
+    rand::distributions::Standard: rand::distributions::Distribution<T>,
+{
+    loop {
+        let bytes = rand::random::<T>();
+        if bytes.as_ref().iter().any(|&b| b != 0) {
+            return bytes.as_ref().iter().fold(String::new(), |mut id, b| {
+                let _ = write!(id, "{:02x}", b);
+                id
+            });
+        }
+    }
+}
+
+/// Middleware binding which propagates W3C Trace Context headers, storing a `TraceContext` in
+/// `State` and adding `traceparent` (and any `tracestate`) headers to the response.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::create_empty_response;
+/// # use gotham::middleware::trace_context::{TraceContext, TraceContextMiddleware};
+/// # use gotham::pipeline::new_pipeline;
+/// # use gotham::pipeline::single::single_pipeline;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     {
+///         let context = TraceContext::borrow_from(&state);
+///         assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+///         assert_eq!(context.parent_id(), Some("00f067aa0ba902b7"));
+///     }
+///
+///     let response = create_empty_response(&state, StatusCode::OK);
+///     (state, response)
+/// }
+///
+/// # fn main() {
+/// let (chain, pipelines) =
+///     single_pipeline(new_pipeline().add(TraceContextMiddleware::new()).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/")
+/// #     .with_header(
+/// #         "traceparent",
+/// #         "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".parse().unwrap(),
+/// #     )
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TraceContextMiddleware {
+    sample_new_traces: bool,
+}
+
+impl TraceContextMiddleware {
+    /// Creates a new middleware binding. New traces are not marked as sampled.
+    pub fn new() -> TraceContextMiddleware {
+        TraceContextMiddleware {
+            sample_new_traces: false,
+        }
+    }
+
+    /// Marks new traces, started for requests without a valid `traceparent`, as sampled. Incoming
+    /// traces always retain the flags of the caller.
+    pub fn sample_new_traces(self, sample_new_traces: bool) -> TraceContextMiddleware {
+        TraceContextMiddleware { sample_new_traces }
+    }
+}
+
+impl Default for TraceContextMiddleware {
+    fn default() -> TraceContextMiddleware {
+        TraceContextMiddleware::new()
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for TraceContextMiddleware {
+    /// Stores the `TraceContext` of the request before the chain is invoked, and adds the trace
+    /// headers to the response.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        let context = {
+            let headers = HeaderMap::borrow_from(&state);
+            let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+            header(TRACEPARENT)
+                .and_then(|traceparent| TraceContext::child(traceparent, header(TRACESTATE)))
+        };
+
+        let context = match context {
+            Some(context) => context,
+            None => {
+                trace!("[{}] starting new trace", request_id(&state));
+                let flags = if self.sample_new_traces { SAMPLED } else { 0 };
+                TraceContext::root(flags)
+            }
+        };
+
+        trace!(
+            "[{}] trace context: {}",
+            request_id(&state),
+            context.traceparent()
+        );
+
+        let traceparent = HeaderValue::from_str(&context.traceparent()).unwrap();
+        let tracestate = context
+            .tracestate()
+            .and_then(|tracestate| HeaderValue::from_str(tracestate).ok());
+
+        state.put(context);
+
+        chain(state)
+            .and_then(move |(state, mut response)| {
+                let headers = response.headers_mut();
+                headers.insert(TRACEPARENT, traceparent);
+                if let Some(tracestate) = tracestate {
+                    headers.insert(TRACESTATE, tracestate);
+                }
+
+                future::ok((state, response))
+            })
+            .boxed()
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for TraceContextMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> io::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::{Body, Response};
+
+    use crate::state::set_request_id;
+
+    const INCOMING: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn call(
+        middleware: TraceContextMiddleware,
+        headers: &[(&'static str, &str)],
+    ) -> (State, Response<Body>) {
+        let mut state = State::new();
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, value.parse().unwrap());
+        }
+        state.put(map);
+        set_request_id(&mut state);
+
+        let handler = |state: State| future::ok((state, Response::new(Body::empty()))).boxed();
+
+        match futures::executor::block_on(middleware.call(state, handler)) {
+            Ok(result) => result,
+            Err((_, e)) => panic!("error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn incoming_traceparent_produces_child_context() {
+        let (state, response) = call(
+            TraceContextMiddleware::new(),
+            &[(TRACEPARENT, INCOMING), (TRACESTATE, "vendor=value")],
+        );
+
+        let context = TraceContext::borrow_from(&state);
+        assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.parent_id(), Some("00f067aa0ba902b7"));
+        assert_ne!(context.span_id(), "00f067aa0ba902b7");
+        assert!(is_id(context.span_id(), 16));
+        assert!(context.sampled());
+        assert_eq!(context.tracestate(), Some("vendor=value"));
+
+        assert_eq!(
+            response.headers()[TRACEPARENT],
+            format!(
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-{}-01",
+                context.span_id()
+            )
+            .as_str()
+        );
+        assert_eq!(response.headers()[TRACESTATE], "vendor=value");
+    }
+
+    #[test]
+    fn missing_traceparent_starts_new_trace() {
+        let (state, response) = call(TraceContextMiddleware::new(), &[]);
+
+        let context = TraceContext::borrow_from(&state);
+        assert!(is_id(context.trace_id(), 32));
+        assert!(is_id(context.span_id(), 16));
+        assert_eq!(context.parent_id(), None);
+        assert!(!context.sampled());
+
+        assert_eq!(
+            response.headers()[TRACEPARENT],
+            context.traceparent().as_str()
+        );
+        assert!(response.headers().get(TRACESTATE).is_none());
+
+        let (state, _) = call(TraceContextMiddleware::new().sample_new_traces(true), &[]);
+        assert!(TraceContext::borrow_from(&state).sampled());
+    }
+
+    #[test]
+    fn invalid_traceparent_starts_new_trace() {
+        let invalid = [
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        ];
+
+        for traceparent in invalid.iter() {
+            let (state, _) = call(
+                TraceContextMiddleware::new(),
+                &[(TRACEPARENT, *traceparent), (TRACESTATE, "vendor=value")],
+            );
+
+            let context = TraceContext::borrow_from(&state);
+            assert_ne!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+            assert_eq!(context.parent_id(), None);
+            assert_eq!(context.tracestate(), None);
+        }
+    }
+
+    #[test]
+    fn future_versions_are_accepted() {
+        let traceparent = "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra";
+        let (state, response) = call(TraceContextMiddleware::new(), &[(TRACEPARENT, traceparent)]);
+
+        let context = TraceContext::borrow_from(&state);
+        assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(response.headers()[TRACEPARENT]
+            .to_str()
+            .unwrap()
+            .starts_with("00-"));
+    }
+}
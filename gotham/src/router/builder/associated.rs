@@ -330,6 +330,10 @@ where
             matcher: AndRouteMatcher::new(MethodOnlyRouteMatcher::new(methods), matcher.clone()),
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            description: None,
+            produces: Vec::new(),
+            requires_body: false,
+            json_schema: None,
             phantom,
         }
     }
@@ -0,0 +1,50 @@
+//! Defines the `Handler` used by `DefineSingleRoute::to_constant`.
+
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures::prelude::*;
+use hyper::header::CONTENT_LENGTH;
+use hyper::StatusCode;
+use mime::Mime;
+
+use crate::error::Result;
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::helpers::http::response::create_response;
+use crate::state::State;
+
+/// A `Handler` which responds to every request with the same status, content type and body.
+///
+/// The body is shared between requests, so cloning the handler for each request does not copy it.
+#[derive(Clone)]
+pub(super) struct ConstantHandler {
+    status: StatusCode,
+    mime: Mime,
+    body: Bytes,
+}
+
+impl ConstantHandler {
+    pub(super) fn new(status: StatusCode, mime: Mime, body: Bytes) -> ConstantHandler {
+        ConstantHandler { status, mime, body }
+    }
+}
+
+impl NewHandler for ConstantHandler {
+    type Instance = Self;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for ConstantHandler {
+    fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
+        let len = self.body.len();
+        let mut res = create_response(&state, self.status, self.mime, self.body);
+
+        res.headers_mut()
+            .insert(CONTENT_LENGTH, len.to_string().parse().unwrap());
+
+        future::ok((state, res)).boxed()
+    }
+}
@@ -5,18 +5,20 @@ use hyper::Method;
 use log::trace;
 
 use crate::extractor::{NoopPathExtractor, NoopQueryStringExtractor};
+use crate::handler::{Handler, NewHandler};
 use crate::helpers::http::request::path::split_path_segments;
 use crate::pipeline::chain::PipelineHandleChain;
 use crate::pipeline::set::PipelineSet;
 use crate::router::builder::{
-    AssociatedRouteBuilder, DelegateRouteBuilder, RouterBuilder, ScopeBuilder, SingleRouteBuilder,
+    AssociatedRouteBuilder, DefineSingleRoute, DelegateRouteBuilder, ResourceHandlers, ResourceId,
+    RouterBuilder, ScopeBuilder, SingleRouteBuilder,
 };
 use crate::router::route::matcher::{
     AnyRouteMatcher, IntoRouteMatcher, MethodOnlyRouteMatcher, RouteMatcher,
 };
 use crate::router::tree::node::Node;
 use crate::router::tree::regex::ConstrainedSegmentRegex;
-use crate::router::tree::segment::SegmentType;
+use crate::router::tree::segment::{PathSegment, SegmentType};
 
 /// The type returned when building a route that only considers path and http verb(s) when
 /// determining if it matches a request.
@@ -462,8 +464,9 @@ where
         IRM: IntoRouteMatcher<Output = M>,
         M: RouteMatcher + Send + Sync + 'static,
     {
+        let inherited = self.parameter_names().to_vec();
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
-        let node_builder = descend(node_builder, path);
+        let node_builder = descend(node_builder, path, &inherited);
         let matcher = matcher.into_route_matcher();
 
         SingleRouteBuilder {
@@ -471,6 +474,10 @@ where
             node_builder,
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            description: None,
+            produces: Vec::new(),
+            requires_body: false,
+            json_schema: None,
             phantom: PhantomData,
         }
     }
@@ -518,13 +525,21 @@ where
     where
         F: FnOnce(&mut ScopeBuilder<C, P>),
     {
+        let mut parameter_names = self.parameter_names().to_vec();
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
-        let node_builder = descend(node_builder, path);
+        let node_builder = descend(node_builder, path, &parameter_names);
+
+        parameter_names.extend(
+            split_path_segments(path)
+                .filter_map(|segment| PathSegment::parse(segment).name())
+                .map(ToOwned::to_owned),
+        );
 
         let mut scope_builder = ScopeBuilder {
             node_builder,
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            parameter_names,
         };
 
         f(&mut scope_builder)
@@ -636,12 +651,14 @@ where
         F: FnOnce(&mut ScopeBuilder<NC, P>),
         NC: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
     {
+        let parameter_names = self.parameter_names().to_vec();
         let (node_builder, _pipeline_chain, pipelines) = self.component_refs();
 
         let mut scope_builder = ScopeBuilder {
             node_builder,
             pipeline_chain,
             pipelines: pipelines.clone(),
+            parameter_names,
         };
 
         f(&mut scope_builder)
@@ -688,8 +705,9 @@ where
     /// # }
     /// ```
     fn delegate<'b>(&'b mut self, path: &str) -> DelegateRouteBuilder<'b, C, P> {
+        let inherited = self.parameter_names().to_vec();
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
-        let node_builder = descend(node_builder, path);
+        let node_builder = descend(node_builder, path, &inherited);
 
         DelegateRouteBuilder {
             node_builder,
@@ -770,8 +788,9 @@ where
     /// # }
     /// ```
     fn delegate_without_pipelines<'b>(&'b mut self, path: &str) -> DelegateRouteBuilder<'b, (), P> {
+        let inherited = self.parameter_names().to_vec();
         let (node_builder, _pipeline_chain, pipelines) = self.component_refs();
-        let node_builder = descend(node_builder, path);
+        let node_builder = descend(node_builder, path, &inherited);
 
         DelegateRouteBuilder {
             node_builder,
@@ -780,6 +799,53 @@ where
         }
     }
 
+    /// Mounts a `NewHandler` which does its own routing at a subpath of the tree. Requests to the
+    /// path, and any path below it, are dispatched via this router's `PipelineChain` to the
+    /// mounted handler, with the matched prefix removed from the `Uri` in `State`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode, Uri};
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn files(state: State) -> (State, Response<Body>) {
+    ///     // The path is relative to the mount point, e.g. `/docs/index.html`.
+    ///     let path = Uri::borrow_from(&state).path().to_owned();
+    ///     let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, path);
+    ///     (state, res)
+    /// }
+    ///
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.mount("/files", || Ok(files));
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/files/docs/index.html")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "/docs/index.html");
+    /// # }
+    /// ```
+    fn mount<NH>(&mut self, path: &str, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+    {
+        self.delegate(path).to_mounted(new_handler)
+    }
+
     /// Begins associating routes with a fixed path in the tree. In this way, multiple routes can
     /// be quickly associated with a single location.
     ///
@@ -850,8 +916,9 @@ where
     where
         F: FnOnce(&mut DefaultAssociatedRouteBuilder<'b, AnyRouteMatcher, C, P>),
     {
+        let inherited = self.parameter_names().to_vec();
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
-        let node_builder = descend(node_builder, path);
+        let node_builder = descend(node_builder, path, &inherited);
 
         let mut builder =
             AssociatedRouteBuilder::new(node_builder, *pipeline_chain, pipelines.clone());
@@ -859,12 +926,113 @@ where
         f(&mut builder)
     }
 
+    /// Registers the standard CRUD routes for a REST collection in a single call. See
+    /// `ResourceHandlers` for the routes which are created.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::test::TestServer;
+    /// #
+    /// mod users {
+    /// #   use super::*;
+    ///     pub fn index(state: State) -> (State, &'static str) {
+    ///         (state, "all users")
+    ///     }
+    ///
+    ///     pub fn show(state: State) -> (State, String) {
+    ///         let body = format!("user {}", ResourceId::borrow_from(&state).id);
+    ///         (state, body)
+    ///     }
+    ///
+    ///     // Remaining handlers elided.
+    /// #   pub fn create(state: State) -> (State, &'static str) { (state, "") }
+    /// #   pub fn update(state: State) -> (State, &'static str) { (state, "") }
+    /// #   pub fn delete(state: State) -> (State, &'static str) { (state, "") }
+    /// }
+    ///
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.resource(
+    ///         "users",
+    ///         ResourceHandlers {
+    ///             index: users::index,
+    ///             create: users::create,
+    ///             show: users::show,
+    ///             update: users::update,
+    ///             delete: users::delete,
+    ///         },
+    ///     );
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users/")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "all users");
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users/42")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "user 42");
+    /// # }
+    /// ```
+    fn resource<IH, CH, SH, UH, DH>(
+        &mut self,
+        name: &str,
+        handlers: ResourceHandlers<IH, CH, SH, UH, DH>,
+    ) where
+        IH: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+        CH: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+        SH: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+        UH: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+        DH: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+    {
+        let collection = format!("/{}", name.trim_matches('/'));
+        let member = format!("{}/:id", collection);
+
+        self.get_or_head(&collection).to(handlers.index);
+        self.post(&collection).to(handlers.create);
+
+        self.get_or_head(&member)
+            .with_path_extractor::<ResourceId>()
+            .to(handlers.show);
+
+        self.request(vec![Method::PUT, Method::PATCH], &member)
+            .with_path_extractor::<ResourceId>()
+            .to(handlers.update);
+
+        self.delete(&member)
+            .with_path_extractor::<ResourceId>()
+            .to(handlers.delete);
+    }
+
     /// Return the components that comprise this builder. For internal use only.
     #[doc(hidden)]
     fn component_refs(&mut self) -> (&mut Node, &mut C, &PipelineSet<P>);
+
+    /// Return the names of the path parameters extracted by enclosing scopes. For internal use
+    /// only.
+    #[doc(hidden)]
+    fn parameter_names(&self) -> &[String] {
+        &[]
+    }
 }
 
-fn descend<'n>(node_builder: &'n mut Node, path: &str) -> &'n mut Node {
+fn descend<'n>(node_builder: &'n mut Node, path: &str, inherited: &[String]) -> &'n mut Node {
     trace!("[walking to: {}]", path);
 
     let path = if path.starts_with('/') {
@@ -876,10 +1044,33 @@ fn descend<'n>(node_builder: &'n mut Node, path: &str) -> &'n mut Node {
     if path.is_empty() {
         node_builder
     } else {
+        check_parameter_names(path, inherited);
         build_subtree(node_builder, split_path_segments(path))
     }
 }
 
+/// Panics if a dynamic segment name is used more than once in `path`, or is already used by the
+/// `inherited` names of enclosing scopes, as the later value would silently replace the earlier one
+/// when the path is extracted.
+fn check_parameter_names(path: &str, inherited: &[String]) {
+    let mut names: Vec<&str> = inherited.iter().map(String::as_str).collect();
+
+    for segment in split_path_segments(path) {
+        let name = match PathSegment::parse(segment).name() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        if names.contains(&name) {
+            panic!(
+                "path parameter name `{}` is used more than once in route path `{}`",
+                name, path
+            );
+        }
+        names.push(name);
+    }
+}
+
 fn build_subtree<'n, 's, I>(node: &'n mut Node, mut i: I) -> &'n mut Node
 where
     I: Iterator<Item = &'s str>,
@@ -888,21 +1079,20 @@ where
         Some(segment) => {
             trace!("[descending into {}]", segment);
 
-            let (segment, segment_type) = match segment.chars().next() {
-                Some(':') => {
-                    let segment = &segment[1..];
-                    match segment.find(':') {
-                        Some(n) => {
-                            let (segment, pattern) = segment.split_at(n);
-                            let regex = ConstrainedSegmentRegex::new(&pattern[1..]);
-                            (segment, SegmentType::Constrained { regex })
-                        }
-                        None => (segment, SegmentType::Dynamic),
-                    }
+            let (segment, segment_type) = match PathSegment::parse(segment) {
+                PathSegment::Static(segment) => (segment, SegmentType::Static),
+                PathSegment::Dynamic {
+                    name,
+                    constraint: Some(pattern),
+                } => {
+                    let regex = ConstrainedSegmentRegex::new(pattern);
+                    (name, SegmentType::Constrained { regex })
                 }
-                Some('*') if segment.len() == 1 => (segment, SegmentType::Glob),
-                Some('\\') => (&segment[1..], SegmentType::Static),
-                _ => (segment, SegmentType::Static),
+                PathSegment::Dynamic {
+                    name,
+                    constraint: None,
+                } => (name, SegmentType::Dynamic),
+                PathSegment::Glob => (segment, SegmentType::Glob),
             };
 
             if !node.has_child(segment, segment_type.clone()) {
@@ -945,6 +1135,10 @@ where
             &self.pipelines,
         )
     }
+
+    fn parameter_names(&self) -> &[String] {
+        &self.parameter_names
+    }
 }
 
 #[cfg(test)]
@@ -953,7 +1147,7 @@ mod tests {
     use std::pin::Pin;
 
     use futures::prelude::*;
-    use hyper::{Body, Response, StatusCode};
+    use hyper::{Body, Response, StatusCode, Uri};
 
     use crate::handler::HandlerFuture;
     use crate::helpers::http::response::create_empty_response;
@@ -961,7 +1155,7 @@ mod tests {
     use crate::pipeline::single::*;
     use crate::pipeline::*;
     use crate::router::builder::*;
-    use crate::state::State;
+    use crate::state::{FromState, State};
     use crate::test::TestServer;
 
     #[derive(Clone, Copy)]
@@ -1041,4 +1235,146 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::ACCEPTED);
     }
+
+    #[test]
+    #[should_panic(expected = "path parameter name `id` is used more than once")]
+    fn duplicate_parameter_names_are_rejected() {
+        build_simple_router(|route| {
+            route.get("/:id/x/:id").to(test_handler);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "path parameter name `id` is used more than once")]
+    fn duplicate_constrained_parameter_names_are_rejected() {
+        build_simple_router(|route| {
+            route.get("/:id:[0-9]+/x/:id").to(test_handler);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "path parameter name `id` is used more than once")]
+    fn parameter_names_of_enclosing_scopes_are_rejected() {
+        build_simple_router(|route| {
+            route.scope("/users/:id", |route| {
+                route.scope("/posts", |route| {
+                    route.get("/:id").to(test_handler);
+                });
+            });
+        });
+    }
+
+    #[test]
+    fn distinct_parameter_names_are_accepted() {
+        let router = build_simple_router(|route| {
+            route.get("/:id/x/:other_id").to(test_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/1/x/2")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[test]
+    fn mount_strips_matched_prefix() {
+        fn echo_path(state: State) -> (State, String) {
+            let path = Uri::borrow_from(&state).path().to_owned();
+            (state, path)
+        }
+
+        let router = build_simple_router(|route| {
+            route.mount("/mount", || Ok(echo_path));
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let client = test_server.client();
+
+        let response = client.get("http://localhost/mount/a/b").perform().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "/a/b");
+
+        let response = client.get("http://localhost/mount").perform().unwrap();
+        assert_eq!(response.read_utf8_body().unwrap(), "/");
+    }
+
+    mod users {
+        use super::*;
+
+        pub fn index(state: State) -> (State, String) {
+            (state, "index".to_owned())
+        }
+
+        pub fn create(state: State) -> (State, String) {
+            (state, "create".to_owned())
+        }
+
+        pub fn show(state: State) -> (State, String) {
+            let body = format!("show {}", ResourceId::borrow_from(&state).id);
+            (state, body)
+        }
+
+        pub fn update(state: State) -> (State, String) {
+            let body = format!("update {}", ResourceId::borrow_from(&state).id);
+            (state, body)
+        }
+
+        pub fn delete(state: State) -> (State, String) {
+            let body = format!("delete {}", ResourceId::borrow_from(&state).id);
+            (state, body)
+        }
+    }
+
+    #[test]
+    fn resource_registers_crud_routes() {
+        let router = build_simple_router(|route| {
+            route.resource(
+                "users",
+                ResourceHandlers {
+                    index: users::index,
+                    create: users::create,
+                    show: users::show,
+                    update: users::update,
+                    delete: users::delete,
+                },
+            );
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let client = test_server.client();
+
+        for uri in &["http://localhost/users", "http://localhost/users/"] {
+            let response = client.get(*uri).perform().unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.read_utf8_body().unwrap(), "index");
+
+            let response = client.post(*uri, "", mime::TEXT_PLAIN).perform().unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.read_utf8_body().unwrap(), "create");
+        }
+
+        for uri in &["http://localhost/users/7", "http://localhost/users/7/"] {
+            let response = client.get(*uri).perform().unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.read_utf8_body().unwrap(), "show 7");
+
+            let response = client.put(*uri, "", mime::TEXT_PLAIN).perform().unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.read_utf8_body().unwrap(), "update 7");
+
+            let response = client.patch(*uri, "", mime::TEXT_PLAIN).perform().unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.read_utf8_body().unwrap(), "update 7");
+
+            let response = client.delete(*uri).perform().unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.read_utf8_body().unwrap(), "delete 7");
+        }
+
+        let response = client.delete("http://localhost/users").perform().unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
 }
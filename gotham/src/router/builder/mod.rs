@@ -1,18 +1,28 @@
 //! Defines a builder API for constructing a `Router`.
 
 mod associated;
+mod constant;
 mod draw;
 mod modify;
+mod mount;
+mod require_body;
+mod resource;
 mod single;
+mod validate_json;
 
 use std::marker::PhantomData;
 use std::panic::RefUnwindSafe;
+use std::sync::Arc;
 
-use hyper::{Body, StatusCode};
+use hyper::{Body, Response, StatusCode, Version};
+use mime::Mime;
 
 use crate::extractor::{
     NoopPathExtractor, NoopQueryStringExtractor, PathExtractor, QueryStringExtractor,
 };
+use crate::handler::{HandlerError, NewHandler};
+use crate::helpers::http::request::json_schema::JsonSchema;
+use crate::helpers::http::response::create_empty_response;
 use crate::pipeline::chain::PipelineHandleChain;
 use crate::pipeline::set::{finalize_pipeline_set, new_pipeline_set, PipelineSet};
 use crate::router::response::extender::ResponseExtender;
@@ -22,11 +32,13 @@ use crate::router::route::matcher::{AnyRouteMatcher, RouteMatcher};
 use crate::router::route::{Delegation, Extractors, RouteImpl};
 use crate::router::tree::node::Node;
 use crate::router::tree::Tree;
-use crate::router::Router;
+use crate::router::{Router, RouterSettings};
+use crate::state::State;
 
 pub use self::associated::{AssociatedRouteBuilder, AssociatedSingleRouteBuilder};
 pub use self::draw::DrawRoutes;
 pub use self::modify::{ExtendRouteMatcher, ReplacePathExtractor, ReplaceQueryStringExtractor};
+pub use self::resource::{ResourceHandlers, ResourceId};
 pub use self::single::DefineSingleRoute;
 
 /// Builds a `Router` using the provided closure. Routes are defined using the `RouterBuilder`
@@ -90,20 +102,24 @@ where
 {
     let mut tree = Tree::new();
 
-    let response_finalizer = {
+    let (response_finalizer, settings) = {
         let mut builder = RouterBuilder {
             node_builder: tree.borrow_root_mut(),
             pipeline_chain,
             pipelines,
             response_finalizer_builder: ResponseFinalizerBuilder::internal_new(),
+            settings: RouterSettings::default(),
         };
 
         f(&mut builder);
 
-        builder.response_finalizer_builder.finalize()
+        (
+            builder.response_finalizer_builder.finalize(),
+            builder.settings,
+        )
     };
 
-    Router::internal_new(tree, response_finalizer)
+    Router::internal_new(tree, response_finalizer, settings)
 }
 
 /// Builds a `Router` with **no** middleware using the provided closure. Routes are defined using
@@ -158,6 +174,7 @@ where
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
     response_finalizer_builder: ResponseFinalizerBuilder,
+    settings: RouterSettings,
 }
 
 impl<'a, C, P> RouterBuilder<'a, C, P>
@@ -222,6 +239,440 @@ where
         self.response_finalizer_builder
             .add(status_code, Box::new(extender))
     }
+
+    /// Adds an `X-Response-Time` header to every response from the `Router`, holding the number of
+    /// milliseconds elapsed since the request was received.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.add_response_time_header();
+    ///         route.get("/").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let millis: f64 = response.headers()["x-response-time"].to_str().unwrap().parse().unwrap();
+    /// #   assert!(millis >= 0.0);
+    /// # }
+    /// ```
+    pub fn add_response_time_header(&mut self) {
+        self.response_finalizer_builder.add_response_time_header()
+    }
+
+    /// Normalizes the request headers before the `Router` selects a route, so that header based
+    /// `RouteMatcher` implementations behave predictably. Leading and trailing whitespace is
+    /// trimmed from every header value. Header names are always held in their canonical lowercase
+    /// form, whatever casing the client used.
+    ///
+    /// The normalized headers are also seen by middleware and handlers. Requests delegated to a
+    /// secondary `Router` are normalized before delegation.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use hyper::header::HeaderName;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::router::route::matcher::HeaderRouteMatcher;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.normalize_headers();
+    ///
+    ///         let matcher =
+    ///             HeaderRouteMatcher::new("x-api-version".parse().unwrap(), "2".parse().unwrap());
+    ///         route.get("/").add_route_matcher(matcher).to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/")
+    /// #       .with_header(
+    /// #           HeaderName::from_bytes(b"X-Api-Version").unwrap(),
+    /// #           " 2 ".parse().unwrap(),
+    /// #       )
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    pub fn normalize_headers(&mut self) {
+        self.settings.normalize_headers = true;
+    }
+
+    /// Responds with `505 HTTP Version Not Supported` to requests made with an HTTP version older
+    /// than `version`, before any routing takes place.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode, Version};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         // Reject HTTP/1.0 (and HTTP/0.9) clients.
+    ///         route.min_http_version(Version::HTTP_11);
+    ///         route.get("/").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    pub fn min_http_version(&mut self, version: Version) {
+        self.settings.min_http_version = Some(version);
+    }
+
+    /// Responds with `400 Bad Request` to requests whose query string contains more than `max`
+    /// parameters, before any routing or extraction takes place. Repeated keys count once for
+    /// each occurrence.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.max_query_params(32);
+    ///         route.get("/search").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/search?q=gotham")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    pub fn max_query_params(&mut self, max: usize) {
+        self.settings.max_query_params = Some(max);
+    }
+
+    /// Renders the response for handlers which fail with a `HandlerError`, in place of the
+    /// default empty response with the status of the `HandlerError`. This applies to every route
+    /// in the `Router`, and the response is passed to any `ResponseExtender` registered for its
+    /// status as normal.
+    ///
+    /// Without this, a `ResponseExtender` such as `JsonErrorExtender` registered for the status
+    /// of the `HandlerError` can be used to render its body.
+    ///
+    /// ```rust
+    /// # extern crate futures;
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use std::pin::Pin;
+    /// #
+    /// # use futures::prelude::*;
+    /// # use hyper::StatusCode;
+    /// # use gotham::handler::{HandlerFuture, IntoHandlerError};
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn my_handler(state: State) -> Pin<Box<HandlerFuture>> {
+    ///     let err = std::io::Error::new(std::io::ErrorKind::Other, "disk on fire");
+    ///     future::err((state, err.into_handler_error())).boxed()
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.handler_error_response(|state, err| {
+    ///             let body = format!("request failed with status {}", err.status().as_u16());
+    ///             create_response(state, err.status(), mime::TEXT_PLAIN, body)
+    ///         });
+    ///
+    ///         route.get("/").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "request failed with status 500");
+    /// # }
+    /// ```
+    pub fn handler_error_response<F>(&mut self, f: F)
+    where
+        F: Fn(&State, HandlerError) -> Response<Body> + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.settings.handler_error_response = Some(Arc::new(f));
+    }
+
+    /// Answers CORS preflight requests via `pipeline_chain` alone, so that they are not subject to
+    /// the pipelines (e.g. authentication) of the route they target.
+    ///
+    /// A preflight request is an `OPTIONS` request carrying both `Origin` and
+    /// `Access-Control-Request-Method` headers. It is only answered here when it targets a routed
+    /// path and no route, including any `OPTIONS` route defined for the path, matches it. Other
+    /// `OPTIONS` requests are routed as usual.
+    ///
+    /// The handler at the end of the chain responds with `204 No Content`; middleware in the
+    /// chain adds any CORS headers, or responds itself.
+    ///
+    /// ```rust
+    /// # extern crate futures;
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use std::pin::Pin;
+    /// #
+    /// # use futures::prelude::*;
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use hyper::header::{ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN};
+    /// # use gotham::handler::HandlerFuture;
+    /// # use gotham::middleware::{Middleware, NewMiddleware};
+    /// # use gotham::pipeline::new_pipeline;
+    /// # use gotham::pipeline::set::{finalize_pipeline_set, new_pipeline_set};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # #[derive(Clone, Copy)]
+    /// # struct Authentication;
+    /// #
+    /// # impl NewMiddleware for Authentication {
+    /// #     type Instance = Self;
+    /// #     fn new_middleware(&self) -> std::io::Result<Self> { Ok(*self) }
+    /// # }
+    /// #
+    /// # impl Middleware for Authentication {
+    /// #     fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    /// #     where
+    /// #         Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    /// #     {
+    /// #         chain(state)
+    /// #     }
+    /// # }
+    /// #
+    /// # #[derive(Clone, Copy)]
+    /// # struct Cors;
+    /// #
+    /// # impl NewMiddleware for Cors {
+    /// #     type Instance = Self;
+    /// #     fn new_middleware(&self) -> std::io::Result<Self> { Ok(*self) }
+    /// # }
+    /// #
+    /// # impl Middleware for Cors {
+    /// #     fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    /// #     where
+    /// #         Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    /// #     {
+    /// #         chain(state)
+    /// #             .map_ok(|(state, mut res)| {
+    /// #                 res.headers_mut()
+    /// #                     .insert(ACCESS_CONTROL_ALLOW_ORIGIN, "*".parse().unwrap());
+    /// #                 (state, res)
+    /// #             })
+    /// #             .boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// fn router() -> Router {
+    ///     let pipelines = new_pipeline_set();
+    ///     let (pipelines, default) = pipelines.add(new_pipeline().add(Authentication).build());
+    ///     let (pipelines, cors) = pipelines.add(new_pipeline().add(Cors).build());
+    ///     let pipelines = finalize_pipeline_set(pipelines);
+    ///
+    ///     build_router((default, ()), pipelines, |route| {
+    ///         route.options_pipeline_chain((cors, ()));
+    ///         route.post("/orders").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .options("https://example.com/orders")
+    /// #       .with_header(ORIGIN, "https://shop.example.com".parse().unwrap())
+    /// #       .with_header(ACCESS_CONTROL_REQUEST_METHOD, "POST".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    /// #   assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    /// # }
+    /// ```
+    pub fn options_pipeline_chain<NC>(&mut self, pipeline_chain: NC)
+    where
+        NC: PipelineHandleChain<P> + Send + Sync + 'static,
+        P: RefUnwindSafe,
+    {
+        fn preflight(state: State) -> (State, Response<Body>) {
+            let res = create_empty_response(&state, StatusCode::NO_CONTENT);
+            (state, res)
+        }
+
+        let dispatcher =
+            DispatcherImpl::new(|| Ok(preflight), pipeline_chain, self.pipelines.clone());
+        self.settings.options_dispatcher = Some(Box::new(dispatcher));
+    }
+
+    /// Buffers response bodies of unknown length (e.g. those produced by a stream) up to
+    /// `threshold` bytes, so that small responses are sent with a `Content-Length` header. Once a
+    /// body exceeds the threshold, it is sent with chunked transfer encoding instead.
+    ///
+    /// Responses which already have a known length are not affected.
+    ///
+    /// ```rust
+    /// # extern crate futures;
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use futures::stream;
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use hyper::header::{CONTENT_LENGTH, TRANSFER_ENCODING};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn stream_handler(state: State) -> (State, Response<Body>) {
+    ///     let chunks = vec!["Hello, ", "world!"].into_iter().map(Ok::<_, hyper::Error>);
+    ///     (state, Response::new(Body::wrap_stream(stream::iter(chunks))))
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.buffer_responses(64 * 1024);
+    ///         route.get("/").to(stream_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.headers()[CONTENT_LENGTH], "13");
+    /// #   assert!(response.headers().get(TRANSFER_ENCODING).is_none());
+    /// # }
+    /// ```
+    pub fn buffer_responses(&mut self, threshold: usize) {
+        self.response_finalizer_builder.buffer_responses(threshold)
+    }
+
+    /// Generates a random nonce for every request, stored in `State` as `CspNonce`, and adds a
+    /// `Content-Security-Policy` header holding the nonce to `text/html` responses. Each occurrence
+    /// of `{nonce}` in the `policy` is replaced with the nonce of the request.
+    ///
+    /// Responses which already have a `Content-Security-Policy` header are not affected.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use hyper::header::CONTENT_SECURITY_POLICY;
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::state::{CspNonce, FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn my_handler(state: State) -> (State, Response<Body>) {
+    ///     let nonce = CspNonce::borrow_from(&state).value().to_owned();
+    ///     let body = format!("<script nonce=\"{}\">alert('Hello!');</script>", nonce);
+    ///
+    ///     let response = create_response(&state, StatusCode::OK, mime::TEXT_HTML, body);
+    ///     (state, response)
+    /// }
+    ///
+    /// fn router() -> Router {
+    ///     build_simple_router(|route| {
+    ///         route.add_csp_nonce("script-src 'nonce-{nonce}'; object-src 'none'");
+    ///         route.get("/").to(my_handler);
+    ///     })
+    /// }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #
+    /// #   let policy = response.headers()[CONTENT_SECURITY_POLICY].to_str().unwrap().to_owned();
+    /// #   let body = response.read_utf8_body().unwrap();
+    /// #   let nonce = body.split('"').nth(1).unwrap();
+    /// #   assert_eq!(policy, format!("script-src 'nonce-{}'; object-src 'none'", nonce));
+    /// # }
+    /// ```
+    pub fn add_csp_nonce(&mut self, policy: &str) {
+        self.response_finalizer_builder.add_csp_nonce(policy)
+    }
 }
 
 /// A scoped builder, which is created by `DrawRoutes::scope` and passed to the provided closure.
@@ -234,6 +685,7 @@ where
     node_builder: &'a mut Node,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    parameter_names: Vec<String>,
 }
 
 /// A delegated builder, which is created by `DrawRoutes::delegate` and returned. The `DrawRoutes`
@@ -267,6 +719,27 @@ where
 
         self.node_builder.add_route(Box::new(route));
     }
+
+    /// Directs the delegated route to the given `NewHandler`, which routes on the remainder of the
+    /// request path.
+    fn to_mounted<NH>(self, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+    {
+        let dispatcher = DispatcherImpl::new(
+            mount::Mount::new(new_handler),
+            self.pipeline_chain,
+            self.pipelines,
+        );
+        let route: DelegatedRoute = DelegatedRoute::new(
+            AnyRouteMatcher::new(),
+            Box::new(dispatcher),
+            Extractors::new(),
+            Delegation::External,
+        );
+
+        self.node_builder.add_route(Box::new(route));
+    }
 }
 
 /// Implements the traits required to define a single route, after determining which request paths
@@ -283,6 +756,10 @@ where
     matcher: M,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    description: Option<String>,
+    produces: Vec<(StatusCode, Mime)>,
+    requires_body: bool,
+    json_schema: Option<Arc<JsonSchema>>,
     phantom: PhantomData<(PE, QSE)>,
 }
 
@@ -307,6 +784,10 @@ where
             matcher: self.matcher,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines,
+            description: self.description,
+            produces: self.produces,
+            requires_body: self.requires_body,
+            json_schema: self.json_schema,
             phantom: PhantomData,
         }
     }
@@ -316,16 +797,21 @@ where
 mod tests {
     use super::*;
 
+    use std::pin::Pin;
+
+    use futures::prelude::*;
     use hyper::service::Service;
-    use hyper::{body, Body, Request, Response, StatusCode};
+    use hyper::{body, Body, Method, Request, Response, StatusCode};
     use serde_derive::Deserialize;
 
+    use crate::handler::HandlerFuture;
+
     use crate::middleware::cookie::CookieParser;
     use crate::middleware::session::NewSessionMiddleware;
     use crate::pipeline::new_pipeline;
     use crate::router::response::extender::StaticResponseExtender;
     use crate::service::GothamService;
-    use crate::state::{State, StateData};
+    use crate::state::{FromState, State, StateData};
 
     #[derive(Deserialize)]
     struct SalutationParams {
@@ -626,4 +1112,453 @@ mod tests {
         let response = call(Request::get("/trailing-slash").body(Body::empty()).unwrap());
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[test]
+    fn requires_body_rejects_empty_bodies() {
+        fn echo(mut state: State) -> Pin<Box<HandlerFuture>> {
+            let body = Body::take_from(&mut state);
+            async move {
+                let bytes = body::to_bytes(body).await.unwrap();
+                let res = Response::new(Body::from(bytes));
+                Ok((state, res))
+            }
+            .boxed()
+        }
+
+        let router = build_simple_router(|route| {
+            route
+                .request(vec![Method::GET, Method::POST], "/")
+                .requires_body()
+                .to(echo);
+        });
+
+        let new_service = GothamService::new(router);
+
+        let call = move |req| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            futures::executor::block_on(service.call(req)).unwrap()
+        };
+
+        let response = call(Request::post("/").body(Body::from("data")).unwrap());
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let chunked = futures::stream::iter(vec![
+            Ok::<_, std::io::Error>(""),
+            Ok("chunked "),
+            Ok("data"),
+        ]);
+        let response = call(Request::post("/").body(Body::wrap_stream(chunked)).unwrap());
+        assert_eq!(response.status(), StatusCode::OK);
+        let response_bytes =
+            futures::executor::block_on(body::to_bytes(response.into_body())).unwrap();
+        assert_eq!(&response_bytes[..], b"chunked data");
+
+        let response = call(Request::post("/").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let empty_chunked = futures::stream::iter(vec![Ok::<_, std::io::Error>("")]);
+        let response = call(
+            Request::post("/")
+                .body(Body::wrap_stream(empty_chunked))
+                .unwrap(),
+        );
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call(Request::get("/").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn to_constant_responds_with_fixed_content() {
+        use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+
+        let router = build_simple_router(|route| {
+            route.get_or_head("/robots.txt").to_constant(
+                StatusCode::OK,
+                mime::TEXT_PLAIN,
+                "User-agent: *",
+            );
+            route.post("/stub").to_constant(
+                StatusCode::CREATED,
+                mime::APPLICATION_JSON,
+                &b"{}"[..],
+            );
+        });
+
+        let new_service = GothamService::new(router);
+
+        let call = move |req| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            futures::executor::block_on(service.call(req)).unwrap()
+        };
+
+        for _ in 0..2 {
+            let response = call(Request::get("/robots.txt").body(Body::empty()).unwrap());
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+            assert_eq!(response.headers()[CONTENT_LENGTH], "13");
+            let response_bytes =
+                futures::executor::block_on(body::to_bytes(response.into_body())).unwrap();
+            assert_eq!(&response_bytes[..], b"User-agent: *");
+        }
+
+        let response = call(Request::head("/robots.txt").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_LENGTH], "13");
+
+        let response = call(Request::post("/stub").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response.headers()[CONTENT_LENGTH], "2");
+    }
+
+    #[test]
+    fn normalize_headers_before_matching() {
+        use crate::router::route::matcher::HeaderRouteMatcher;
+        use hyper::header::HeaderName;
+
+        let build = |normalize: bool| {
+            build_simple_router(|route| {
+                if normalize {
+                    route.normalize_headers();
+                }
+
+                let matcher = HeaderRouteMatcher::new(
+                    HeaderName::from_static("x-api-version"),
+                    "2".parse().unwrap(),
+                );
+                route.get("/").add_route_matcher(matcher).to(api::submit);
+            })
+        };
+
+        let call = |router: Router| {
+            let new_service = GothamService::new(router);
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            let req = Request::get("/")
+                .header(HeaderName::from_bytes(b"X-API-Version").unwrap(), "  2 ")
+                .body(Body::empty())
+                .unwrap();
+            futures::executor::block_on(service.call(req)).unwrap()
+        };
+
+        assert_eq!(call(build(false)).status(), StatusCode::BAD_REQUEST);
+        assert_eq!(call(build(true)).status(), StatusCode::ACCEPTED);
+    }
+
+    #[test]
+    fn min_http_version_rejects_older_requests() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+
+        use crate::server::ServerConfig;
+
+        let router = build_simple_router(|route| {
+            route.min_http_version(Version::HTTP_11);
+            route.get("/").to(api::submit);
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.spawn(ServerConfig::default().init_server_with_listener(listener, router));
+
+        let send = |request: &[u8]| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request).unwrap();
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response.split_whitespace().nth(1).map(ToOwned::to_owned)
+        };
+
+        let status = send(b"GET / HTTP/1.0\r\nHost: localhost\r\n\r\n");
+        assert_eq!(status.as_ref().map(String::as_str), Some("505"));
+
+        let status = send(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert_eq!(status.as_ref().map(String::as_str), Some("202"));
+    }
+
+    #[test]
+    fn max_query_params_rejects_flooded_requests() {
+        let router = build_simple_router(|route| {
+            route.max_query_params(3);
+            route.get("/").to(api::submit);
+        });
+
+        let new_service = GothamService::new(router);
+
+        let call = move |uri: &str| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            let req = Request::get(uri).body(Body::empty()).unwrap();
+            futures::executor::block_on(service.call(req)).unwrap()
+        };
+
+        let response = call("/?a=1&b=2&c=3&d=4");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call("/?a=1&a=2&a=3&a=4");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call("/?a=1&b=2&c=3");
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let response = call("/");
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[test]
+    fn handler_errors_are_rendered_as_configured() {
+        use crate::handler::IntoHandlerError;
+        use crate::helpers::http::response::create_response;
+        use crate::router::response::json_error::{JsonErrorExtender, SingleErrorFormatter};
+
+        fn failing(state: State) -> Pin<Box<HandlerFuture>> {
+            let err = std::io::Error::new(std::io::ErrorKind::Other, "invalid order")
+                .into_handler_error()
+                .with_status(StatusCode::UNPROCESSABLE_ENTITY);
+            future::err((state, err)).boxed()
+        }
+
+        let call = |router: Router| {
+            let new_service = GothamService::new(router);
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            let req = Request::post("/orders").body(Body::empty()).unwrap();
+            let response = futures::executor::block_on(service.call(req)).unwrap();
+            let status = response.status();
+            let body = futures::executor::block_on(body::to_bytes(response.into_body())).unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        };
+
+        // The status of the `HandlerError` is rendered by the error envelope for that status.
+        let router = build_simple_router(|route| {
+            route.add_response_extender(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                JsonErrorExtender::new(SingleErrorFormatter),
+            );
+            route.post("/orders").to(failing);
+        });
+
+        let (status, body) = call(router);
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body, r#"{"error":"Unprocessable Entity"}"#);
+
+        // A global override replaces the default rendering.
+        let router = build_simple_router(|route| {
+            route.handler_error_response(|state, err| {
+                create_response(
+                    state,
+                    StatusCode::BAD_REQUEST,
+                    mime::TEXT_PLAIN,
+                    format!("rejected ({})", err.status().as_u16()),
+                )
+            });
+            route.post("/orders").to(failing);
+        });
+
+        let (status, body) = call(router);
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, "rejected (422)");
+    }
+
+    #[test]
+    fn options_pipeline_chain_skips_default_pipeline() {
+        use std::io;
+
+        use hyper::header::{HeaderMap, ACCESS_CONTROL_ALLOW_ORIGIN, AUTHORIZATION};
+
+        use crate::middleware::{Middleware, NewMiddleware};
+
+        #[derive(Clone, Copy)]
+        struct RequireAuthorization;
+
+        impl NewMiddleware for RequireAuthorization {
+            type Instance = Self;
+
+            fn new_middleware(&self) -> io::Result<Self> {
+                Ok(*self)
+            }
+        }
+
+        impl Middleware for RequireAuthorization {
+            fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+            where
+                Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+            {
+                if HeaderMap::borrow_from(&state).contains_key(AUTHORIZATION) {
+                    chain(state)
+                } else {
+                    let res = create_empty_response(&state, StatusCode::UNAUTHORIZED);
+                    future::ok((state, res)).boxed()
+                }
+            }
+        }
+
+        #[derive(Clone, Copy)]
+        struct Cors;
+
+        impl NewMiddleware for Cors {
+            type Instance = Self;
+
+            fn new_middleware(&self) -> io::Result<Self> {
+                Ok(*self)
+            }
+        }
+
+        impl Middleware for Cors {
+            fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+            where
+                Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+            {
+                chain(state)
+                    .map_ok(|(state, mut res)| {
+                        res.headers_mut()
+                            .insert(ACCESS_CONTROL_ALLOW_ORIGIN, "*".parse().unwrap());
+                        (state, res)
+                    })
+                    .boxed()
+            }
+        }
+
+        let pipelines = new_pipeline_set();
+        let (pipelines, default) = pipelines.add(new_pipeline().add(RequireAuthorization).build());
+        let (pipelines, cors) = pipelines.add(new_pipeline().add(Cors).build());
+        let pipelines = finalize_pipeline_set(pipelines);
+
+        let router = build_router((default, ()), pipelines, |route| {
+            route.options_pipeline_chain((cors, ()));
+            route.post("/orders").to(api::submit);
+        });
+
+        let new_service = GothamService::new(router);
+
+        let call = move |req: Request<Body>| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            futures::executor::block_on(service.call(req)).unwrap()
+        };
+
+        let response = call(Request::post("/orders").body(Body::empty()).unwrap());
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = call(
+            Request::options("/orders")
+                .header("origin", "https://shop.example.com")
+                .header("access-control-request-method", "POST")
+                .body(Body::empty())
+                .unwrap(),
+        );
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+
+    #[test]
+    fn options_pipeline_chain_only_answers_unrouted_preflights() {
+        use crate::helpers::http::response::create_response;
+
+        fn describe(state: State) -> (State, Response<Body>) {
+            let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "GET, PUT");
+            (state, res)
+        }
+
+        let pipelines = new_pipeline_set();
+        let (pipelines, default) = pipelines.add(new_pipeline().build());
+        let (pipelines, cors) = pipelines.add(new_pipeline().build());
+        let pipelines = finalize_pipeline_set(pipelines);
+
+        let router = build_router((default, ()), pipelines, |route| {
+            route.options_pipeline_chain((cors, ()));
+            route.post("/orders").to(api::submit);
+            route.options("/reports").to(describe);
+            route.get("/reports").to(api::submit);
+        });
+
+        let new_service = GothamService::new(router);
+
+        let call = move |path: &str, preflight: bool| {
+            let mut req = Request::options(path);
+            if preflight {
+                req = req
+                    .header("origin", "https://shop.example.com")
+                    .header("access-control-request-method", "GET");
+            }
+
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            let response =
+                futures::executor::block_on(service.call(req.body(Body::empty()).unwrap()))
+                    .unwrap();
+            response.status()
+        };
+
+        assert_eq!(call("/orders", true), StatusCode::NO_CONTENT);
+        assert_eq!(call("/orders", false), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(call("/reports", true), StatusCode::OK);
+        assert_eq!(call("/reports", false), StatusCode::OK);
+        assert_eq!(call("/missing", true), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn validate_json_rejects_nonconforming_bodies() {
+        use serde_json::{json, Value};
+
+        use crate::helpers::http::request::body::BodyReadConfig;
+        use crate::helpers::http::request::json_schema::ValidatedJson;
+
+        fn create_user(state: State) -> (State, Response<Body>) {
+            let name = ValidatedJson::borrow_from(&state).value()["name"].clone();
+            let res = Response::new(Body::from(name.as_str().unwrap().to_owned()));
+            (state, res)
+        }
+
+        let schema = JsonSchema::new(json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "age": { "type": "integer", "minimum": 0 }
+            }
+        }))
+        .unwrap()
+        .with_body_config(BodyReadConfig::default().with_max_size(1024));
+
+        let router = build_simple_router(|route| {
+            route.post("/users").validate_json(schema).to(create_user);
+        });
+
+        let new_service = GothamService::new(router);
+
+        let call = move |body: String| {
+            let mut service = new_service.connect("127.0.0.1:10000".parse().unwrap());
+            let req = Request::post("/users").body(Body::from(body)).unwrap();
+            let response = futures::executor::block_on(service.call(req)).unwrap();
+            let status = response.status();
+            let bytes = futures::executor::block_on(body::to_bytes(response.into_body())).unwrap();
+            (status, bytes)
+        };
+
+        let (status, bytes) = call(r#"{"name": "Alice", "age": 30}"#.to_owned());
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(&bytes[..], b"Alice");
+
+        let (status, bytes) = call(r#"{"age": -1}"#.to_owned());
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let errors: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            errors,
+            json!({
+                "errors": [
+                    { "path": "", "message": "missing required property name" },
+                    { "path": "/age", "message": "must be at least 0" }
+                ]
+            })
+        );
+
+        let (status, bytes) = call("not json".to_owned());
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let errors: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(errors["errors"][0]["path"], "");
+
+        let name = "a".repeat(2048);
+        let (status, _) = call(json!({ "name": name }).to_string());
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
 }
@@ -106,6 +106,10 @@ where
             node_builder: self.node_builder,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines,
+            description: self.description,
+            produces: self.produces,
+            requires_body: self.requires_body,
+            json_schema: self.json_schema,
         }
     }
 }
@@ -0,0 +1,80 @@
+//! Defines the `NewHandler` wrapper used by `DrawRoutes::mount`.
+
+use std::pin::Pin;
+
+use hyper::Uri;
+use log::trace;
+
+use crate::error::Result;
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::helpers::http::request::path::{split_path_segments, RequestPathSegments};
+use crate::state::{request_id, FromState, State};
+
+/// Wraps a `NewHandler` which does its own routing, so that the `Uri` seen by its handlers holds
+/// only the part of the request path below the mount point.
+pub(super) struct Mount<NH> {
+    new_handler: NH,
+}
+
+impl<NH> Mount<NH> {
+    pub(super) fn new(new_handler: NH) -> Mount<NH> {
+        Mount { new_handler }
+    }
+}
+
+impl<NH> NewHandler for Mount<NH>
+where
+    NH: NewHandler,
+{
+    type Instance = Mounted<NH::Instance>;
+
+    fn new_handler(&self) -> Result<Self::Instance> {
+        self.new_handler
+            .new_handler()
+            .map(|handler| Mounted { handler })
+    }
+}
+
+pub(super) struct Mounted<H> {
+    handler: H,
+}
+
+impl<H> Handler for Mounted<H>
+where
+    H: Handler,
+{
+    fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
+        let remaining =
+            RequestPathSegments::try_borrow_from(&state).map_or(0, |rps| rps.segments().len());
+
+        if let Some(uri) = remaining_uri(Uri::borrow_from(&state), remaining) {
+            trace!(
+                "[{}] dispatching to mounted handler with path {}",
+                request_id(&state),
+                uri.path()
+            );
+            state.put(uri);
+        }
+
+        self.handler.handle(state)
+    }
+}
+
+/// Rebuilds the `Uri` with only the last `remaining` segments of its path, retaining the query
+/// string.
+fn remaining_uri(uri: &Uri, remaining: usize) -> Option<Uri> {
+    let segments: Vec<&str> = split_path_segments(uri.path()).collect();
+    let path = format!(
+        "/{}",
+        segments[segments.len().saturating_sub(remaining)..].join("/")
+    );
+
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
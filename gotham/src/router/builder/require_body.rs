@@ -0,0 +1,80 @@
+//! Defines the `Handler` wrapper used by `DefineSingleRoute::requires_body`.
+
+use std::pin::Pin;
+
+use futures::prelude::*;
+use futures::stream;
+use hyper::body::HttpBody;
+use hyper::{Body, Method, StatusCode};
+use log::trace;
+
+use crate::handler::{Handler, HandlerError, HandlerFuture};
+use crate::helpers::http::response::create_empty_response;
+use crate::state::{request_id, FromState, State};
+
+/// Wraps a `Handler`, responding with `400 Bad Request` instead of invoking it when a `POST`,
+/// `PUT` or `PATCH` request arrives without a body.
+pub(super) struct RequireBody<H> {
+    handler: H,
+}
+
+impl<H> RequireBody<H> {
+    pub(super) fn new(handler: H) -> RequireBody<H> {
+        RequireBody { handler }
+    }
+}
+
+impl<H> Handler for RequireBody<H>
+where
+    H: Handler + 'static,
+{
+    fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
+        match *Method::borrow_from(&state) {
+            Method::POST | Method::PUT | Method::PATCH => (),
+            _ => return self.handler.handle(state),
+        }
+
+        let body = state.try_take::<Body>().unwrap_or_else(Body::empty);
+
+        async move {
+            match non_empty_body(body).await {
+                Ok(Some(body)) => {
+                    state.put(body);
+                    self.handler.handle(state).await
+                }
+                Ok(None) => {
+                    trace!("[{}] request body is required", request_id(&state));
+                    let res = create_empty_response(&state, StatusCode::BAD_REQUEST);
+                    Ok((state, res))
+                }
+                Err(e) => Err((state, HandlerError::from(e))),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Determines whether the `Body` is empty, returning `None` if so.
+///
+/// Where the length of the body is not known in advance (e.g. a chunked body), the first chunk is
+/// read and the returned `Body` yields it again before the remainder of the stream.
+async fn non_empty_body(mut body: Body) -> Result<Option<Body>, hyper::Error> {
+    if body.is_end_stream() {
+        return Ok(None);
+    }
+
+    if body.size_hint().exact().is_some() {
+        return Ok(Some(body));
+    }
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+
+        if !chunk.is_empty() {
+            let rest = stream::once(future::ok(chunk)).chain(body);
+            return Ok(Some(Body::wrap_stream(rest)));
+        }
+    }
+
+    Ok(None)
+}
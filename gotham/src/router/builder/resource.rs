@@ -0,0 +1,58 @@
+//! Defines the types used by `DrawRoutes::resource` to register a REST collection.
+
+use hyper::{Body, Response, StatusCode};
+use serde_derive::Deserialize;
+
+use crate::router::response::extender::StaticResponseExtender;
+use crate::state::{State, StateData};
+
+/// The handlers for the standard CRUD routes of a REST collection, registered in a single call to
+/// `DrawRoutes::resource`.
+///
+/// For a resource named `users`, the handlers are mapped to the following routes:
+///
+/// | Handler  | Method(s)      | Path         |
+/// |----------|----------------|--------------|
+/// | `index`  | `GET`, `HEAD`  | `/users`     |
+/// | `create` | `POST`         | `/users`     |
+/// | `show`   | `GET`, `HEAD`  | `/users/:id` |
+/// | `update` | `PUT`, `PATCH` | `/users/:id` |
+/// | `delete` | `DELETE`       | `/users/:id` |
+///
+/// As with all routes, empty path segments are ignored when matching so `/users/` and `/users/1/`
+/// are dispatched in the same way as `/users` and `/users/1`. The `show`, `update` and `delete`
+/// handlers can access the member identifier via the `ResourceId` stored in `State`.
+pub struct ResourceHandlers<I, C, S, U, D> {
+    /// Lists the members of the collection.
+    pub index: I,
+
+    /// Adds a new member to the collection.
+    pub create: C,
+
+    /// Retrieves a single member of the collection.
+    pub show: S,
+
+    /// Replaces or modifies a single member of the collection.
+    pub update: U,
+
+    /// Removes a single member from the collection.
+    pub delete: D,
+}
+
+/// The path extractor used for the member routes registered by `DrawRoutes::resource`, holding
+/// the value of the `:id` segment.
+#[derive(Deserialize)]
+pub struct ResourceId {
+    /// The identifier of the requested member, as it appeared in the request path.
+    pub id: String,
+}
+
+impl StateData for ResourceId {}
+
+impl StaticResponseExtender for ResourceId {
+    type ResBody = Body;
+
+    fn extend(_state: &mut State, res: &mut Response<Body>) {
+        *res.status_mut() = StatusCode::BAD_REQUEST;
+    }
+}
@@ -1,15 +1,23 @@
-use hyper::Body;
+use bytes::Bytes;
+use hyper::{Body, StatusCode};
+use mime::Mime;
 
 use std::panic::RefUnwindSafe;
+use std::sync::Arc;
 
 use crate::extractor::{PathExtractor, QueryStringExtractor};
 use crate::handler::assets::{DirHandler, FileHandler, FileOptions, FilePathExtractor};
 use crate::handler::{Handler, NewHandler};
+use crate::helpers::http::request::json_schema::JsonSchema;
 use crate::pipeline::chain::PipelineHandleChain;
+use crate::pipeline::set::PipelineSet;
+use crate::router::builder::constant::ConstantHandler;
+use crate::router::builder::require_body::RequireBody;
+use crate::router::builder::validate_json::ValidateJson;
 use crate::router::builder::{
     ExtendRouteMatcher, ReplacePathExtractor, ReplaceQueryStringExtractor, SingleRouteBuilder,
 };
-use crate::router::route::dispatch::DispatcherImpl;
+use crate::router::route::dispatch::{Dispatcher, DispatcherImpl};
 use crate::router::route::matcher::RouteMatcher;
 use crate::router::route::{Delegation, Extractors, RouteImpl};
 
@@ -264,6 +272,50 @@ pub trait DefineSingleRoute {
         self.to_new_handler(FileHandler::new(options));
     }
 
+    /// Directs the route to respond with the same status, content type and body to every request.
+    /// The `Content-Length` header is set from the length of the body. This is useful for stub
+    /// endpoints and small pieces of static content.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::StatusCode;
+    /// # use hyper::header::CONTENT_TYPE;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/robots.txt")
+    ///         .to_constant(StatusCode::OK, mime::TEXT_PLAIN, "User-agent: *\nDisallow:\n");
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/robots.txt")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "User-agent: *\nDisallow:\n");
+    /// # }
+    /// ```
+    fn to_constant<B>(self, status: StatusCode, mime: Mime, body: B)
+    where
+        Self: Sized,
+        B: Into<Bytes>,
+    {
+        self.to_new_handler(ConstantHandler::new(status, mime, body.into()));
+    }
+
     /// Applies a `PathExtractor` type to the current route, to extract path parameters into
     /// `State` with the given type.
     ///
@@ -454,6 +506,167 @@ pub trait DefineSingleRoute {
         NRM: RouteMatcher + Send + Sync + 'static,
         Self: ExtendRouteMatcher<NRM>,
         Self::Output: DefineSingleRoute;
+
+    /// Attaches a human-readable description to the current route. The description has no effect
+    /// on how requests are dispatched, but is available via `Router::routes` for introspection.
+    ///
+    /// ```
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::builder::*;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.get("/users")
+    ///          .describe("List all users")
+    ///          .to(my_handler);
+    /// });
+    /// #
+    /// # assert_eq!(router.routes()[0].description(), Some("List all users"));
+    /// # }
+    /// ```
+    fn describe<S>(self, description: S) -> Self
+    where
+        S: Into<String>;
+
+    /// Annotates the current route with the status codes and content types of the responses it
+    /// produces. Like `describe`, the annotation has no effect on how requests are dispatched, but
+    /// is available via `Router::routes` for introspection and documentation.
+    ///
+    /// ```
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::builder::*;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.get("/users/:id")
+    ///          .produces(&[(200, mime::APPLICATION_JSON), (404, mime::TEXT_PLAIN)])
+    ///          .to(my_handler);
+    /// });
+    /// #
+    /// # assert_eq!(
+    /// #     router.routes()[0].produces(),
+    /// #     &[
+    /// #         (StatusCode::OK, mime::APPLICATION_JSON),
+    /// #         (StatusCode::NOT_FOUND, mime::TEXT_PLAIN),
+    /// #     ][..]
+    /// # );
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If any of the status codes is not between 100 and 999.
+    fn produces(self, responses: &[(u16, Mime)]) -> Self;
+
+    /// Requires that `POST`, `PUT` and `PATCH` requests to the current route have a non-empty
+    /// body. Requests with a zero `Content-Length`, or an empty chunked body, receive a
+    /// `400 Bad Request` response without the handler being invoked. Requests using any other
+    /// method are unaffected.
+    ///
+    /// ```
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.post("/users")
+    ///          .requires_body()
+    ///          .to(my_handler);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/users", "", mime::APPLICATION_JSON)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    /// # }
+    /// ```
+    fn requires_body(self) -> Self;
+
+    /// Validates the JSON request body against `schema` before the handler is invoked. Bodies
+    /// which are not valid JSON, or do not conform to the schema, receive a
+    /// `422 Unprocessable Entity` response holding a JSON list of the errors, and the handler is
+    /// not invoked. Otherwise, the parsed body is stored in `State` as `ValidatedJson`.
+    ///
+    /// ```
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// # extern crate mime;
+    /// # #[macro_use]
+    /// # extern crate serde_json;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::request::json_schema::{JsonSchema, ValidatedJson};
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn create_user(state: State) -> (State, Response<Body>) {
+    ///     let name = ValidatedJson::borrow_from(&state).value()["name"].to_string();
+    ///     let res = create_response(&state, StatusCode::CREATED, mime::APPLICATION_JSON, name);
+    ///     (state, res)
+    /// }
+    ///
+    /// # fn router() -> Router {
+    /// let schema = JsonSchema::new(json!({
+    ///     "type": "object",
+    ///     "required": ["name"],
+    ///     "properties": { "name": { "type": "string" } }
+    /// }))
+    /// .unwrap();
+    ///
+    /// build_simple_router(|route| {
+    ///     route.post("/users")
+    ///          .validate_json(schema)
+    ///          .to(create_user);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .post("https://example.com/users", r#"{"name": 1}"#, mime::APPLICATION_JSON)
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    /// # }
+    /// ```
+    fn validate_json(self, schema: JsonSchema) -> Self;
 }
 
 impl<'a, M, C, P, PE, QSE> DefineSingleRoute for SingleRouteBuilder<'a, M, C, P, PE, QSE>
@@ -475,13 +688,40 @@ where
     where
         NH: NewHandler + 'static,
     {
-        let dispatcher = DispatcherImpl::new(new_handler, self.pipeline_chain, self.pipelines);
+        let (chain, pipelines) = (self.pipeline_chain, self.pipelines);
+
+        let dispatcher = match (self.requires_body, self.json_schema) {
+            (false, None) => dispatcher(new_handler, chain, pipelines),
+            (true, None) => {
+                let new_handler = move || new_handler.new_handler().map(RequireBody::new);
+                dispatcher(new_handler, chain, pipelines)
+            }
+            (false, Some(schema)) => {
+                let new_handler = move || {
+                    new_handler
+                        .new_handler()
+                        .map(|handler| ValidateJson::new(handler, schema.clone()))
+                };
+                dispatcher(new_handler, chain, pipelines)
+            }
+            (true, Some(schema)) => {
+                let new_handler = move || {
+                    new_handler
+                        .new_handler()
+                        .map(|handler| RequireBody::new(ValidateJson::new(handler, schema.clone())))
+                };
+                dispatcher(new_handler, chain, pipelines)
+            }
+        };
+
         let route: RouteImpl<M, PE, QSE> = RouteImpl::new(
             self.matcher,
-            Box::new(dispatcher),
+            dispatcher,
             Extractors::new(),
             Delegation::Internal,
-        );
+        )
+        .with_description(self.description)
+        .with_produces(self.produces);
         self.node_builder.add_route(Box::new(route));
     }
 
@@ -507,4 +747,53 @@ where
     {
         self.extend_route_matcher(matcher)
     }
+
+    fn describe<S>(self, description: S) -> Self
+    where
+        S: Into<String>,
+    {
+        SingleRouteBuilder {
+            description: Some(description.into()),
+            ..self
+        }
+    }
+
+    fn produces(self, responses: &[(u16, Mime)]) -> Self {
+        let mut produces = self.produces;
+        produces.extend(responses.iter().map(|(status, mime)| {
+            let status = StatusCode::from_u16(*status)
+                .unwrap_or_else(|_| panic!("invalid status code in route annotation: {}", status));
+            (status, mime.clone())
+        }));
+
+        SingleRouteBuilder { produces, ..self }
+    }
+
+    fn requires_body(self) -> Self {
+        SingleRouteBuilder {
+            requires_body: true,
+            ..self
+        }
+    }
+
+    fn validate_json(self, schema: JsonSchema) -> Self {
+        SingleRouteBuilder {
+            json_schema: Some(Arc::new(schema)),
+            ..self
+        }
+    }
+}
+
+/// Boxes a `DispatcherImpl` for the route, so that the handler may be wrapped as configured.
+fn dispatcher<NH, C, P>(
+    new_handler: NH,
+    pipeline_chain: C,
+    pipelines: PipelineSet<P>,
+) -> Box<dyn Dispatcher + Send + Sync>
+where
+    NH: NewHandler + 'static,
+    C: PipelineHandleChain<P> + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+{
+    Box::new(DispatcherImpl::new(new_handler, pipeline_chain, pipelines))
 }
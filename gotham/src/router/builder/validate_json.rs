@@ -0,0 +1,79 @@
+//! Defines the `Handler` wrapper used by `DefineSingleRoute::validate_json`.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::prelude::*;
+use hyper::{Body, StatusCode};
+use log::trace;
+use serde_json::{json, Value};
+
+use crate::handler::{Handler, HandlerError, HandlerFuture};
+use crate::helpers::http::request::body::read_body;
+use crate::helpers::http::request::json_schema::{JsonSchema, ValidatedJson, ValidationError};
+use crate::helpers::http::response::create_response;
+use crate::state::{request_id, State};
+
+/// Wraps a `Handler`, validating the JSON request body against a `JsonSchema` before invoking it.
+/// Bodies which are not valid JSON, or do not conform to the schema, receive a
+/// `422 Unprocessable Entity` response listing the errors. The body is read according to the
+/// schema's `BodyReadConfig`, so oversized bodies are rejected before they are parsed.
+pub(super) struct ValidateJson<H> {
+    handler: H,
+    schema: Arc<JsonSchema>,
+}
+
+impl<H> ValidateJson<H> {
+    pub(super) fn new(handler: H, schema: Arc<JsonSchema>) -> ValidateJson<H> {
+        ValidateJson { handler, schema }
+    }
+}
+
+impl<H> Handler for ValidateJson<H>
+where
+    H: Handler + 'static,
+{
+    fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
+        let body = state.try_take::<Body>().unwrap_or_else(Body::empty);
+
+        async move {
+            let read = read_body(body, self.schema.body_config()).await;
+            let bytes = match read {
+                Ok(buffered) => match buffered.into_bytes().await {
+                    Ok(bytes) => bytes,
+                    Err(e) => return Err((state, HandlerError::from(e))),
+                },
+                Err(e) => return Err((state, HandlerError::from(e))),
+            };
+
+            let result = serde_json::from_slice::<Value>(&bytes)
+                .map_err(|e| vec![ValidationError::new("", format!("invalid JSON: {}", e))])
+                .and_then(|value| self.schema.validate(&value).map(|()| value));
+
+            match result {
+                Ok(value) => {
+                    state.put(ValidatedJson::new(value));
+                    state.put(Body::from(bytes));
+                    self.handler.handle(state).await
+                }
+                Err(errors) => {
+                    trace!(
+                        "[{}] request body failed validation: {:?}",
+                        request_id(&state),
+                        errors
+                    );
+
+                    let body = json!({ "errors": errors }).to_string();
+                    let res = create_response(
+                        &state,
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        mime::APPLICATION_JSON,
+                        body,
+                    );
+                    Ok((state, res))
+                }
+            }
+        }
+        .boxed()
+    }
+}
@@ -5,40 +5,119 @@ pub mod non_match;
 pub mod response;
 pub mod route;
 pub mod tree;
+pub mod url;
 
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::panic::RefUnwindSafe;
 use std::pin::Pin;
 use std::sync::Arc;
 
 use futures::prelude::*;
 
-use hyper::header::ALLOW;
-use hyper::{Body, Response, StatusCode};
+use hyper::header::{HeaderMap, ACCESS_CONTROL_REQUEST_METHOD, ALLOW, ORIGIN};
+use hyper::{Body, Method, Response, StatusCode, Uri, Version};
 use log::{error, trace};
 
 use crate::error::*;
-use crate::handler::{Handler, HandlerFuture, IntoResponse, NewHandler};
+use crate::handler::{Handler, HandlerError, HandlerFuture, IntoResponse, NewHandler};
+use crate::helpers::http::header::normalize_headers;
 use crate::helpers::http::request::path::RequestPathSegments;
+use crate::helpers::http::request::query_string;
 use crate::helpers::http::response::create_empty_response;
 use crate::router::response::finalizer::ResponseFinalizer;
-use crate::router::route::{Delegation, Route};
+use crate::router::route::dispatch::Dispatcher;
+use crate::router::route::{
+    Delegation, ExtractorFailed, ExtractorResponse, Route, RouteExplanation, RouteInfo,
+};
 use crate::router::tree::segment::SegmentMapping;
 use crate::router::tree::Tree;
-use crate::state::{request_id, State};
+use crate::state::{request_id, set_request_id, State};
+
+/// Renders the response for a `HandlerError`, in place of its `IntoResponse` implementation.
+type HandlerErrorResponse =
+    Arc<dyn Fn(&State, HandlerError) -> Response<Body> + Send + Sync + RefUnwindSafe>;
+
+/// Router level settings, which are filled in by the `RouterBuilder` and applied to every request
+/// handled by the `Router`.
+#[derive(Default)]
+pub(in crate::router) struct RouterSettings {
+    pub(in crate::router) normalize_headers: bool,
+    pub(in crate::router) min_http_version: Option<Version>,
+    pub(in crate::router) max_query_params: Option<usize>,
+    pub(in crate::router) handler_error_response: Option<HandlerErrorResponse>,
+    pub(in crate::router) options_dispatcher: Option<Box<dyn Dispatcher + Send + Sync>>,
+}
+
+impl RouterSettings {
+    /// Determines whether every setting has its default value.
+    fn is_default(&self) -> bool {
+        !self.normalize_headers
+            && self.min_http_version.is_none()
+            && self.max_query_params.is_none()
+            && self.handler_error_response.is_none()
+            && self.options_dispatcher.is_none()
+    }
+}
 
 struct RouterData {
     tree: Tree,
     response_finalizer: ResponseFinalizer,
+    settings: RouterSettings,
 }
 
 impl RouterData {
-    fn new(tree: Tree, response_finalizer: ResponseFinalizer) -> RouterData {
+    fn new(
+        tree: Tree,
+        response_finalizer: ResponseFinalizer,
+        settings: RouterSettings,
+    ) -> RouterData {
         RouterData {
             tree,
             response_finalizer,
+            settings,
         }
     }
+
+    /// Determines whether any router level setting differs from its default.
+    fn has_settings(&self) -> bool {
+        !self.response_finalizer.is_default() || !self.settings.is_default()
+    }
 }
 
+/// Returned from `Router::merge` when two routers cannot be merged.
+#[derive(Debug)]
+pub enum MergeError {
+    /// A route in the merged `Router` conflicts with an existing route, having the same path and
+    /// accepting a common request method.
+    Conflict(RouteInfo),
+
+    /// One of the routers has been cloned, so its routes cannot be taken.
+    InUse,
+
+    /// The merged `Router` has router level settings (such as response extenders), which would be
+    /// discarded by the merge.
+    Settings,
+}
+
+impl Display for MergeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            MergeError::Conflict(ref route) => {
+                write!(
+                    f,
+                    "route conflicts with an existing route: {}",
+                    route.path()
+                )
+            }
+            MergeError::InUse => write!(f, "router is in use and cannot be merged"),
+            MergeError::Settings => write!(f, "merged router has router level settings"),
+        }
+    }
+}
+
+impl StdError for MergeError {}
+
 /// Responsible for dispatching HTTP requests to defined routes, and responding with appropriate
 /// error codes when a valid `Route` is unable to be determined or the dispatch cannot be
 /// performed.
@@ -72,11 +151,49 @@ impl Handler for Router {
     fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
         trace!("[{}] starting", request_id(&state));
 
+        self.data.response_finalizer.prepare(&mut state);
+
+        if let Some(min) = self.data.settings.min_http_version {
+            if state
+                .try_borrow::<Version>()
+                .map_or(false, |version| *version < min)
+            {
+                trace!("[{}] unsupported http version", request_id(&state));
+                let res = create_empty_response(&state, StatusCode::HTTP_VERSION_NOT_SUPPORTED);
+                return self.finalize_response(future::ok((state, res)).boxed());
+            }
+        }
+
+        if let Some(max) = self.data.settings.max_query_params {
+            if state
+                .try_borrow::<Uri>()
+                .and_then(Uri::query)
+                .map_or(false, |query| query_string::count(query) > max)
+            {
+                trace!("[{}] too many query parameters", request_id(&state));
+                let res = create_empty_response(&state, StatusCode::BAD_REQUEST);
+                return self.finalize_response(future::ok((state, res)).boxed());
+            }
+        }
+
+        if self.data.settings.normalize_headers {
+            if let Some(headers) = state.try_borrow_mut::<HeaderMap>() {
+                normalize_headers(headers);
+            }
+        }
+
         let future = match state.try_take::<RequestPathSegments>() {
             Some(rps) => {
                 if let Some((node, params, processed)) = self.data.tree.traverse(&rps.segments()) {
-                    match node.select_route(&state) {
-                        Ok(route) => match route.delegation() {
+                    let preflight = self
+                        .data
+                        .settings
+                        .options_dispatcher
+                        .as_ref()
+                        .filter(|_| is_preflight(&state));
+
+                    match (node.select_route(&state), preflight) {
+                        (Ok(route), _) => match route.delegation() {
                             Delegation::External => {
                                 trace!("[{}] delegating to secondary router", request_id(&state));
 
@@ -88,7 +205,11 @@ impl Handler for Router {
                                 self.dispatch(state, params, route)
                             }
                         },
-                        Err(non_match) => {
+                        (Err(_), Some(dispatcher)) => {
+                            trace!("[{}] dispatching to options pipeline", request_id(&state));
+                            dispatcher.dispatch(state)
+                        }
+                        (Err(non_match), None) => {
                             let (status, allow) = non_match.deconstruct();
 
                             trace!("[{}] responding with error status", request_id(&state));
@@ -121,6 +242,15 @@ impl Handler for Router {
     }
 }
 
+/// Determines whether the request is a CORS preflight request, i.e. an `OPTIONS` request with both
+/// `Origin` and `Access-Control-Request-Method` headers.
+fn is_preflight(state: &State) -> bool {
+    state.try_borrow::<Method>() == Some(&Method::OPTIONS)
+        && state.try_borrow::<HeaderMap>().map_or(false, |headers| {
+            headers.contains_key(ORIGIN) && headers.contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+        })
+}
+
 impl Router {
     /// Manually assembles a `Router` instance from a provided `Tree`.
     #[deprecated(
@@ -128,17 +258,180 @@ impl Router {
         note = "use the new `gotham::router::builder` API to construct a Router"
     )]
     pub fn new(tree: Tree, response_finalizer: ResponseFinalizer) -> Router {
-        Router::internal_new(tree, response_finalizer)
+        Router::internal_new(tree, response_finalizer, RouterSettings::default())
     }
 
     /// Same as `new`, but private and not deprecated.
-    fn internal_new(tree: Tree, response_finalizer: ResponseFinalizer) -> Router {
-        let router_data = RouterData::new(tree, response_finalizer);
+    fn internal_new(
+        tree: Tree,
+        response_finalizer: ResponseFinalizer,
+        settings: RouterSettings,
+    ) -> Router {
+        let router_data = RouterData::new(tree, response_finalizer, settings);
         Router {
             data: Arc::new(router_data),
         }
     }
 
+    /// Describes the routes defined in this `Router`, in the order they are considered when
+    /// dispatching a request.
+    ///
+    /// Routes which delegate to a secondary `Router` are listed, but the routes of the secondary
+    /// `Router` are not.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Method, Response};
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// #
+    /// # fn handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::new(Body::empty()))
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.get("/users").describe("List all users").to(handler);
+    /// });
+    ///
+    /// let routes = router.routes();
+    /// assert_eq!(routes[0].path(), "/users");
+    /// assert_eq!(routes[0].methods(), Some(&[Method::GET][..]));
+    /// assert_eq!(routes[0].description(), Some("List all users"));
+    /// # }
+    /// ```
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        self.data.tree.route_info()
+    }
+
+    /// Merges the routes of `other` into this `Router`, allowing sections of an application to be
+    /// built as independent routers and combined into one.
+    ///
+    /// Each route retains the pipelines it was built with, so the two routers may use different
+    /// pipelines. The router level settings of `self` (response extenders, the options pipeline,
+    /// header normalization, and the limits on HTTP version and query parameters) apply to every
+    /// route of the merged `Router`. As they would otherwise be discarded, `other` must not have
+    /// any router level settings of its own, or `MergeError::Settings` is returned. Where both
+    /// routers define routes at the same node, the routes of `self` are considered first.
+    ///
+    /// Two routes conflict when their paths match the same requests and they accept a common
+    /// request method. Paths match the same requests when each of their segments is of the same
+    /// kind, regardless of the names given to dynamic segments, so `/users/:id` conflicts with
+    /// `/users/:user_id`. Conflicting routes are reported via `MergeError::Conflict`, rather than
+    /// allowing one to silently shadow the other. Routes which are distinguished only by other
+    /// matchers (such as `Accept` headers) are also reported, and should be defined in the same
+    /// router.
+    ///
+    /// Routers can only be merged before they are cloned (e.g. by starting a server), otherwise
+    /// `MergeError::InUse` is returned.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response};
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// #
+    /// # fn handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::new(Body::empty()))
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let users = build_simple_router(|route| {
+    ///     route.get("/users").to(handler);
+    /// });
+    ///
+    /// let orders = build_simple_router(|route| {
+    ///     route.get("/orders").to(handler);
+    /// });
+    ///
+    /// let router = users.merge(orders).unwrap();
+    /// assert_eq!(router.routes().len(), 2);
+    /// # }
+    /// ```
+    pub fn merge(self, other: Router) -> ::std::result::Result<Router, MergeError> {
+        if let Some(route) = self.data.tree.find_conflict(&other.data.tree) {
+            trace!(" conflicting route found during merge: {}", route.path());
+            return Err(MergeError::Conflict(route));
+        }
+
+        if other.data.has_settings() {
+            trace!(" merged router has router level settings");
+            return Err(MergeError::Settings);
+        }
+
+        let mut data = Arc::try_unwrap(self.data).map_err(|_| MergeError::InUse)?;
+        let other = Arc::try_unwrap(other.data).map_err(|_| MergeError::InUse)?;
+
+        data.tree.merge(other.tree);
+
+        Ok(Router {
+            data: Arc::new(data),
+        })
+    }
+
+    /// Explains how a request with the given method and path would be routed, for debugging
+    /// requests which are dispatched to an unexpected `Route`.
+    ///
+    /// The routes considered for the request are listed in priority order, each with the reason
+    /// it was accepted or rejected. Evaluation stops at the first route which matches, so at most
+    /// the final entry will not be a matcher failure. An empty list indicates that no routable
+    /// path matched, or that `path` could not be parsed as a URI.
+    ///
+    /// The request is evaluated without any headers, so routes which match on headers (such as
+    /// `Accept` or `Content-Type`) are reported as `RouteOutcome::MatcherFailed`.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Method, Response};
+    /// # use gotham::router::builder::*;
+    /// # use gotham::router::route::RouteOutcome;
+    /// # use gotham::state::State;
+    /// #
+    /// # fn handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::new(Body::empty()))
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.get("/users").to(handler);
+    ///     route.post("/users").to(handler);
+    /// });
+    ///
+    /// let explanation = router.explain(Method::POST, "/users");
+    /// assert_eq!(explanation.len(), 2);
+    /// assert_eq!(explanation[0].outcome(), RouteOutcome::MethodMismatch);
+    /// assert_eq!(explanation[1].outcome(), RouteOutcome::Accepted);
+    /// # }
+    /// ```
+    pub fn explain(&self, method: Method, path: &str) -> Vec<RouteExplanation> {
+        let uri = match path.parse::<Uri>() {
+            Ok(uri) => uri,
+            Err(_) => return vec![],
+        };
+
+        let rps = RequestPathSegments::new(uri.path());
+
+        let mut state = State::new();
+        state.put(method);
+        state.put(uri);
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+
+        match self.data.tree.traverse(rps.segments()) {
+            Some((node, params, _)) => {
+                let path = self.data.tree.node_path(node).unwrap_or_default();
+                node.explain_routes(&mut state, &params, &path)
+            }
+            None => vec![],
+        }
+    }
+
     fn dispatch<'a>(
         &self,
         mut state: State,
@@ -154,7 +447,12 @@ impl Router {
                         trace!("[{}] dispatching", request_id(&state));
                         route.dispatch(state)
                     }
-                    Err(_) => {
+                    Err(ExtractorFailed) => {
+                        if let Some(ExtractorResponse(res)) = state.try_take() {
+                            trace!("[{}] query string extractor responded", request_id(&state));
+                            return future::ok((state, res)).boxed();
+                        }
+
                         error!("[{}] the server cannot or will not process the request due to a client error within the query string",
                                request_id(&state));
 
@@ -164,7 +462,12 @@ impl Router {
                     }
                 }
             }
-            Err(_) => {
+            Err(ExtractorFailed) => {
+                if let Some(ExtractorResponse(res)) = state.try_take() {
+                    trace!("[{}] path extractor responded", request_id(&state));
+                    return future::ok((state, res)).boxed();
+                }
+
                 error!(
                     "[{}] the server cannot or will not process the request due to a client error on the request path",
                     request_id(&state)
@@ -178,15 +481,19 @@ impl Router {
 
     fn finalize_response(&self, result: Pin<Box<HandlerFuture>>) -> Pin<Box<HandlerFuture>> {
         let response_finalizer = self.data.response_finalizer.clone();
+        let handler_error_response = self.data.settings.handler_error_response.clone();
         result
-            .or_else(|(state, err)| {
+            .or_else(move |(state, err)| {
                 trace!(
                     "[{}] converting error into http response \
                      during finalization: {:?}",
                     request_id(&state),
                     err
                 );
-                let response = err.into_response(&state);
+                let response = match handler_error_response {
+                    Some(f) => f(&state, err),
+                    None => err.into_response(&state),
+                };
                 future::ok((state, response))
             })
             .and_then(move |(state, res)| {
@@ -403,4 +710,344 @@ mod tests {
             Err(_) => unreachable!("Router should have correctly handled request"),
         };
     }
+
+    #[test]
+    fn routes_include_produced_responses() {
+        use crate::router::builder::*;
+
+        let router = build_simple_router(|route| {
+            route
+                .get("/users/:id")
+                .produces(&[(200, mime::APPLICATION_JSON)])
+                .produces(&[(404, mime::TEXT_PLAIN)])
+                .to(handler);
+            route.delete("/users/:id").to(handler);
+        });
+
+        let routes = router.routes();
+        assert_eq!(
+            routes[0].produces(),
+            &[
+                (StatusCode::OK, mime::APPLICATION_JSON),
+                (StatusCode::NOT_FOUND, mime::TEXT_PLAIN),
+            ][..]
+        );
+        assert!(routes[1].produces().is_empty());
+    }
+
+    #[test]
+    fn routes_include_descriptions() {
+        use crate::router::builder::*;
+
+        let router = build_simple_router(|route| {
+            route.get("/users").describe("List all users").to(handler);
+            route.post("/users").to(handler);
+            route.associate("/users/:id", |assoc| {
+                assoc.get().describe("Show a user").to(handler);
+                assoc.delete().to(handler);
+            });
+            route
+                .request(vec![Method::PUT, Method::PATCH], "/users/:id:[0-9]+")
+                .describe("Update a user")
+                .to(handler);
+        });
+
+        let routes: Vec<_> = router
+            .routes()
+            .into_iter()
+            .map(|info| {
+                (
+                    info.path().to_owned(),
+                    info.methods().map(<[Method]>::to_vec),
+                    info.description().map(ToOwned::to_owned),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            routes,
+            vec![
+                (
+                    "/users".to_owned(),
+                    Some(vec![Method::GET]),
+                    Some("List all users".to_owned())
+                ),
+                ("/users".to_owned(), Some(vec![Method::POST]), None),
+                (
+                    "/users/:id:[0-9]+".to_owned(),
+                    Some(vec![Method::PUT, Method::PATCH]),
+                    Some("Update a user".to_owned())
+                ),
+                (
+                    "/users/:id".to_owned(),
+                    Some(vec![Method::GET]),
+                    Some("Show a user".to_owned())
+                ),
+                ("/users/:id".to_owned(), Some(vec![Method::DELETE]), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn explain_lists_candidates_in_priority_order() {
+        use serde_derive::Deserialize;
+
+        use crate::router::builder::*;
+        use crate::router::response::extender::StaticResponseExtender;
+        use crate::router::route::RouteOutcome;
+        use crate::state::StateData;
+
+        #[derive(Deserialize)]
+        struct Page {
+            #[allow(dead_code)]
+            page: u32,
+        }
+
+        impl StateData for Page {}
+
+        impl StaticResponseExtender for Page {
+            type ResBody = Body;
+
+            fn extend(_state: &mut State, _res: &mut Response<Body>) {}
+        }
+
+        let router = build_simple_router(|route| {
+            route.associate("/users/:id", |assoc| {
+                assoc.put().to(handler);
+                assoc
+                    .get()
+                    .with_query_string_extractor::<Page>()
+                    .to(handler);
+                assoc.delete().to(handler);
+            });
+        });
+
+        let outcomes = |method, path| {
+            router
+                .explain(method, path)
+                .into_iter()
+                .map(|explanation| {
+                    assert_eq!(explanation.route().path(), "/users/:id");
+                    (
+                        explanation.route().methods().unwrap()[0].clone(),
+                        explanation.outcome(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            outcomes(Method::DELETE, "/users/1"),
+            vec![
+                (Method::PUT, RouteOutcome::MethodMismatch),
+                (Method::GET, RouteOutcome::MethodMismatch),
+                (Method::DELETE, RouteOutcome::Accepted),
+            ]
+        );
+
+        assert_eq!(
+            outcomes(Method::GET, "/users/1?page=first"),
+            vec![
+                (Method::PUT, RouteOutcome::MethodMismatch),
+                (Method::GET, RouteOutcome::QueryStringExtractorFailed),
+            ]
+        );
+
+        assert_eq!(
+            outcomes(Method::GET, "/users/1?page=2"),
+            vec![
+                (Method::PUT, RouteOutcome::MethodMismatch),
+                (Method::GET, RouteOutcome::Accepted),
+            ]
+        );
+
+        assert!(router.explain(Method::GET, "/missing").is_empty());
+    }
+
+    #[test]
+    fn extractors_can_respond_without_dispatching() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        use serde_derive::Deserialize;
+
+        use crate::router::builder::*;
+        use crate::router::response::extender::StaticResponseExtender;
+        use crate::state::StateData;
+        use crate::test::TestServer;
+
+        static INVOKED: AtomicBool = AtomicBool::new(false);
+
+        #[derive(Deserialize)]
+        struct SignedLink {
+            expires: u64,
+        }
+
+        impl StateData for SignedLink {}
+
+        impl StaticResponseExtender for SignedLink {
+            type ResBody = Body;
+
+            fn extend(_state: &mut State, _res: &mut Response<Body>) {}
+
+            fn respond(&self, state: &State) -> Option<Response<Body>> {
+                if self.expires < 100 {
+                    Some(create_empty_response(state, StatusCode::GONE))
+                } else {
+                    None
+                }
+            }
+        }
+
+        fn download(state: State) -> (State, Response<Body>) {
+            INVOKED.store(true, Ordering::SeqCst);
+            let res = create_empty_response(&state, StatusCode::OK);
+            (state, res)
+        }
+
+        let router = build_simple_router(|route| {
+            route
+                .get("/download")
+                .with_query_string_extractor::<SignedLink>()
+                .to(download);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |uri| test_server.client().get(uri).perform().unwrap().status();
+
+        assert_eq!(
+            get("http://localhost/download?expires=50"),
+            StatusCode::GONE
+        );
+        assert!(!INVOKED.load(Ordering::SeqCst));
+
+        assert_eq!(get("http://localhost/download?expires=150"), StatusCode::OK);
+        assert!(INVOKED.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn merge_combines_disjoint_routers() {
+        use crate::router::builder::*;
+
+        fn created(state: State) -> (State, Response<Body>) {
+            let res = create_empty_response(&state, StatusCode::CREATED);
+            (state, res)
+        }
+
+        let users = build_simple_router(|route| {
+            route.get("/api/users").to(handler);
+        });
+
+        let orders = build_simple_router(|route| {
+            route.post("/api/orders").to(created);
+            route.get("/api/users").to(handler);
+        });
+
+        match users.merge(orders) {
+            Err(MergeError::Conflict(route)) => assert_eq!(route.path(), "/api/users"),
+            _ => panic!("expected a conflict"),
+        }
+
+        let users = build_simple_router(|route| {
+            route.get("/api/users").to(handler);
+            route.post("/api/users").to(created);
+        });
+
+        let orders = build_simple_router(|route| {
+            route.post("/api/orders").to(created);
+            route.delete("/api/users").to(handler);
+        });
+
+        let router = users.merge(orders).unwrap();
+
+        let paths = router
+            .routes()
+            .into_iter()
+            .map(|route| route.path().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec!["/api/orders", "/api/users", "/api/users", "/api/users"]
+        );
+
+        let status = |method, uri| match send_request(router.clone(), method, uri) {
+            Ok((_, res)) => res.status(),
+            Err(_) => panic!("Router should have handled request"),
+        };
+
+        assert_eq!(status(Method::GET, "/api/users"), StatusCode::OK);
+        assert_eq!(status(Method::POST, "/api/users"), StatusCode::CREATED);
+        assert_eq!(status(Method::DELETE, "/api/users"), StatusCode::OK);
+        assert_eq!(status(Method::POST, "/api/orders"), StatusCode::CREATED);
+        assert_eq!(status(Method::GET, "/api/missing"), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn merge_detects_conflicts_between_differently_named_segments() {
+        use crate::router::builder::*;
+
+        let users = build_simple_router(|route| {
+            route.get("/users/:id").to(handler);
+            route.get("/tags/\\:all").to(handler);
+        });
+
+        let profiles = build_simple_router(|route| {
+            route.get("/users/:user_id").to(handler);
+        });
+
+        match users.merge(profiles) {
+            Err(MergeError::Conflict(route)) => assert_eq!(route.path(), "/users/:user_id"),
+            _ => panic!("expected a conflict"),
+        }
+
+        let users = build_simple_router(|route| {
+            route.get("/users/:id").to(handler);
+            route.get("/tags/\\:all").to(handler);
+        });
+
+        // a static segment does not conflict with a dynamic one, even where their text matches
+        let tags = build_simple_router(|route| {
+            route.get("/tags/:all").to(handler);
+            route.get("/users/:id:[0-9]+").to(handler);
+        });
+
+        assert!(users.merge(tags).is_ok());
+    }
+
+    #[test]
+    fn merge_rejects_router_level_settings() {
+        use crate::router::builder::*;
+
+        let users = build_simple_router(|route| {
+            route.get("/users").to(handler);
+        });
+
+        let orders = build_simple_router(|route| {
+            route.max_query_params(10);
+            route.get("/orders").to(handler);
+        });
+
+        match users.merge(orders) {
+            Err(MergeError::Settings) => (),
+            _ => panic!("expected the router level settings to be rejected"),
+        }
+    }
+
+    #[test]
+    fn merge_rejects_routers_in_use() {
+        use crate::router::builder::*;
+
+        let users = build_simple_router(|route| {
+            route.get("/users").to(handler);
+        });
+        let _in_use = users.clone();
+
+        let orders = build_simple_router(|route| {
+            route.get("/orders").to(handler);
+        });
+
+        match users.merge(orders) {
+            Err(MergeError::InUse) => (),
+            _ => panic!("expected the router to be in use"),
+        }
+    }
 }
@@ -12,6 +12,13 @@ pub trait StaticResponseExtender: RefUnwindSafe {
 
     /// Extend the response.
     fn extend(state: &mut State, response: &mut Response<Self::ResBody>);
+
+    /// Inspects the value once it has been extracted from the request, returning a `Response` to
+    /// send in place of dispatching the request (e.g. `410 Gone` for a signed URL which has
+    /// expired). The request is dispatched as normal when `None` is returned, which is the default.
+    fn respond(&self, _state: &State) -> Option<Response<Self::ResBody>> {
+        None
+    }
 }
 
 /// Allow complex types to extend the `Response` based on current `State` and `Response` data.
@@ -5,12 +5,18 @@ use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::Arc;
 
+use bytes::BytesMut;
 use futures::prelude::*;
+use futures::stream;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderValue, CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE};
 use hyper::{Body, Response, StatusCode};
 use log::trace;
 
 use crate::handler::HandlerFuture;
-use crate::state::{request_id, State};
+use crate::helpers::http::header::X_RESPONSE_TIME;
+use crate::state::csp_nonce::put_csp_nonce;
+use crate::state::{request_id, request_start, CspNonce, FromState, State};
 
 use crate::router::response::extender::ResponseExtender;
 
@@ -21,11 +27,17 @@ use crate::router::response::extender::ResponseExtender;
 #[derive(Clone)]
 pub struct ResponseFinalizer {
     data: Arc<HashMap<StatusCode, Box<dyn ResponseExtender<Body> + Send + Sync>>>,
+    response_time_header: bool,
+    buffer_threshold: Option<usize>,
+    csp_policy: Option<String>,
 }
 
 /// Builds an immutable `ResponseFinalizer`.
 pub struct ResponseFinalizerBuilder {
     data: HashMap<StatusCode, Box<dyn ResponseExtender<Body> + Send + Sync>>,
+    response_time_header: bool,
+    buffer_threshold: Option<usize>,
+    csp_policy: Option<String>,
 }
 
 impl ResponseFinalizerBuilder {
@@ -40,7 +52,12 @@ impl ResponseFinalizerBuilder {
 
     pub(in crate::router) fn internal_new() -> Self {
         let handlers = HashMap::new();
-        ResponseFinalizerBuilder { data: handlers }
+        ResponseFinalizerBuilder {
+            data: handlers,
+            response_time_header: false,
+            buffer_threshold: None,
+            csp_policy: None,
+        }
     }
 
     /// Add an Finalizer for responses that have been assigned this status_code.
@@ -53,15 +70,58 @@ impl ResponseFinalizerBuilder {
         self.data.insert(status_code, extender);
     }
 
+    /// Stamps every response with an `X-Response-Time` header, holding the number of milliseconds
+    /// elapsed since the request was received according to the `RequestStart` in `State`.
+    pub fn add_response_time_header(&mut self) {
+        trace!(" adding response time header");
+        self.response_time_header = true;
+    }
+
+    /// Buffers response bodies of unknown length up to `threshold` bytes, so that a
+    /// `Content-Length` header can be sent for small responses. Bodies which exceed the threshold
+    /// are sent with chunked transfer encoding, starting with the buffered bytes.
+    pub fn buffer_responses(&mut self, threshold: usize) {
+        trace!(" buffering responses up to {} bytes", threshold);
+        self.buffer_threshold = Some(threshold);
+    }
+
+    /// Generates a `CspNonce` for every request, and adds a `Content-Security-Policy` header to
+    /// `text/html` responses. Each occurrence of `{nonce}` in the `policy` is replaced with the
+    /// nonce, e.g. `script-src 'nonce-{nonce}'`.
+    pub fn add_csp_nonce(&mut self, policy: &str) {
+        trace!(" adding csp nonce");
+        self.csp_policy = Some(policy.to_owned());
+    }
+
     /// Finalize population of error handlers for the application, ready for use by a `Router`
     pub fn finalize(self) -> ResponseFinalizer {
         ResponseFinalizer {
             data: Arc::new(self.data),
+            response_time_header: self.response_time_header,
+            buffer_threshold: self.buffer_threshold,
+            csp_policy: self.csp_policy,
         }
     }
 }
 
 impl ResponseFinalizer {
+    /// Determines whether the `ResponseFinalizer` leaves every `Response` untouched, having no
+    /// extenders or other settings.
+    pub(crate) fn is_default(&self) -> bool {
+        self.data.is_empty()
+            && !self.response_time_header
+            && self.buffer_threshold.is_none()
+            && self.csp_policy.is_none()
+    }
+
+    /// Stores any values required during finalization in `State`, before the request is
+    /// dispatched.
+    pub(crate) fn prepare(&self, state: &mut State) {
+        if self.csp_policy.is_some() {
+            put_csp_nonce(state);
+        }
+    }
+
     /// Finalize the `Response` if a `ResponseFinalizer` has been supplied for the
     /// status code assigned to the `Response`.
     pub fn finalize(&self, mut state: State, mut res: Response<Body>) -> Pin<Box<HandlerFuture>> {
@@ -83,6 +143,241 @@ impl ResponseFinalizer {
             }
         }
 
-        future::ok((state, res)).boxed()
+        if self.response_time_header {
+            if let Some(elapsed) = request_start::elapsed(&state) {
+                let millis = elapsed.as_secs_f64() * 1000.0;
+                res.headers_mut()
+                    .insert(X_RESPONSE_TIME, format!("{:.3}", millis).parse().unwrap());
+            }
+        }
+
+        if let Some(ref policy) = self.csp_policy {
+            add_csp_header(&state, &mut res, policy);
+        }
+
+        match self.buffer_threshold {
+            Some(threshold) => buffer_body(res, threshold)
+                .map(|res| Ok((state, res)))
+                .boxed(),
+            None => future::ok((state, res)).boxed(),
+        }
+    }
+}
+
+/// Adds a `Content-Security-Policy` header holding the `CspNonce` to `text/html` responses which
+/// do not already have one.
+fn add_csp_header(state: &State, res: &mut Response<Body>, policy: &str) {
+    let is_html = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .map(|mime| mime.type_() == mime::TEXT && mime.subtype() == mime::HTML)
+        .unwrap_or(false);
+
+    if !is_html || res.headers().contains_key(CONTENT_SECURITY_POLICY) {
+        return;
+    }
+
+    let nonce = match CspNonce::try_borrow_from(state) {
+        Some(nonce) => nonce,
+        None => return,
+    };
+
+    match HeaderValue::from_str(&policy.replace("{nonce}", nonce.value())) {
+        Ok(value) => {
+            res.headers_mut().insert(CONTENT_SECURITY_POLICY, value);
+        }
+        Err(_) => trace!("[{}] invalid csp policy", request_id(state)),
+    }
+}
+
+/// Reads the body of a `Response` of unknown length until it completes, or exceeds `threshold`
+/// bytes. Completed bodies are sent with a `Content-Length`, and others are streamed on from the
+/// buffered bytes.
+async fn buffer_body(res: Response<Body>, threshold: usize) -> Response<Body> {
+    if res.headers().contains_key(CONTENT_LENGTH) || res.body().size_hint().exact().is_some() {
+        return res;
+    }
+
+    let (mut parts, mut body) = res.into_parts();
+    let mut buffered = BytesMut::new();
+
+    while buffered.len() <= threshold {
+        match body.data().await {
+            Some(Ok(chunk)) => buffered.extend_from_slice(&chunk),
+            Some(Err(e)) => {
+                // the error is passed on, so the response fails as it would without buffering
+                let chunks = vec![Ok(buffered.freeze()), Err(e)];
+                return Response::from_parts(parts, Body::wrap_stream(stream::iter(chunks)));
+            }
+            None => {
+                parts.headers.insert(CONTENT_LENGTH, buffered.len().into());
+                return Response::from_parts(parts, Body::from(buffered.freeze()));
+            }
+        }
+    }
+
+    let rest = stream::once(future::ok(buffered.freeze())).chain(body);
+    Response::from_parts(parts, Body::wrap_stream(rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::state::request_start::put_request_start;
+
+    #[test]
+    fn response_time_header_is_added() {
+        let mut builder = ResponseFinalizerBuilder::internal_new();
+        builder.add_response_time_header();
+        let finalizer = builder.finalize();
+
+        let mut state = State::new();
+        put_request_start(&mut state);
+
+        let (_state, res) =
+            futures::executor::block_on(finalizer.finalize(state, Response::new(Body::empty())))
+                .unwrap_or_else(|_| panic!("finalizer failed"));
+
+        let millis: f64 = res
+            .headers()
+            .get(X_RESPONSE_TIME)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        assert!(millis >= 0.0);
+    }
+
+    #[test]
+    fn response_time_header_is_opt_in() {
+        let finalizer = ResponseFinalizerBuilder::internal_new().finalize();
+
+        let mut state = State::new();
+        put_request_start(&mut state);
+
+        let (_state, res) =
+            futures::executor::block_on(finalizer.finalize(state, Response::new(Body::empty())))
+                .unwrap_or_else(|_| panic!("finalizer failed"));
+
+        assert!(res.headers().get(X_RESPONSE_TIME).is_none());
+    }
+
+    fn finalize_streamed(chunks: Vec<&'static str>) -> Response<Body> {
+        let mut builder = ResponseFinalizerBuilder::internal_new();
+        builder.buffer_responses(16);
+        let finalizer = builder.finalize();
+
+        let chunks = chunks.into_iter().map(Ok::<_, hyper::Error>);
+        let res = Response::new(Body::wrap_stream(stream::iter(chunks)));
+
+        let (_state, res) = futures::executor::block_on(finalizer.finalize(State::new(), res))
+            .unwrap_or_else(|_| panic!("finalizer failed"));
+
+        res
+    }
+
+    fn read_body(res: Response<Body>) -> Vec<u8> {
+        futures::executor::block_on(hyper::body::to_bytes(res.into_body()))
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn small_responses_are_buffered() {
+        let res = finalize_streamed(vec!["Hello, ", "world!"]);
+
+        assert_eq!(res.headers()[CONTENT_LENGTH], "13");
+        assert_eq!(res.body().size_hint().exact(), Some(13));
+        assert_eq!(read_body(res), b"Hello, world!");
+    }
+
+    #[test]
+    fn large_responses_are_streamed() {
+        let res = finalize_streamed(vec!["Hello, ", "world! ", "This body is ", "too large."]);
+
+        assert!(res.headers().get(CONTENT_LENGTH).is_none());
+        assert!(res.body().size_hint().exact().is_none());
+        assert_eq!(
+            read_body(res),
+            &b"Hello, world! This body is too large."[..]
+        );
+    }
+
+    fn finalize_with_csp(state: State, res: Response<Body>) -> Response<Body> {
+        let mut builder = ResponseFinalizerBuilder::internal_new();
+        builder.add_csp_nonce("script-src 'nonce-{nonce}'; object-src 'none'");
+        let finalizer = builder.finalize();
+
+        let (_state, res) = futures::executor::block_on(finalizer.finalize(state, res))
+            .unwrap_or_else(|_| panic!("finalizer failed"));
+
+        res
+    }
+
+    fn prepared_state() -> State {
+        let mut builder = ResponseFinalizerBuilder::internal_new();
+        builder.add_csp_nonce("script-src 'nonce-{nonce}'");
+
+        let mut state = State::new();
+        builder.finalize().prepare(&mut state);
+        state
+    }
+
+    fn html_response() -> Response<Body> {
+        Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn csp_header_holds_nonce_from_state() {
+        let state = prepared_state();
+        let nonce = CspNonce::borrow_from(&state).value().to_owned();
+
+        let res = finalize_with_csp(state, html_response());
+
+        assert_eq!(
+            res.headers()[CONTENT_SECURITY_POLICY],
+            format!("script-src 'nonce-{}'; object-src 'none'", nonce).as_str()
+        );
+    }
+
+    #[test]
+    fn csp_header_is_only_added_to_html() {
+        let res = Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::empty())
+            .unwrap();
+
+        let res = finalize_with_csp(prepared_state(), res);
+        assert!(res.headers().get(CONTENT_SECURITY_POLICY).is_none());
+    }
+
+    #[test]
+    fn csp_header_set_by_handler_is_retained() {
+        let mut res = html_response();
+        res.headers_mut().insert(
+            CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'self'"),
+        );
+
+        let res = finalize_with_csp(prepared_state(), res);
+        assert_eq!(res.headers()[CONTENT_SECURITY_POLICY], "default-src 'self'");
+    }
+
+    #[test]
+    fn csp_nonce_is_opt_in() {
+        let mut state = State::new();
+        ResponseFinalizerBuilder::internal_new()
+            .finalize()
+            .prepare(&mut state);
+
+        assert!(CspNonce::try_borrow_from(&state).is_none());
     }
 }
@@ -0,0 +1,305 @@
+//! Defines a `ResponseExtender` which renders JSON error bodies, in an envelope chosen by the
+//! application.
+
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::{body::HttpBody, Body, Method, Response, StatusCode};
+use log::trace;
+use mime::Mime;
+use serde_json::{json, Map, Value};
+use std::panic::RefUnwindSafe;
+
+use crate::router::response::extender::ResponseExtender;
+use crate::state::{request_id, FromState, State, StateData};
+
+/// A description of an error, stored in `State` by a handler or middleware so that it is included
+/// in the error body rendered by `JsonErrorExtender`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorDetail {
+    detail: String,
+}
+
+impl StateData for ErrorDetail {}
+
+impl ErrorDetail {
+    /// Creates a new `ErrorDetail` holding the given description.
+    pub fn new<S: Into<String>>(detail: S) -> ErrorDetail {
+        ErrorDetail {
+            detail: detail.into(),
+        }
+    }
+
+    /// The description of the error.
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+}
+
+/// Renders the JSON body of an error response, allowing the shape of the envelope to be chosen by
+/// the application.
+pub trait ErrorFormatter: RefUnwindSafe {
+    /// The content type of the rendered body.
+    fn content_type(&self) -> Mime {
+        mime::APPLICATION_JSON
+    }
+
+    /// Renders the body for an error with the given `status`, and a `detail` when one was stored
+    /// in `State` via `ErrorDetail`.
+    fn format(&self, status: StatusCode, detail: Option<&str>) -> Value;
+}
+
+/// Renders errors as a single message, e.g. `{"error": "Not Found"}`.
+///
+/// The message is the `ErrorDetail` when present, and the canonical reason of the status code
+/// otherwise.
+#[derive(Clone, Copy, Debug)]
+pub struct SingleErrorFormatter;
+
+impl ErrorFormatter for SingleErrorFormatter {
+    fn format(&self, status: StatusCode, detail: Option<&str>) -> Value {
+        json!({ "error": message(status, detail) })
+    }
+}
+
+/// Renders errors as a list of messages, e.g. `{"errors": ["Not Found"]}`.
+///
+/// The message is the `ErrorDetail` when present, and the canonical reason of the status code
+/// otherwise.
+#[derive(Clone, Copy, Debug)]
+pub struct ErrorListFormatter;
+
+impl ErrorFormatter for ErrorListFormatter {
+    fn format(&self, status: StatusCode, detail: Option<&str>) -> Value {
+        json!({ "errors": [message(status, detail)] })
+    }
+}
+
+/// Renders errors as RFC 7807 problem details, with a content type of `application/problem+json`,
+/// e.g. `{"type": "about:blank", "title": "Not Found", "status": 404}`.
+///
+/// The `ErrorDetail` is included as `detail` when present.
+#[derive(Clone, Debug)]
+pub struct ProblemDetailsFormatter {
+    type_uri: String,
+}
+
+impl ProblemDetailsFormatter {
+    /// Creates a new `ProblemDetailsFormatter`, using `about:blank` as the problem type.
+    pub fn new() -> ProblemDetailsFormatter {
+        ProblemDetailsFormatter {
+            type_uri: "about:blank".to_owned(),
+        }
+    }
+
+    /// Sets the URI identifying the problem type.
+    pub fn with_type(self, type_uri: &str) -> ProblemDetailsFormatter {
+        ProblemDetailsFormatter {
+            type_uri: type_uri.to_owned(),
+        }
+    }
+}
+
+impl Default for ProblemDetailsFormatter {
+    fn default() -> ProblemDetailsFormatter {
+        ProblemDetailsFormatter::new()
+    }
+}
+
+impl ErrorFormatter for ProblemDetailsFormatter {
+    fn content_type(&self) -> Mime {
+        "application/problem+json".parse().unwrap()
+    }
+
+    fn format(&self, status: StatusCode, detail: Option<&str>) -> Value {
+        let mut problem = Map::new();
+        problem.insert("type".to_owned(), Value::from(self.type_uri.as_str()));
+        problem.insert("title".to_owned(), Value::from(reason(status)));
+        problem.insert("status".to_owned(), Value::from(status.as_u16()));
+
+        if let Some(detail) = detail {
+            problem.insert("detail".to_owned(), Value::from(detail));
+        }
+
+        Value::Object(problem)
+    }
+}
+
+fn reason(status: StatusCode) -> &'static str {
+    status.canonical_reason().unwrap_or("Unknown Error")
+}
+
+fn message(status: StatusCode, detail: Option<&str>) -> &str {
+    detail.unwrap_or_else(|| reason(status))
+}
+
+/// A `ResponseExtender` which adds a JSON error body to responses, rendered by an
+/// `ErrorFormatter`. Responses which already have a body are not affected.
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::create_empty_response;
+/// # use gotham::router::Router;
+/// # use gotham::router::builder::*;
+/// # use gotham::router::response::json_error::{ErrorDetail, JsonErrorExtender, ProblemDetailsFormatter};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn my_handler(mut state: State) -> (State, Response<Body>) {
+///     state.put(ErrorDetail::new("No user with ID 42"));
+///     let res = create_empty_response(&state, StatusCode::NOT_FOUND);
+///     (state, res)
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route.add_response_extender(
+///             StatusCode::NOT_FOUND,
+///             JsonErrorExtender::new(ProblemDetailsFormatter::new()),
+///         );
+///
+///         route.get("/users/42").to(my_handler);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/users/42")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+/// #   assert_eq!(response.headers()["content-type"], "application/problem+json");
+/// #   assert_eq!(
+/// #       response.read_utf8_body().unwrap(),
+/// #       r#"{"detail":"No user with ID 42","status":404,"title":"Not Found","type":"about:blank"}"#
+/// #   );
+/// # }
+/// ```
+pub struct JsonErrorExtender<F>
+where
+    F: ErrorFormatter,
+{
+    formatter: F,
+}
+
+impl<F> JsonErrorExtender<F>
+where
+    F: ErrorFormatter,
+{
+    /// Creates a new `JsonErrorExtender`, rendering error bodies with `formatter`.
+    pub fn new(formatter: F) -> JsonErrorExtender<F> {
+        JsonErrorExtender { formatter }
+    }
+}
+
+impl<F> ResponseExtender<Body> for JsonErrorExtender<F>
+where
+    F: ErrorFormatter,
+{
+    fn extend(&self, state: &mut State, res: &mut Response<Body>) {
+        if res.body().size_hint().exact() != Some(0) {
+            trace!(
+                "[{}] response already has a body, no change made",
+                request_id(state)
+            );
+            return;
+        }
+
+        let detail = ErrorDetail::try_borrow_from(state).map(ErrorDetail::detail);
+        let body = self.formatter.format(res.status(), detail).to_string();
+
+        let headers = res.headers_mut();
+        headers.insert(
+            CONTENT_TYPE,
+            self.formatter.content_type().as_ref().parse().unwrap(),
+        );
+        headers.insert(CONTENT_LENGTH, body.len().into());
+
+        if Method::try_borrow_from(state) != Some(&Method::HEAD) {
+            *res.body_mut() = Body::from(body);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extend<F: ErrorFormatter>(formatter: F, detail: Option<&str>) -> (String, Value) {
+        let mut state = State::new();
+        state.put(Method::GET);
+        if let Some(detail) = detail {
+            state.put(ErrorDetail::new(detail));
+        }
+
+        let mut res = Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap();
+
+        JsonErrorExtender::new(formatter).extend(&mut state, &mut res);
+
+        let content_type = res.headers()[CONTENT_TYPE].to_str().unwrap().to_owned();
+        let body = futures::executor::block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        (content_type, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn renders_single_error() {
+        let (content_type, body) = extend(SingleErrorFormatter, Some("No user with ID 42"));
+
+        assert_eq!(content_type, "application/json");
+        assert_eq!(body, json!({ "error": "No user with ID 42" }));
+
+        let (_, body) = extend(SingleErrorFormatter, None);
+        assert_eq!(body, json!({ "error": "Not Found" }));
+    }
+
+    #[test]
+    fn renders_error_list() {
+        let (content_type, body) = extend(ErrorListFormatter, Some("No user with ID 42"));
+
+        assert_eq!(content_type, "application/json");
+        assert_eq!(body, json!({ "errors": ["No user with ID 42"] }));
+    }
+
+    #[test]
+    fn renders_problem_details() {
+        let formatter = ProblemDetailsFormatter::new().with_type("https://example.com/not-found");
+        let (content_type, body) = extend(formatter, Some("No user with ID 42"));
+
+        assert_eq!(content_type, "application/problem+json");
+        assert_eq!(
+            body,
+            json!({
+                "type": "https://example.com/not-found",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "No user with ID 42",
+            })
+        );
+
+        let (_, body) = extend(ProblemDetailsFormatter::new(), None);
+        assert_eq!(
+            body,
+            json!({ "type": "about:blank", "title": "Not Found", "status": 404 })
+        );
+    }
+
+    #[test]
+    fn existing_bodies_are_retained() {
+        let mut state = State::new();
+        let mut res = Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("custom"))
+            .unwrap();
+
+        JsonErrorExtender::new(SingleErrorFormatter).extend(&mut state, &mut res);
+
+        assert!(res.headers().get(CONTENT_TYPE).is_none());
+        let body = futures::executor::block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        assert_eq!(&body[..], b"custom");
+    }
+}
@@ -2,3 +2,4 @@
 
 pub mod extender;
 pub mod finalizer;
+pub mod json_error;
@@ -1,5 +1,7 @@
 //! Defines the type `AndRouteMatcher`
 
+use hyper::Method;
+
 use crate::router::non_match::RouteNonMatch;
 use crate::router::route::RouteMatcher;
 use crate::state::State;
@@ -83,4 +85,12 @@ where
             (Err(e), Err(e1)) => Err(e.intersection(e1)),
         }
     }
+
+    fn methods(&self) -> Option<Vec<Method>> {
+        match (self.t.methods(), self.u.methods()) {
+            (Some(t), Some(u)) => Some(t.into_iter().filter(|m| u.contains(m)).collect()),
+            (Some(t), None) => Some(t),
+            (None, u) => u,
+        }
+    }
 }
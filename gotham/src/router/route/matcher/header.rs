@@ -0,0 +1,81 @@
+//! Defines the `HeaderRouteMatcher`.
+
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::StatusCode;
+use log::trace;
+
+use crate::router::non_match::RouteNonMatch;
+use crate::router::route::RouteMatcher;
+use crate::state::{request_id, FromState, State};
+
+/// A `RouteMatcher` that succeeds when the `Request` has been made with a header of the given name
+/// and value. Where the header is repeated, any of the values may match. The matcher will fail
+/// with `400 Bad Request` if the header is missing or holds a different value.
+///
+/// Header values are compared exactly, so values which may be padded with whitespace by clients
+/// should be normalized first via `RouterBuilder::normalize_headers`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # fn main() {
+/// #   use hyper::header::HeaderMap;
+/// #   use gotham::state::State;
+/// #   use gotham::router::route::matcher::{HeaderRouteMatcher, RouteMatcher};
+/// #
+/// #   State::with_new(|state| {
+/// #
+/// let matcher = HeaderRouteMatcher::new("x-api-version".parse().unwrap(), "2".parse().unwrap());
+///
+/// // No header
+/// state.put(HeaderMap::new());
+/// assert!(matcher.is_match(&state).is_err());
+///
+/// // Matching header
+/// let mut headers = HeaderMap::new();
+/// headers.insert("x-api-version", "2".parse().unwrap());
+/// state.put(headers);
+/// assert!(matcher.is_match(&state).is_ok());
+///
+/// // Different value
+/// let mut headers = HeaderMap::new();
+/// headers.insert("x-api-version", "1".parse().unwrap());
+/// state.put(headers);
+/// assert!(matcher.is_match(&state).is_err());
+/// #
+/// #   });
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct HeaderRouteMatcher {
+    name: HeaderName,
+    value: HeaderValue,
+}
+
+impl HeaderRouteMatcher {
+    /// Creates a new `HeaderRouteMatcher`
+    pub fn new(name: HeaderName, value: HeaderValue) -> Self {
+        HeaderRouteMatcher { name, value }
+    }
+}
+
+impl RouteMatcher for HeaderRouteMatcher {
+    /// Determines if the `Request` was made with the required header value.
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        let headers = HeaderMap::borrow_from(state);
+
+        if headers.get_all(&self.name).iter().any(|v| *v == self.value) {
+            return Ok(());
+        }
+
+        trace!(
+            "[{}] did not provide the {} header required by this Route",
+            request_id(&state),
+            self.name
+        );
+
+        Err(RouteNonMatch::new(StatusCode::BAD_REQUEST))
+    }
+}
@@ -4,10 +4,14 @@ pub mod accept;
 pub mod and;
 pub mod any;
 pub mod content_type;
+pub mod header;
+pub mod or;
 
 pub use self::accept::AcceptHeaderRouteMatcher;
 pub use self::and::AndRouteMatcher;
 pub use self::any::AnyRouteMatcher;
+pub use self::header::HeaderRouteMatcher;
+pub use self::or::OrRouteMatcher;
 
 use std::panic::RefUnwindSafe;
 
@@ -22,6 +26,12 @@ use crate::state::{request_id, FromState, State};
 pub trait RouteMatcher: RefUnwindSafe + Clone {
     /// Determines if the `Request` meets pre-defined conditions.
     fn is_match(&self, state: &State) -> Result<(), RouteNonMatch>;
+
+    /// The request methods accepted by this `RouteMatcher`, or `None` if it does not restrict the
+    /// request method. This is used for route introspection only, and does not affect matching.
+    fn methods(&self) -> Option<Vec<Method>> {
+        None
+    }
 }
 
 /// Allow various types to represent themselves as a `RouteMatcher`
@@ -111,4 +121,8 @@ impl RouteMatcher for MethodOnlyRouteMatcher {
                 .with_allow_list(self.methods.as_slice()))
         }
     }
+
+    fn methods(&self) -> Option<Vec<Method>> {
+        Some(self.methods.clone())
+    }
 }
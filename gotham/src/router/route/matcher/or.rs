@@ -0,0 +1,157 @@
+//! Defines the type `OrRouteMatcher`
+
+use hyper::Method;
+
+use crate::router::non_match::RouteNonMatch;
+use crate::router::route::RouteMatcher;
+use crate::state::State;
+
+/// Allows two `RouteMatcher` values to be combined, such that a request is accepted when either of
+/// them matches.
+///
+/// The first matcher is tried first, and the second is only consulted when the first fails. When
+/// both fail, their errors are combined via `RouteNonMatch::union`, so that the most specific
+/// status is used, and the `Allow` header lists the methods accepted by either matcher.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # fn main() {
+/// #   use hyper::header::{HeaderMap, HOST};
+/// #   use gotham::state::State;
+/// #   use gotham::router::route::matcher::{HeaderRouteMatcher, OrRouteMatcher, RouteMatcher};
+/// #
+/// #   State::with_new(|state| {
+/// #
+/// let matcher = OrRouteMatcher::new(
+///     HeaderRouteMatcher::new(HOST, "example.com".parse().unwrap()),
+///     HeaderRouteMatcher::new(HOST, "www.example.com".parse().unwrap()),
+/// );
+///
+/// // Request to the second host
+/// let mut headers = HeaderMap::new();
+/// headers.insert(HOST, "www.example.com".parse().unwrap());
+/// state.put(headers);
+/// assert!(matcher.is_match(&state).is_ok());
+///
+/// // Request to neither host
+/// let mut headers = HeaderMap::new();
+/// headers.insert(HOST, "example.org".parse().unwrap());
+/// state.put(headers);
+/// assert!(matcher.is_match(&state).is_err());
+/// #
+/// #   });
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct OrRouteMatcher<T, U>
+where
+    T: RouteMatcher,
+    U: RouteMatcher,
+{
+    t: T,
+    u: U,
+}
+
+impl<T, U> OrRouteMatcher<T, U>
+where
+    T: RouteMatcher,
+    U: RouteMatcher,
+{
+    /// Creates a new `OrRouteMatcher`
+    pub fn new(t: T, u: U) -> Self {
+        OrRouteMatcher { t, u }
+    }
+}
+
+impl<T, U> RouteMatcher for OrRouteMatcher<T, U>
+where
+    T: RouteMatcher,
+    U: RouteMatcher,
+{
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        match self.t.is_match(state) {
+            Ok(()) => Ok(()),
+            Err(e) => self.u.is_match(state).map_err(|e1| e.union(e1)),
+        }
+    }
+
+    fn methods(&self) -> Option<Vec<Method>> {
+        match (self.t.methods(), self.u.methods()) {
+            (Some(mut t), Some(u)) => {
+                for method in u {
+                    if !t.contains(&method) {
+                        t.push(method);
+                    }
+                }
+                Some(t)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::{HeaderMap, ACCEPT};
+    use hyper::StatusCode;
+
+    use crate::router::route::matcher::{AcceptHeaderRouteMatcher, MethodOnlyRouteMatcher};
+    use crate::state::set_request_id;
+
+    fn matcher() -> OrRouteMatcher<MethodOnlyRouteMatcher, AcceptHeaderRouteMatcher> {
+        OrRouteMatcher::new(
+            MethodOnlyRouteMatcher::new(vec![Method::PUT]),
+            AcceptHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON]),
+        )
+    }
+
+    fn state_with(method: Method, accept: mime::Mime) -> State {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, accept.to_string().parse().unwrap());
+
+        let mut state = State::new();
+        state.put(method);
+        state.put(headers);
+        set_request_id(&mut state);
+        state
+    }
+
+    #[test]
+    fn matches_when_only_second_matcher_matches() {
+        let state = state_with(Method::GET, mime::APPLICATION_JSON);
+        assert!(matcher().is_match(&state).is_ok());
+    }
+
+    #[test]
+    fn fails_with_most_specific_error_when_neither_matches() {
+        let state = state_with(Method::GET, mime::TEXT_HTML);
+
+        match matcher().is_match(&state) {
+            Err(e) => assert_eq!(StatusCode::from(e), StatusCode::NOT_ACCEPTABLE),
+            Ok(()) => panic!("expected neither matcher to match"),
+        }
+    }
+
+    #[test]
+    fn methods_are_combined() {
+        let matcher = OrRouteMatcher::new(
+            MethodOnlyRouteMatcher::new(vec![Method::GET, Method::HEAD]),
+            MethodOnlyRouteMatcher::new(vec![Method::HEAD, Method::POST]),
+        );
+        assert_eq!(
+            matcher.methods(),
+            Some(vec![Method::GET, Method::HEAD, Method::POST])
+        );
+
+        let matcher = OrRouteMatcher::new(
+            MethodOnlyRouteMatcher::new(vec![Method::GET]),
+            AcceptHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON]),
+        );
+        assert_eq!(matcher.methods(), None);
+    }
+}
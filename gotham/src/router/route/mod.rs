@@ -11,8 +11,9 @@ use std::marker::PhantomData;
 use std::panic::RefUnwindSafe;
 use std::pin::Pin;
 
-use hyper::{Body, Response, Uri};
+use hyper::{Body, Method, Response, StatusCode, Uri};
 use log::debug;
+use mime::Mime;
 
 use crate::extractor::{self, PathExtractor, QueryStringExtractor};
 use crate::handler::HandlerFuture;
@@ -21,7 +22,7 @@ use crate::router::non_match::RouteNonMatch;
 use crate::router::route::dispatch::Dispatcher;
 use crate::router::route::matcher::RouteMatcher;
 use crate::router::tree::segment::SegmentMapping;
-use crate::state::{request_id, State};
+use crate::state::{request_id, State, StateData};
 
 #[derive(Clone, Copy, PartialEq)]
 /// Indicates whether this `Route` will dispatch the request to an inner `Router` instance. To
@@ -45,7 +46,8 @@ pub enum Delegation {
 /// 2. Determine whether the route's `Delegation` is `Internal` or `External`. If `External`, halt
 ///    processing and dispatch to the inner `Router`;
 /// 3. Run `PathExtractor` and `QueryStringExtractor` logic to popuate `State` with the necessary
-///    request data. If either of these extractors fail, the request is halted here;
+///    request data. If either of these extractors fail, or respond to the request directly via
+///    `StaticResponseExtender::respond`, the request is halted here;
 /// 4. Dispatch the request via `Route::dispatch`.
 ///
 /// `Route` exists as a trait to allow abstraction over the generic types in `RouteImpl`. This
@@ -83,12 +85,119 @@ pub trait Route: RefUnwindSafe {
     /// Dispatches the request to this `Route`, which will execute the pipelines and the handler
     /// assigned to the `Route.
     fn dispatch(&self, state: State) -> Pin<Box<HandlerFuture>>;
+
+    /// The request methods accepted by this `Route`, or `None` if they are not restricted.
+    fn methods(&self) -> Option<Vec<Method>> {
+        None
+    }
+
+    /// The human-readable description attached to this `Route` when it was built, if any.
+    fn description(&self) -> Option<&str> {
+        None
+    }
+
+    /// The status codes and content types of the responses this `Route` was annotated as
+    /// producing when it was built.
+    fn produces(&self) -> &[(StatusCode, Mime)] {
+        &[]
+    }
+}
+
+/// Describes a `Route` which has been added to a `Router`, as returned by `Router::routes`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteInfo {
+    path: String,
+    methods: Option<Vec<Method>>,
+    description: Option<String>,
+    produces: Vec<(StatusCode, Mime)>,
+}
+
+impl RouteInfo {
+    pub(crate) fn new(path: &str, route: &dyn Route<ResBody = Body>) -> RouteInfo {
+        RouteInfo {
+            path: path.to_owned(),
+            methods: route.methods(),
+            description: route.description().map(ToOwned::to_owned),
+            produces: route.produces().to_vec(),
+        }
+    }
+
+    /// The path of the route, as it was defined in the builder (e.g. `/users/:id`).
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The request methods accepted by the route, or `None` if they are not restricted.
+    pub fn methods(&self) -> Option<&[Method]> {
+        self.methods.as_ref().map(Vec::as_slice)
+    }
+
+    /// The description attached to the route via `DefineSingleRoute::describe`, if any.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_ref().map(String::as_str)
+    }
+
+    /// The status codes and content types attached to the route via `DefineSingleRoute::produces`.
+    pub fn produces(&self) -> &[(StatusCode, Mime)] {
+        &self.produces
+    }
+}
+
+/// Explains how a single `Route` was treated when dispatching a request, as returned by
+/// `Router::explain`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteExplanation {
+    route: RouteInfo,
+    outcome: RouteOutcome,
+}
+
+impl RouteExplanation {
+    pub(crate) fn new(route: RouteInfo, outcome: RouteOutcome) -> RouteExplanation {
+        RouteExplanation { route, outcome }
+    }
+
+    /// The `Route` which was considered.
+    pub fn route(&self) -> &RouteInfo {
+        &self.route
+    }
+
+    /// Whether the `Route` accepted the request, and if not, why it was rejected.
+    pub fn outcome(&self) -> RouteOutcome {
+        self.outcome
+    }
+}
+
+/// The reason a `Route` accepted or rejected a request, as reported by `Router::explain`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RouteOutcome {
+    /// The `Route` matched, and the request would be dispatched to it.
+    Accepted,
+
+    /// The `Route` does not accept the request method.
+    MethodMismatch,
+
+    /// The `RouteMatcher` rejected the request for another reason, indicated by the status code.
+    MatcherFailed(StatusCode),
+
+    /// The `Route` matched, but the `PathExtractor` failed so the request would be rejected.
+    PathExtractorFailed,
+
+    /// The `Route` matched, but the `QueryStringExtractor` failed so the request would be
+    /// rejected.
+    QueryStringExtractorFailed,
 }
 
 /// Returned in the `Err` variant from `extract_query_string` or `extract_request_path`, this
 /// signals that the extractor has failed and the request should not proceed.
 pub struct ExtractorFailed;
 
+/// A complete `Response` returned by an extractor from `StaticResponseExtender::respond`. It is
+/// put into `State` before `ExtractorFailed` is returned, and sent by the `Router` in place of the
+/// response built by `extend_response_on_path_error` or `extend_response_on_query_string_error`.
+pub(crate) struct ExtractorResponse(pub(crate) Response<Body>);
+
+impl StateData for ExtractorResponse {}
+
 /// Concrete type for a route in a Gotham web application. Values of this type are created by the
 /// `gotham::router::builder` API and held internally in the `Router` for dispatching requests.
 pub struct RouteImpl<RM, PE, QSE>
@@ -101,6 +210,8 @@ where
     dispatcher: Box<dyn Dispatcher + Send + Sync>,
     _extractors: Extractors<PE, QSE>,
     delegation: Delegation,
+    description: Option<String>,
+    produces: Vec<(StatusCode, Mime)>,
 }
 
 /// Extractors used by `RouteImpl` to acquire request data and change into a type safe form
@@ -132,8 +243,25 @@ where
             dispatcher,
             _extractors,
             delegation,
+            description: None,
+            produces: Vec::new(),
         }
     }
+
+    /// Attaches a human-readable description to the `RouteImpl`, for introspection via
+    /// `Router::routes`.
+    pub fn with_description(self, description: Option<String>) -> Self {
+        RouteImpl {
+            description,
+            ..self
+        }
+    }
+
+    /// Annotates the `RouteImpl` with the responses it produces, for introspection via
+    /// `Router::routes`.
+    pub fn with_produces(self, produces: Vec<(StatusCode, Mime)>) -> Self {
+        RouteImpl { produces, ..self }
+    }
 }
 
 impl<PE, QSE> Extractors<PE, QSE>
@@ -170,13 +298,32 @@ where
         self.dispatcher.dispatch(state)
     }
 
+    fn methods(&self) -> Option<Vec<Method>> {
+        self.matcher.methods()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_ref().map(String::as_str)
+    }
+
+    fn produces(&self) -> &[(StatusCode, Mime)] {
+        &self.produces
+    }
+
     fn extract_request_path<'a>(
         &self,
         state: &mut State,
         params: SegmentMapping<'a>,
     ) -> Result<(), ExtractorFailed> {
         match extractor::internal::from_segment_mapping::<PE>(params) {
-            Ok(val) => Ok(state.put(val)),
+            Ok(val) => match val.respond(state) {
+                Some(res) => {
+                    debug!("[{}] path extractor responded", request_id(&state));
+                    state.put(ExtractorResponse(res));
+                    Err(ExtractorFailed)
+                }
+                None => Ok(state.put(val)),
+            },
             Err(e) => {
                 debug!("[{}] path extractor failed: {}", request_id(&state), e);
                 Err(ExtractorFailed)
@@ -196,7 +343,14 @@ where
         };
 
         match result {
-            Ok(val) => Ok(state.put(val)),
+            Ok(val) => match val.respond(state) {
+                Some(res) => {
+                    debug!("[{}] query string extractor responded", request_id(&state));
+                    state.put(ExtractorResponse(res));
+                    Err(ExtractorFailed)
+                }
+                None => Ok(state.put(val)),
+            },
             Err(e) => {
                 debug!(
                     "[{}] query string extractor failed: {}",
@@ -1,7 +1,7 @@
 //! Defines a hierarchial `Tree` with subtrees of `Node`.
 
 use crate::helpers::http::PercentDecoded;
-use crate::router::route::Route;
+use crate::router::route::{Route, RouteInfo};
 use crate::router::tree::node::Node;
 use crate::router::tree::segment::{SegmentMapping, SegmentType};
 use hyper::Body;
@@ -52,6 +52,29 @@ impl Tree {
         self.root.has_child(segment, segment_type)
     }
 
+    /// Merges the nodes and routes of `other` into this `Tree`.
+    pub(crate) fn merge(&mut self, other: Tree) {
+        self.root.merge(other.root);
+    }
+
+    /// Finds a `Route` of `other` which conflicts with a `Route` of this `Tree`, matching the same
+    /// requests. See `Node::find_conflict` for details.
+    pub(crate) fn find_conflict(&self, other: &Tree) -> Option<RouteInfo> {
+        self.root.find_conflict(&other.root, None)
+    }
+
+    /// Describes every `Route` in the `Tree`, in the order they are considered during traversal.
+    pub(crate) fn route_info(&self) -> Vec<RouteInfo> {
+        let mut routes = Vec::new();
+        self.root.collect_route_info(None, &mut routes);
+        routes
+    }
+
+    /// Determines the path of a `Node` within the `Tree`, as it was defined in the builder.
+    pub(crate) fn node_path(&self, node: &Node) -> Option<String> {
+        self.root.find_route_path(node, None)
+    }
+
     /// Attempt to acquire a path from the `Tree` which matches the `Request` path and is routable.
     pub(crate) fn traverse<'a>(
         &'a self,
@@ -109,10 +132,7 @@ mod tests {
             Some((node, params, processed)) => {
                 assert!(node.is_routable());
                 assert_eq!(processed, 2);
-                assert_eq!(
-                    params.get("thing").unwrap().last().unwrap().as_ref(),
-                    "workflow5"
-                );
+                assert_eq!(params.first("thing").unwrap().as_ref(), "workflow5");
             }
             None => panic!(),
         }
@@ -1,16 +1,16 @@
 //! Defines `Node` for `Tree`.
 
-use hyper::{Body, StatusCode};
+use hyper::{Body, Method, StatusCode};
 use log::trace;
 
 use crate::helpers::http::PercentDecoded;
 use crate::router::non_match::RouteNonMatch;
-use crate::router::route::{Delegation, Route};
+use crate::router::route::{Delegation, Route, RouteExplanation, RouteInfo, RouteOutcome};
 use crate::router::tree::segment::{SegmentMapping, SegmentType};
 use crate::state::{request_id, State};
 
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::ptr;
 
 /// A recursive member of `Tree`, representative of segment(s) in a request path.
 ///
@@ -48,6 +48,22 @@ impl Node {
         self
     }
 
+    /// Merges the routes and children of `other` into this `Node`. Children representing the same
+    /// segment are merged recursively, and the routes of `other` are considered after the existing
+    /// routes of each `Node`.
+    pub(crate) fn merge(&mut self, other: Node) {
+        self.routes.extend(other.routes);
+
+        for child in other.children {
+            match self.borrow_child_mut(&child.segment, child.segment_type.clone()) {
+                Some(existing) => existing.merge(child),
+                None => {
+                    self.add_child(child);
+                }
+            }
+        }
+    }
+
     /// Borrows a child `Node` based on the defined segment bounds.
     pub fn borrow_child(&self, segment: &str, segment_type: SegmentType) -> Option<&Node> {
         self.children
@@ -98,7 +114,7 @@ impl Node {
         segments: &'a [PercentDecoded],
     ) -> Option<(&'a Node, SegmentMapping<'a>, usize)> {
         // accumulators for recursion
-        let mut params = HashMap::new();
+        let mut params = SegmentMapping::new();
         let mut processed = 0;
 
         // process and map the results through to the required form
@@ -113,6 +129,137 @@ impl Node {
         &self.segment
     }
 
+    /// Appends a `RouteInfo` for each `Route` attached to this `Node` and its children, in the
+    /// order they are considered by the `Router`. The root `Node` is indicated by a `parent` of
+    /// `None`.
+    pub(crate) fn collect_route_info(&self, parent: Option<&str>, routes: &mut Vec<RouteInfo>) {
+        let path = self.route_path(parent);
+
+        routes.extend(self.routes.iter().map(|r| RouteInfo::new(&path, &**r)));
+
+        for child in &self.children {
+            child.collect_route_info(Some(&path), routes);
+        }
+    }
+
+    /// Finds a `Route` of `other` which conflicts with a `Route` of this `Node` or its children,
+    /// being attached at a path which matches the same requests and accepting a common request
+    /// method. Paths match the same requests when their segments are of the same kind, regardless
+    /// of the names given to dynamic segments. The root `Node` is indicated by a `parent` of
+    /// `None`.
+    pub(crate) fn find_conflict(&self, other: &Node, parent: Option<&str>) -> Option<RouteInfo> {
+        let path = other.route_path(parent);
+
+        for route in &other.routes {
+            let methods = route.methods();
+            let methods = methods.as_ref().map(Vec::as_slice);
+            let conflicts = self
+                .routes
+                .iter()
+                .any(|r| methods_overlap(r.methods().as_ref().map(Vec::as_slice), methods));
+
+            if conflicts {
+                return Some(RouteInfo::new(&path, &**route));
+            }
+        }
+
+        other.children.iter().find_map(|theirs| {
+            self.children
+                .iter()
+                .filter(|ours| ours.matches_same_requests(theirs))
+                .find_map(|ours| ours.find_conflict(theirs, Some(&path)))
+        })
+    }
+
+    /// Determines whether this `Node` and `other` match the same request path segments, which is
+    /// the case when they are of the same `SegmentType`, and are either dynamic or have the same
+    /// static segment.
+    fn matches_same_requests(&self, other: &Node) -> bool {
+        self.segment_type == other.segment_type
+            && (self.segment_type != SegmentType::Static || self.segment == other.segment)
+    }
+
+    /// Locates `target` within this `Node` and its children, returning its path as it was
+    /// defined in the builder.
+    pub(crate) fn find_route_path(&self, target: &Node, parent: Option<&str>) -> Option<String> {
+        let path = self.route_path(parent);
+
+        if ptr::eq(self, target) {
+            return Some(path);
+        }
+
+        self.children
+            .iter()
+            .find_map(|child| child.find_route_path(target, Some(&path)))
+    }
+
+    /// Evaluates the `Route` instances associated with this `Node` in the same order as
+    /// `select_route`, explaining why each was accepted or rejected. Evaluation stops at the
+    /// first `Route` which matches, as no further routes are considered by the `Router`.
+    pub(crate) fn explain_routes(
+        &self,
+        state: &mut State,
+        params: &SegmentMapping,
+        path: &str,
+    ) -> Vec<RouteExplanation> {
+        let mut explanations = Vec::new();
+
+        for r in self.routes.iter() {
+            let info = RouteInfo::new(path, &**r);
+
+            let outcome = match r.is_match(state) {
+                Err(e) => match StatusCode::from(e) {
+                    StatusCode::METHOD_NOT_ALLOWED => RouteOutcome::MethodMismatch,
+                    status => RouteOutcome::MatcherFailed(status),
+                },
+                Ok(()) if r.delegation() == Delegation::External => RouteOutcome::Accepted,
+                Ok(()) => {
+                    if r.extract_request_path(state, params.clone()).is_err() {
+                        RouteOutcome::PathExtractorFailed
+                    } else if r.extract_query_string(state).is_err() {
+                        RouteOutcome::QueryStringExtractorFailed
+                    } else {
+                        RouteOutcome::Accepted
+                    }
+                }
+            };
+
+            let matched = match outcome {
+                RouteOutcome::MethodMismatch | RouteOutcome::MatcherFailed(_) => false,
+                _ => true,
+            };
+
+            explanations.push(RouteExplanation::new(info, outcome));
+
+            if matched {
+                break;
+            }
+        }
+
+        explanations
+    }
+
+    /// Renders the path of this `Node` as it was defined in the builder, given the path of its
+    /// parent. The root `Node` is indicated by a `parent` of `None`.
+    fn route_path(&self, parent: Option<&str>) -> String {
+        match parent {
+            None => "/".to_owned(),
+            Some(parent) => {
+                let segment = match self.segment_type {
+                    SegmentType::Static | SegmentType::Glob => self.segment.clone(),
+                    SegmentType::Dynamic => format!(":{}", self.segment),
+                    SegmentType::Constrained { ref regex } => {
+                        let pattern = regex.as_str();
+                        let pattern = &pattern[1..pattern.len() - 1];
+                        format!(":{}:{}", self.segment, pattern)
+                    }
+                };
+
+                format!("{}/{}", parent.trim_end_matches('/'), segment)
+            }
+        }
+    }
+
     /// Determines if a `Route` instance associated with this `Node` is willing to `Handle` the
     /// request.
     ///
@@ -203,10 +350,7 @@ impl Node {
                 // Globbing matches everything, so we append the segment value
                 // to the parameters against the child segment name.
                 SegmentType::Glob => {
-                    params
-                        .entry(&child.segment)
-                        .or_insert_with(|| vec![])
-                        .push(&segment);
+                    params.push(&child.segment, &segment);
                 }
 
                 // Static matches based on a raw string match, so we simply
@@ -251,9 +395,7 @@ impl Node {
         // `inner_match_node` on ourself again (to simulate wildcards).
         if let SegmentType::Glob = self.segment_type {
             // push the segment to the parameters of the glob
-            if let Some(path) = params.get_mut(self.segment()) {
-                path.push(&segment);
-            }
+            params.push(self.segment(), &segment);
             // call again, but after shifting the segments to the next
             return self.inner_match_node(remaining, params, processed);
         }
@@ -270,6 +412,14 @@ impl PartialEq for Node {
     }
 }
 
+/// Determines whether two routes accept a common request method, where `None` accepts any method.
+fn methods_overlap(a: Option<&[Method]>, b: Option<&[Method]>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.iter().any(|m| b.contains(m)),
+        _ => true,
+    }
+}
+
 impl Ord for Node {
     /// Compares two `Node` values to determine an appropriate `Ordering`.
     fn cmp(&self, other: &Node) -> Ordering {
@@ -496,6 +646,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn segment_values_are_mapped() {
+        let root = test_structure();
+        let values = |values: &[&PercentDecoded]| {
+            values
+                .iter()
+                .map(|v| v.as_ref().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        // Glob segments hold each path segment they matched, in order.
+        let rs = RequestPathSegments::new("/some/path/seg9/another/branch");
+        let (_node, params, _processed) = root.match_node(&rs.segments()).unwrap();
+        assert_eq!(values(params.all("seg8")), vec!["some", "path"]);
+        assert_eq!(values(params.all("seg10")), vec!["another", "branch"]);
+        assert_eq!(params.first("seg8").unwrap().as_ref(), "some");
+
+        // Dynamic segments hold a single value.
+        let rs = RequestPathSegments::new("/seg5/someval/seg7");
+        let (_node, params, _processed) = root.match_node(&rs.segments()).unwrap();
+        assert_eq!(values(params.all(":segdyn1")), vec!["someval"]);
+        assert_eq!(params.first(":segdyn1").unwrap().as_ref(), "someval");
+
+        assert!(params.all("missing").is_empty());
+        assert!(params.first("missing").is_none());
+    }
+
     #[test]
     fn non_matching_routes_allow_list_tests() {
         let root = test_structure();
@@ -1,11 +1,123 @@
 //! Defines `SegmentType` for `Tree`.
-use std::collections::HashMap;
+use std::collections::hash_map::{self, HashMap};
+use std::ops::Deref;
 
 use crate::helpers::http::PercentDecoded;
 use crate::router::tree::regex::ConstrainedSegmentRegex;
 
+/// A segment of a route path, as it is written in the builder (e.g. `users`, `:id:[0-9]+` or
+/// `*`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum PathSegment<'a> {
+    /// A segment which is matched exactly. A leading `\` escape has been removed.
+    Static(&'a str),
+
+    /// A dynamic segment, holding the name its value is extracted as, and the pattern it is
+    /// constrained by, if any.
+    Dynamic {
+        name: &'a str,
+        constraint: Option<&'a str>,
+    },
+
+    /// A glob segment, whose values are extracted with the name `*`.
+    Glob,
+}
+
+impl<'a> PathSegment<'a> {
+    /// Parses a single segment of a route path.
+    pub(crate) fn parse(segment: &'a str) -> PathSegment<'a> {
+        match segment.chars().next() {
+            Some(':') => {
+                let segment = &segment[1..];
+                match segment.find(':') {
+                    Some(n) => PathSegment::Dynamic {
+                        name: &segment[..n],
+                        constraint: Some(&segment[n + 1..]),
+                    },
+                    None => PathSegment::Dynamic {
+                        name: segment,
+                        constraint: None,
+                    },
+                }
+            }
+            Some('*') if segment.len() == 1 => PathSegment::Glob,
+            Some('\\') => PathSegment::Static(&segment[1..]),
+            _ => PathSegment::Static(segment),
+        }
+    }
+
+    /// The name the value of the segment is extracted as, which is `None` for a static segment.
+    pub(crate) fn name(&self) -> Option<&'a str> {
+        match *self {
+            PathSegment::Static(_) => None,
+            PathSegment::Dynamic { name, .. } => Some(name),
+            PathSegment::Glob => Some("*"),
+        }
+    }
+}
+
 /// Mapping of segment names into the collection of values for that segment.
-pub type SegmentMapping<'r> = HashMap<&'r str, Vec<&'r PercentDecoded>>;
+///
+/// `Dynamic` and `Constrained` segments always hold exactly one value. A `Glob` segment holds one
+/// value for each request path segment it matched, in the order they appear in the path.
+///
+/// `SegmentMapping` dereferences to, and can be created from, the `HashMap` it was previously
+/// defined as an alias of, so existing code continues to work.
+#[derive(Clone, Debug, Default)]
+pub struct SegmentMapping<'r> {
+    values: HashMap<&'r str, Vec<&'r PercentDecoded>>,
+}
+
+impl<'r> SegmentMapping<'r> {
+    pub(crate) fn new() -> SegmentMapping<'r> {
+        SegmentMapping::default()
+    }
+
+    /// Replaces the values held for the segment `name`.
+    pub(crate) fn insert(&mut self, name: &'r str, values: Vec<&'r PercentDecoded>) {
+        self.values.insert(name, values);
+    }
+
+    /// Appends a value to those held for the segment `name`.
+    pub(crate) fn push(&mut self, name: &'r str, value: &'r PercentDecoded) {
+        self.values.entry(name).or_insert_with(Vec::new).push(value);
+    }
+
+    /// Returns the first value of the segment `name`, which is the only value for segments other
+    /// than `Glob`.
+    pub fn first(&self, name: &str) -> Option<&'r PercentDecoded> {
+        self.all(name).first().cloned()
+    }
+
+    /// Returns all values of the segment `name`, which for a `Glob` segment is each path segment
+    /// it matched. The slice is empty if the segment was not matched.
+    pub fn all(&self, name: &str) -> &[&'r PercentDecoded] {
+        self.values.get(name).map_or(&[][..], Vec::as_slice)
+    }
+}
+
+impl<'r> Deref for SegmentMapping<'r> {
+    type Target = HashMap<&'r str, Vec<&'r PercentDecoded>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.values
+    }
+}
+
+impl<'r> From<HashMap<&'r str, Vec<&'r PercentDecoded>>> for SegmentMapping<'r> {
+    fn from(values: HashMap<&'r str, Vec<&'r PercentDecoded>>) -> SegmentMapping<'r> {
+        SegmentMapping { values }
+    }
+}
+
+impl<'r> IntoIterator for SegmentMapping<'r> {
+    type Item = (&'r str, Vec<&'r PercentDecoded>);
+    type IntoIter = hash_map::IntoIter<&'r str, Vec<&'r PercentDecoded>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
 
 /// Indicates the type of segment which is being represented by this Node.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
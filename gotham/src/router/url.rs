@@ -0,0 +1,295 @@
+//! Defines typed URL generation for routes.
+//!
+//! A route is given a name by implementing `NamedRoute` on a marker type, which links the path
+//! the route is drawn with to a struct holding its path parameters. `url_for` then builds the path
+//! for a request to that route from a value of the struct.
+//!
+//! The link between the path and the struct is not checked by the compiler: a struct which lacks a
+//! field for one of the dynamic segments of the path causes `url_for` to return a `UrlError`.
+//!
+//! ```rust
+//! # extern crate gotham;
+//! # #[macro_use]
+//! # extern crate gotham_derive;
+//! # extern crate hyper;
+//! # extern crate serde;
+//! # #[macro_use]
+//! # extern crate serde_derive;
+//! #
+//! # use hyper::{Body, Response, StatusCode};
+//! # use gotham::router::Router;
+//! # use gotham::router::builder::*;
+//! # use gotham::router::url::{url_for, NamedRoute};
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! struct UserDetail;
+//!
+//! #[derive(Deserialize, Serialize, StateData, StaticResponseExtender)]
+//! struct UserDetailParams {
+//!     id: u64,
+//! }
+//!
+//! impl NamedRoute for UserDetail {
+//!     const PATH: &'static str = "/users/:id";
+//!     type Params = UserDetailParams;
+//! }
+//!
+//! # fn handler(state: State) -> (State, Response<Body>) {
+//! #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+//! # }
+//! #
+//! fn router() -> Router {
+//!     build_simple_router(|route| {
+//!         route
+//!             .get(UserDetail::PATH)
+//!             .with_path_extractor::<UserDetailParams>()
+//!             .to(handler);
+//!     })
+//! }
+//!
+//! # fn main() {
+//! let url = url_for::<UserDetail>(UserDetailParams { id: 42 }).unwrap();
+//! assert_eq!(url, "/users/42");
+//! #
+//! # let test_server = TestServer::new(router()).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get(format!("http://example.com{}", url))
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::ACCEPTED);
+//! # }
+//! ```
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::helpers::http::request::path::split_path_segments;
+use crate::router::tree::segment::PathSegment;
+
+/// The characters which are percent encoded when a parameter value is written into a path
+/// segment.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Links a route, identified by the implementing type, to the path it is drawn with and the type
+/// holding its path parameters.
+pub trait NamedRoute {
+    /// The path the route is drawn with, e.g. `/users/:id`.
+    const PATH: &'static str;
+
+    /// The type holding the values of the dynamic segments in `PATH`. Each field is named after
+    /// the segment it fills; a glob segment (`*`) is filled by a field named `*` holding a
+    /// sequence of values.
+    type Params: Serialize;
+}
+
+/// Describes why `url_for` could not build the path of a route, which indicates that the
+/// `NamedRoute::Params` of the route do not match its `NamedRoute::PATH`.
+#[derive(Debug, PartialEq)]
+pub enum UrlError {
+    /// The parameters did not serialize to a map of values.
+    NotAMap,
+
+    /// The parameters held no value for the named dynamic segment.
+    MissingParameter(String),
+
+    /// The value for the named dynamic segment was not a string, number or boolean (or, for a glob
+    /// segment, a sequence of them).
+    InvalidParameter(String),
+}
+
+impl Display for UrlError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            UrlError::NotAMap => write!(f, "path parameters must serialize to a map"),
+            UrlError::MissingParameter(ref name) => {
+                write!(f, "no value for path parameter `{}`", name)
+            }
+            UrlError::InvalidParameter(ref name) => write!(
+                f,
+                "path parameter `{}` must be a string, number or boolean",
+                name
+            ),
+        }
+    }
+}
+
+impl Error for UrlError {}
+
+/// Builds the path of a request to the route `R`, filling its dynamic segments from `params`.
+///
+/// Values are percent encoded where required. Any constraint on a segment (e.g. `:id:[0-9]+`) is
+/// not checked. An error is returned if `params` does not serialize to a map with a value for each
+/// dynamic segment in `R::PATH`.
+pub fn url_for<R>(params: R::Params) -> Result<String, UrlError>
+where
+    R: NamedRoute,
+{
+    let params = match serde_json::to_value(params) {
+        Ok(Value::Object(map)) => map,
+        _ => return Err(UrlError::NotAMap),
+    };
+
+    let mut url = String::new();
+
+    for segment in split_path_segments(R::PATH) {
+        url.push('/');
+
+        match PathSegment::parse(segment) {
+            PathSegment::Static(segment) => url.push_str(segment),
+            segment => {
+                let name = segment.name().unwrap();
+                write_param(&mut url, &params, name)?;
+            }
+        }
+    }
+
+    if url.is_empty() {
+        url.push('/');
+    }
+
+    Ok(url)
+}
+
+fn write_param(url: &mut String, params: &Map<String, Value>, name: &str) -> Result<(), UrlError> {
+    match params.get(name) {
+        Some(Value::Array(values)) if name == "*" => {
+            let segments = values
+                .iter()
+                .map(|value| scalar(value, name))
+                .collect::<Result<Vec<String>, UrlError>>()?;
+            url.push_str(&segments.join("/"));
+        }
+        Some(value) => url.push_str(&scalar(value, name)?),
+        None => return Err(UrlError::MissingParameter(name.to_owned())),
+    }
+
+    Ok(())
+}
+
+fn scalar(value: &Value, name: &str) -> Result<String, UrlError> {
+    match value {
+        Value::String(s) => Ok(utf8_percent_encode(s, SEGMENT).to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(UrlError::InvalidParameter(name.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_derive::Serialize;
+
+    struct Article;
+
+    #[derive(Serialize)]
+    struct ArticleParams {
+        user: String,
+        id: u32,
+    }
+
+    impl NamedRoute for Article {
+        const PATH: &'static str = "/users/:user/articles/:id:[0-9]+";
+        type Params = ArticleParams;
+    }
+
+    struct Asset;
+
+    #[derive(Serialize)]
+    struct AssetParams {
+        #[serde(rename = "*")]
+        path: Vec<String>,
+    }
+
+    impl NamedRoute for Asset {
+        const PATH: &'static str = "/assets/*";
+        type Params = AssetParams;
+    }
+
+    #[test]
+    fn url_for_fills_path_parameters() {
+        let params = ArticleParams {
+            user: "jane doe".to_owned(),
+            id: 7,
+        };
+        assert_eq!(
+            url_for::<Article>(params).unwrap(),
+            "/users/jane%20doe/articles/7"
+        );
+
+        let params = AssetParams {
+            path: vec!["css".to_owned(), "site.css".to_owned()],
+        };
+        assert_eq!(url_for::<Asset>(params).unwrap(), "/assets/css/site.css");
+    }
+
+    #[test]
+    fn url_for_unescapes_static_segments() {
+        struct Tag;
+
+        #[derive(Serialize)]
+        struct TagParams {
+            name: String,
+        }
+
+        impl NamedRoute for Tag {
+            const PATH: &'static str = "/\\:tags/:name";
+            type Params = TagParams;
+        }
+
+        let params = TagParams {
+            name: "rust".to_owned(),
+        };
+        assert_eq!(url_for::<Tag>(params).unwrap(), "/:tags/rust");
+    }
+
+    #[test]
+    fn url_for_rejects_mismatched_parameters() {
+        struct MissingId;
+
+        #[derive(Serialize)]
+        struct MissingIdParams {
+            user: String,
+        }
+
+        impl NamedRoute for MissingId {
+            const PATH: &'static str = "/users/:user/articles/:id";
+            type Params = MissingIdParams;
+        }
+
+        let params = MissingIdParams {
+            user: "jane".to_owned(),
+        };
+        assert_eq!(
+            url_for::<MissingId>(params),
+            Err(UrlError::MissingParameter("id".to_owned()))
+        );
+
+        struct Unit;
+
+        impl NamedRoute for Unit {
+            const PATH: &'static str = "/users/:id";
+            type Params = ();
+        }
+
+        assert_eq!(url_for::<Unit>(()), Err(UrlError::NotAMap));
+    }
+}
@@ -0,0 +1,581 @@
+//! Defines the options used to serve a Gotham application.
+
+use std::net::{self, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use hyper::server::conn::Http;
+use log::{debug, info};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::connection::{ConnectionTimeouts, TimeoutStream};
+use crate::handler::NewHandler;
+use crate::middleware::request_id::RequestIdGenerator;
+use crate::service::{ConnectedGothamService, GothamService};
+use crate::{new_runtime, tcp_listener};
+
+/// The smallest limit which can be applied to the size of a request head, imposed by Hyper.
+const MIN_MAX_HEAD_SIZE: usize = 8 * 1024;
+
+/// Options for serving a Gotham application, which can be combined as required before the server
+/// is started.
+///
+/// By default, the server uses one worker thread per CPU, applies no connection timeouts and runs
+/// until the process exits.
+///
+/// ```rust,no_run
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate tokio;
+/// #
+/// # use std::time::Duration;
+/// # use hyper::{Body, Response};
+/// # use gotham::connection::ConnectionTimeouts;
+/// # use gotham::server::ServerConfig;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, Response<Body>) {
+/// #     (state, Response::new(Body::empty()))
+/// # }
+/// #
+/// # fn main() {
+/// let listener = match gotham::listener::inherited().unwrap() {
+///     Some(listener) => listener,
+///     None => std::net::TcpListener::bind("127.0.0.1:7878").unwrap(),
+/// };
+///
+/// let signal = async {
+///     tokio::signal::ctrl_c().await.unwrap();
+/// };
+///
+/// ServerConfig::default()
+///     .with_timeouts(ConnectionTimeouts::default().with_read_timeout(Duration::from_secs(30)))
+///     .with_graceful_shutdown(signal, Duration::from_secs(30))
+///     .start_with_listener(listener, || Ok(handler));
+/// # }
+/// ```
+pub struct ServerConfig {
+    threads: usize,
+    timeouts: ConnectionTimeouts,
+    max_head_size: Option<usize>,
+    max_header_line_size: Option<usize>,
+    request_id_generator: Option<Arc<dyn RequestIdGenerator>>,
+    shutdown_signal: Option<BoxFuture<'static, ()>>,
+    drain_timeout: Option<Duration>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            threads: num_cpus::get(),
+            timeouts: ConnectionTimeouts::default(),
+            max_head_size: None,
+            max_header_line_size: None,
+            request_id_generator: None,
+            shutdown_signal: None,
+            drain_timeout: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Sets the number of worker threads used by `start` and `start_with_listener`.
+    pub fn with_num_threads(self, threads: usize) -> ServerConfig {
+        ServerConfig { threads, ..self }
+    }
+
+    /// Closes connections which stall for longer than the provided `ConnectionTimeouts`.
+    ///
+    /// The timeouts apply once the connection has been wrapped, i.e. after any TLS handshake.
+    pub fn with_timeouts(self, timeouts: ConnectionTimeouts) -> ServerConfig {
+        ServerConfig { timeouts, ..self }
+    }
+
+    /// Caps the total size of each request head (the request line and every header together) at
+    /// `max_head_size` bytes. A request whose head grows beyond the cap is answered with
+    /// `431 Request Header Fields Too Large` and its connection closed, without buffering the rest
+    /// of the head.
+    ///
+    /// The cap is enforced by Hyper as it parses each HTTP/1 request, so it applies afresh to every
+    /// request on a keep-alive connection, and the rejection is only written once the responses to
+    /// any earlier pipelined requests have been. HTTP/2 connections are unaffected. When no cap is
+    /// set, Hyper's default of roughly 400KiB applies.
+    ///
+    /// # Panics
+    ///
+    /// If `max_head_size` is below 8KiB, the smallest cap supported by Hyper.
+    pub fn with_max_head_size(self, max_head_size: usize) -> ServerConfig {
+        assert!(
+            max_head_size >= MIN_MAX_HEAD_SIZE,
+            "the request head size cannot be capped below {} bytes",
+            MIN_MAX_HEAD_SIZE
+        );
+
+        ServerConfig {
+            max_head_size: Some(max_head_size),
+            ..self
+        }
+    }
+
+    /// Responds with `431 Request Header Fields Too Large` to any request carrying a header line
+    /// (its name, colon, space and value) longer than `max_header_line_size` bytes. The request is
+    /// rejected as soon as its head has been parsed, before it is routed.
+    ///
+    /// The bytes buffered while parsing the head are bounded by the total cap set with
+    /// `with_max_head_size`.
+    pub fn with_max_header_line_size(self, max_header_line_size: usize) -> ServerConfig {
+        ServerConfig {
+            max_header_line_size: Some(max_header_line_size),
+            ..self
+        }
+    }
+
+    /// Generates the ID of each request with `generator`, rather than as a UUID v4. Unlike
+    /// `RequestIdMiddleware`, the generated ID is used from the moment each request is received,
+    /// so it appears in every log line written for the request.
+    ///
+    /// A request ID provided by the client via the `X-Request-ID` header is retained.
+    pub fn with_request_id_generator<G>(self, generator: G) -> ServerConfig
+    where
+        G: RequestIdGenerator + 'static,
+    {
+        ServerConfig {
+            request_id_generator: Some(Arc::new(generator)),
+            ..self
+        }
+    }
+
+    /// Stops accepting connections once `signal` completes, and shuts down gracefully.
+    ///
+    /// Idle connections are closed straight away, while in-flight requests are allowed to
+    /// complete. Connections which are still busy once `drain_timeout` has elapsed are closed
+    /// without waiting for their requests to complete. The server stops once every connection has
+    /// been closed.
+    pub fn with_graceful_shutdown<S>(self, signal: S, drain_timeout: Duration) -> ServerConfig
+    where
+        S: Future<Output = ()> + Send + 'static,
+    {
+        ServerConfig {
+            shutdown_signal: Some(signal.boxed()),
+            drain_timeout: Some(drain_timeout),
+            ..self
+        }
+    }
+
+    /// Starts a Gotham application on plain, unsecured HTTP.
+    pub fn start<NH, A>(self, addr: A, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+        A: ToSocketAddrs + 'static + Send,
+    {
+        let mut runtime = new_runtime(self.threads);
+        let _ = runtime.block_on(self.init_server(addr, new_handler));
+    }
+
+    /// Starts a Gotham application on a listening socket which has already been bound, such as
+    /// one inherited from another process via `gotham::listener::inherited`.
+    pub fn start_with_listener<NH>(self, listener: net::TcpListener, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+    {
+        let mut runtime = new_runtime(self.threads);
+        let _ = runtime.block_on(self.init_server_with_listener(listener, new_handler));
+    }
+
+    /// Returns a `Future` used to spawn a Gotham application.
+    ///
+    /// See `gotham::init_server` for details. The number of worker threads is not applied, as the
+    /// `Future` is run on the caller's runtime.
+    pub async fn init_server<NH, A>(self, addr: A, new_handler: NH) -> Result<(), ()>
+    where
+        NH: NewHandler + 'static,
+        A: ToSocketAddrs + 'static + Send,
+    {
+        let listener = tcp_listener(addr).map_err(|_| ()).await?;
+        self.serve(listener, new_handler).await
+    }
+
+    /// Returns a `Future` used to spawn a Gotham application on a listening socket which has
+    /// already been bound.
+    ///
+    /// See `init_server` for details.
+    pub async fn init_server_with_listener<NH>(
+        self,
+        listener: net::TcpListener,
+        new_handler: NH,
+    ) -> Result<(), ()>
+    where
+        NH: NewHandler + 'static,
+    {
+        listener.set_nonblocking(true).map_err(|_| ())?;
+        let listener = TcpListener::from_std(listener).map_err(|_| ())?;
+        self.serve(listener, new_handler).await
+    }
+
+    /// Returns a `Future` used to spawn a Gotham application, wrapping each accepted connection
+    /// with `wrap`.
+    ///
+    /// See `gotham::bind_server` for details.
+    pub async fn bind_server<NH, F, Wrapped, Wrap>(
+        self,
+        listener: TcpListener,
+        new_handler: NH,
+        wrap: Wrap,
+    ) -> Result<(), ()>
+    where
+        NH: NewHandler + 'static,
+        F: Future<Output = Result<Wrapped, ()>> + Unpin + Send + 'static,
+        Wrapped: Unpin + AsyncRead + AsyncWrite + Send + 'static,
+        Wrap: Fn(TcpStream) -> F,
+    {
+        bind_server_with(listener, new_handler, wrap, |_, service| service, self).await
+    }
+
+    async fn serve<NH>(self, listener: TcpListener, new_handler: NH) -> Result<(), ()>
+    where
+        NH: NewHandler + 'static,
+    {
+        let addr = listener.local_addr().unwrap();
+        info!(target: "gotham::start", " Gotham listening on http://{}", addr);
+
+        self.bind_server(listener, new_handler, future::ok).await
+    }
+}
+
+/// Same as `ServerConfig::bind_server`, but allows the connected service to be adjusted based on
+/// the wrapped socket (e.g. to record details of the TLS session).
+pub(crate) async fn bind_server_with<NH, F, Wrapped, Wrap, Connect>(
+    mut listener: TcpListener,
+    new_handler: NH,
+    wrap: Wrap,
+    connect: Connect,
+    config: ServerConfig,
+) -> Result<(), ()>
+where
+    NH: NewHandler + 'static,
+    F: Future<Output = Result<Wrapped, ()>> + Unpin + Send + 'static,
+    Wrapped: Unpin + AsyncRead + AsyncWrite + Send + 'static,
+    Wrap: Fn(TcpStream) -> F,
+    Connect: Fn(&Wrapped, ConnectedGothamService<NH>) -> ConnectedGothamService<NH>,
+{
+    let ServerConfig {
+        timeouts,
+        max_head_size,
+        max_header_line_size,
+        request_id_generator,
+        shutdown_signal,
+        drain_timeout,
+        ..
+    } = config;
+
+    let mut protocol = Http::new();
+    if let Some(max_head_size) = max_head_size {
+        protocol.max_buf_size(max_head_size);
+    }
+
+    let protocol = Arc::new(protocol);
+    let gotham_service = GothamService::new(new_handler);
+    let connect = &connect;
+    let signal = shutdown_signal
+        .unwrap_or_else(|| future::pending().boxed())
+        .shared();
+
+    // Accepts connections until the signal completes.
+    let incoming = stream::unfold(
+        (listener.incoming(), signal.clone()),
+        |(mut incoming, signal)| async move {
+            match future::select(incoming.next(), signal).await {
+                future::Either::Left((Some(socket), signal)) => Some((socket, (incoming, signal))),
+                _ => None,
+            }
+        },
+    );
+
+    incoming
+        .map_err(|e| panic!("socket error = {:?}", e))
+        .try_for_each_concurrent(None, |socket| {
+            let addr = socket.peer_addr().unwrap();
+            let mut service = gotham_service.connect(addr);
+            if let Some(ref generator) = request_id_generator {
+                service = service.with_request_id_generator(generator.clone());
+            }
+            if let Some(limit) = max_header_line_size {
+                service = service.with_max_header_line_size(limit);
+            }
+            let accepted_protocol = protocol.clone();
+            let wrapper = wrap(socket);
+            let signal = signal.clone();
+
+            async move {
+                // NOTE: HTTP protocol errors and handshake errors are ignored here (i.e. so the
+                // socket will be dropped).
+                let socket = wrapper.await?;
+                let service = connect(&socket, service);
+                let socket = TimeoutStream::new(socket, timeouts);
+                let connection = accepted_protocol.serve_connection(socket, service);
+                futures::pin_mut!(connection);
+
+                let mut connection = match future::select(connection, signal).await {
+                    future::Either::Left((result, _)) => return result.map_err(|_| ()),
+                    future::Either::Right((_, connection)) => connection,
+                };
+
+                connection.as_mut().graceful_shutdown();
+
+                match drain_timeout {
+                    Some(timeout) => match tokio::time::timeout(timeout, connection).await {
+                        Ok(result) => result.map_err(|_| ()),
+                        Err(_) => {
+                            debug!(" closing connection from {} after drain timeout", addr);
+                            Ok(())
+                        }
+                    },
+                    None => connection.await.map_err(|_| ()),
+                }
+            }
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::Instant;
+
+    use futures::channel::oneshot;
+    use hyper::{Body, Method, Response, StatusCode};
+    use tokio::time::delay_for;
+
+    use crate::handler::HandlerError;
+    use crate::helpers::http::response::create_response;
+    use crate::router::builder::*;
+    use crate::state::{request_id, State};
+
+    async fn respond_after(
+        state: State,
+        delay: Duration,
+    ) -> Result<(State, Response<Body>), (State, HandlerError)> {
+        delay_for(delay).await;
+        let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "done");
+        Ok((state, res))
+    }
+
+    /// Sends a request on a new connection, returning everything read until it is closed.
+    fn get(addr: net::SocketAddr, path: &str) -> thread::JoinHandle<String> {
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        );
+        let mut stream = TcpStream::connect(addr).unwrap();
+
+        thread::spawn(move || {
+            stream.write_all(request.as_bytes()).unwrap();
+
+            let mut response = String::new();
+            let _ = stream.read_to_string(&mut response);
+            response
+        })
+    }
+
+    #[test]
+    fn drain_timeout_closes_busy_connections() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/quick")
+                .to(|state: State| respond_after(state, Duration::from_millis(100)).boxed());
+            route
+                .get("/slow")
+                .to(|state: State| respond_after(state, Duration::from_secs(30)).boxed());
+        });
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let server = runtime.spawn(
+            ServerConfig::default()
+                .with_graceful_shutdown(signal.map(|_| ()), Duration::from_millis(500))
+                .bind_server(listener, router, future::ok),
+        );
+
+        let slow = get(addr, "/slow");
+        let quick = get(addr, "/quick");
+        thread::sleep(Duration::from_millis(50));
+
+        // both requests are in flight when the signal is sent
+        let started = Instant::now();
+        shutdown.send(()).unwrap();
+        assert!(runtime.block_on(server).unwrap().is_ok());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let quick = quick.join().unwrap();
+        assert!(quick.starts_with("HTTP/1.1 200 OK"));
+        assert!(quick.ends_with("done"));
+
+        // the slow request was closed without a response
+        assert_eq!(slow.join().unwrap(), "");
+    }
+
+    #[test]
+    fn options_can_be_combined() {
+        let router = build_simple_router(|route| {
+            route.get("/").to(|state: State| {
+                let id = request_id(&state).to_owned();
+                let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, id);
+                (state, res)
+            });
+        });
+
+        // stands in for a socket inherited from another process
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let timeouts = ConnectionTimeouts::default().with_read_timeout(Duration::from_millis(200));
+
+        let server = runtime.spawn(
+            ServerConfig::default()
+                .with_timeouts(timeouts)
+                .with_request_id_generator(|| "fixed-id".to_owned())
+                .with_graceful_shutdown(signal.map(|_| ()), Duration::from_secs(1))
+                .init_server_with_listener(listener, router),
+        );
+
+        let response = get(addr, "/").join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("fixed-id"));
+
+        // a connection which never sends a request is closed by the read timeout
+        let started = Instant::now();
+        let mut idle = TcpStream::connect(addr).unwrap();
+        let _ = idle.read_to_end(&mut Vec::new());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        shutdown.send(()).unwrap();
+        assert!(runtime.block_on(server).unwrap().is_ok());
+    }
+
+    /// Starts a server limiting request heads to 16KiB and header lines to 4KiB, returning its
+    /// address.
+    fn serve_with_header_limits(runtime: &mut tokio::runtime::Runtime) -> net::SocketAddr {
+        let router = build_simple_router(|route| {
+            route
+                .request(vec![Method::GET, Method::POST], "/")
+                .to(|state: State| {
+                    let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "ok");
+                    (state, res)
+                });
+        });
+
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        runtime.spawn(
+            ServerConfig::default()
+                .with_max_head_size(16 * 1024)
+                .with_max_header_line_size(4 * 1024)
+                .bind_server(listener, router, future::ok),
+        );
+
+        addr
+    }
+
+    #[test]
+    fn oversized_header_is_rejected() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let addr = serve_with_header_limits(&mut runtime);
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+
+        // the header value is never completed, so the server must respond before buffering
+        // the whole of it
+        let writer = thread::spawn({
+            let mut stream = stream.try_clone().unwrap();
+            move || {
+                let head = "GET / HTTP/1.1\r\nHost: localhost\r\nX-Gigantic: ";
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(&vec![b'a'; 64 * 1024]);
+            }
+        });
+
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        while let Ok(read) = stream.read(&mut buf) {
+            if read == 0 {
+                break;
+            }
+            response.extend_from_slice(&buf[..read]);
+        }
+        writer.join().unwrap();
+
+        let response = String::from_utf8_lossy(&response);
+        assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+    }
+
+    #[test]
+    fn long_header_line_is_rejected() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let addr = serve_with_header_limits(&mut runtime);
+
+        // the head is within the total cap, but one of its lines is not
+        let request = format!(
+            "GET / HTTP/1.1\r\nHost: localhost\r\nX-Long: {}\r\nConnection: close\r\n\r\n",
+            "a".repeat(5 * 1024)
+        );
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be capped below 8192 bytes")]
+    fn head_size_cannot_be_capped_below_minimum() {
+        let _ = ServerConfig::default().with_max_head_size(4 * 1024);
+    }
+
+    #[test]
+    fn limit_applies_to_each_request() {
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let addr = serve_with_header_limits(&mut runtime);
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+
+        // together the requests exceed the limits, but each head and line is within them
+        let body = "b".repeat(32 * 1024);
+        let value = "a".repeat(3 * 1024);
+        let headers = format!("X-A: {0}\r\nX-B: {0}\r\nX-C: {0}\r\n", value);
+        let requests = format!(
+            "POST / HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}\
+             GET / HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+            headers,
+            body.len(),
+            body,
+            headers
+        );
+
+        let writer = thread::spawn({
+            let mut stream = stream.try_clone().unwrap();
+            move || stream.write_all(requests.as_bytes()).unwrap()
+        });
+
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).unwrap();
+        writer.join().unwrap();
+
+        assert_eq!(responses.matches("HTTP/1.1 200 OK\r\n").count(), 2);
+    }
+}
@@ -12,15 +12,20 @@ use failure;
 use futures::prelude::*;
 use futures::task::{self, Poll};
 use http::request;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONNECTION, HOST};
 use hyper::service::Service;
-use hyper::{Body, Request, Response};
+use hyper::{Body, Method, Request, Response, StatusCode, Uri};
 use log::debug;
 
 use crate::handler::NewHandler;
 
 use crate::helpers::http::request::path::RequestPathSegments;
+use crate::middleware::request_id::RequestIdGenerator;
 use crate::state::client_addr::put_client_addr;
-use crate::state::{set_request_id, State};
+use crate::state::request_start::put_request_start;
+use crate::state::{put_cancellation_token, set_request_id_with, ResponseBody, State};
+#[cfg(feature = "rustls")]
+use crate::tls::client_certificate::{put_client_certificate, ClientCertificate};
 
 mod trap;
 
@@ -47,6 +52,10 @@ where
         ConnectedGothamService {
             client_addr,
             handler: self.handler.clone(),
+            request_id_generator: None,
+            max_header_line_size: None,
+            #[cfg(feature = "rustls")]
+            client_certificate: None,
         }
     }
 }
@@ -59,13 +68,50 @@ where
 {
     handler: Arc<T>,
     client_addr: SocketAddr,
+    request_id_generator: Option<Arc<dyn RequestIdGenerator>>,
+    max_header_line_size: Option<usize>,
+    #[cfg(feature = "rustls")]
+    client_certificate: Option<ClientCertificate>,
+}
+
+impl<T> ConnectedGothamService<T>
+where
+    T: NewHandler + 'static,
+{
+    /// Generates the ID of each request on the connection with `generator`, rather than as a
+    /// UUID v4.
+    pub(crate) fn with_request_id_generator(self, generator: Arc<dyn RequestIdGenerator>) -> Self {
+        ConnectedGothamService {
+            request_id_generator: Some(generator),
+            ..self
+        }
+    }
+
+    /// Rejects requests carrying a header line longer than `limit` bytes with a `431 Request
+    /// Header Fields Too Large` response.
+    pub(crate) fn with_max_header_line_size(self, limit: usize) -> Self {
+        ConnectedGothamService {
+            max_header_line_size: Some(limit),
+            ..self
+        }
+    }
+
+    /// Records the certificate presented by the client during the TLS handshake, to be stored in
+    /// `State` for each request on the connection.
+    #[cfg(feature = "rustls")]
+    pub(crate) fn with_client_certificate(self, cert: ClientCertificate) -> Self {
+        ConnectedGothamService {
+            client_certificate: Some(cert),
+            ..self
+        }
+    }
 }
 
 impl<T> Service<Request<Body>> for ConnectedGothamService<T>
 where
     T: NewHandler,
 {
-    type Response = Response<Body>;
+    type Response = Response<ResponseBody>;
     type Error = failure::Compat<failure::Error>; // :Into<Box<StdError + Send + Sync>>
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
 
@@ -77,22 +123,47 @@ where
     }
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
+        if let Some(limit) = self.max_header_line_size {
+            if let Some(name) = find_long_header_line(req.headers(), limit) {
+                debug!(
+                    " header line {} longer than {} bytes, responding with 431",
+                    name.as_str(),
+                    limit
+                );
+                return future::ok(header_line_too_long().map(ResponseBody::from)).boxed();
+            }
+        }
+
         let mut state = State::new();
 
+        put_request_start(&mut state);
         put_client_addr(&mut state, self.client_addr);
+        let cancel_on_drop = put_cancellation_token(&mut state);
+
+        #[cfg(feature = "rustls")]
+        {
+            if let Some(ref cert) = self.client_certificate {
+                put_client_certificate(&mut state, cert.clone());
+            }
+        }
 
         let (
             request::Parts {
                 method,
                 uri,
                 version,
-                headers,
+                mut headers,
                 //extensions?
                 ..
             },
             body,
         ) = req.into_parts();
 
+        apply_absolute_form_host(&uri, &mut headers);
+
+        // hyper never sends the body of a response to a `HEAD` request
+        let head = method == Method::HEAD;
+
         state.put(RequestPathSegments::new(uri.path()));
         state.put(method);
         state.put(uri);
@@ -101,7 +172,8 @@ where
         state.put(body);
 
         {
-            let request_id = set_request_id(&mut state);
+            let generator = self.request_id_generator.as_ref().map(|g| &**g);
+            let request_id = set_request_id_with(&mut state, generator);
             debug!(
                 "[DEBUG][{}][Thread][{:?}]",
                 request_id,
@@ -109,7 +181,52 @@ where
             );
         };
 
+        // If the connection is dropped before the response is complete, this future or the
+        // response body is dropped with it, and the guard cancels the request's
+        // `CancellationToken`.
         trap::call_handler(&*self.handler, AssertUnwindSafe(state))
+            .map(move |result| match result {
+                Ok(response) if !head => Ok(cancel_on_drop.complete_after(response)),
+                result => {
+                    cancel_on_drop.complete();
+                    result.map(|response| response.map(ResponseBody::from))
+                }
+            })
+            .boxed()
+    }
+}
+
+/// Finds a header whose line, as sent by the client (`name: value`), is longer than `limit` bytes.
+fn find_long_header_line(headers: &HeaderMap, limit: usize) -> Option<&HeaderName> {
+    headers
+        .iter()
+        .find(|(name, value)| name.as_str().len() + 2 + value.len() > limit)
+        .map(|(name, _)| name)
+}
+
+/// The response sent in place of dispatching a request with an oversized header line. The
+/// connection is closed, as the client is likely to repeat the header on any further request.
+fn header_line_too_long() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE)
+        .header(CONNECTION, "close")
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Replaces the `Host` header with the host of an absolute-form request-target, such as
+/// `GET http://example.com/path HTTP/1.1` sent by a client which expects a proxy. The path of such
+/// a request is routed as normal; RFC 7230, section 5.4 requires that any `Host` header it carries
+/// is ignored in favour of the request-target.
+fn apply_absolute_form_host(uri: &Uri, headers: &mut HeaderMap) {
+    let host = match (uri.host(), uri.port_u16()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_owned(),
+        (None, _) => return,
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&host) {
+        headers.insert(HOST, value);
     }
 }
 
@@ -159,4 +276,199 @@ mod tests {
         let response = futures::executor::block_on(f).unwrap();
         assert_eq!(response.status(), StatusCode::ACCEPTED);
     }
+
+    #[test]
+    fn absolute_form_requests_are_routed_by_path() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+
+        use crate::helpers::http::response::create_response;
+        use crate::server::ServerConfig;
+        use crate::state::FromState;
+
+        fn echo_host(state: State) -> (State, Response<Body>) {
+            let host = HeaderMap::borrow_from(&state)[HOST]
+                .to_str()
+                .unwrap()
+                .to_owned();
+            let res = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, host);
+            (state, res)
+        }
+
+        let router = build_simple_router(|route| {
+            route.get("/users/list").to(echo_host);
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.spawn(ServerConfig::default().init_server_with_listener(listener, router));
+
+        let send = |request: &[u8]| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request).unwrap();
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let response = send(
+            b"GET http://example.com:8080/users/list HTTP/1.1\r\n\
+              Host: proxy.local\r\nConnection: close\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("\r\n\r\nexample.com:8080"));
+
+        let response =
+            send(b"GET /users/list HTTP/1.1\r\nHost: origin.local\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("\r\n\r\norigin.local"));
+    }
+
+    #[test]
+    fn request_id_generator() {
+        use crate::state::request_id;
+
+        fn echo_request_id(state: State) -> (State, Response<Body>) {
+            let res = Response::new(Body::from(request_id(&state).to_owned()));
+            (state, res)
+        }
+
+        let generator = Arc::new(|| "node-a-1".to_owned());
+        let mut service = GothamService::new(|| Ok(echo_request_id))
+            .connect("127.0.0.1:10000".parse().unwrap())
+            .with_request_id_generator(generator);
+
+        let req = Request::get("http://localhost/")
+            .body(Body::empty())
+            .unwrap();
+        let res = futures::executor::block_on(service.call(req)).unwrap();
+        let body = futures::executor::block_on(hyper::body::to_bytes(res.into_body())).unwrap();
+        assert_eq!(&body[..], b"node-a-1");
+    }
+
+    #[test]
+    fn client_disconnect_cancels_request() {
+        use std::io::Write;
+        use std::net::{Shutdown, TcpListener, TcpStream};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::{Duration, Instant};
+
+        use crate::handler::HandlerFuture;
+        use crate::server::ServerConfig;
+        use crate::state::{CancellationToken, FromState};
+
+        static STARTED: AtomicBool = AtomicBool::new(false);
+        static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+        fn slow(state: State) -> Pin<Box<HandlerFuture>> {
+            let token = CancellationToken::borrow_from(&state).clone();
+
+            // a worker job which stops once the client goes away
+            tokio::spawn(async move {
+                token.cancelled().await;
+                CANCELLED.store(true, Ordering::SeqCst);
+            });
+            STARTED.store(true, Ordering::SeqCst);
+
+            async move {
+                tokio::time::delay_for(Duration::from_secs(30)).await;
+                let res = create_empty_response(&state, StatusCode::OK);
+                Ok((state, res))
+            }
+            .boxed()
+        }
+
+        let wait_for = |flag: &AtomicBool| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !flag.load(Ordering::SeqCst) && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+            flag.load(Ordering::SeqCst)
+        };
+
+        let router = build_simple_router(|route| {
+            route.get("/slow").to(slow);
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.spawn(ServerConfig::default().init_server_with_listener(listener, router));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        assert!(wait_for(&STARTED));
+        assert!(!CANCELLED.load(Ordering::SeqCst));
+
+        stream.shutdown(Shutdown::Both).unwrap();
+        drop(stream);
+
+        assert!(wait_for(&CANCELLED));
+    }
+
+    #[test]
+    fn client_disconnect_during_body_cancels_request() {
+        use std::io::{Read, Write};
+        use std::net::{Shutdown, TcpListener, TcpStream};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::{Duration, Instant};
+
+        use crate::server::ServerConfig;
+        use crate::state::{CancellationToken, FromState};
+
+        static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+        fn streaming(state: State) -> (State, Response<Body>) {
+            let token = CancellationToken::borrow_from(&state).clone();
+            tokio::spawn(async move {
+                token.cancelled().await;
+                CANCELLED.store(true, Ordering::SeqCst);
+            });
+
+            // the first chunk is sent, but the body never ends
+            let chunks = stream::once(future::ok::<_, std::io::Error>("first chunk"))
+                .chain(stream::pending());
+            (state, Response::new(Body::wrap_stream(chunks)))
+        }
+
+        let router = build_simple_router(|route| {
+            route.get("/stream").to(streaming);
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.spawn(ServerConfig::default().init_server_with_listener(listener, router));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /stream HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        let mut received = Vec::new();
+        let mut buf = [0; 1024];
+        while !String::from_utf8_lossy(&received).contains("first chunk") {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "connection closed before the first chunk");
+            received.extend_from_slice(&buf[..n]);
+        }
+        assert!(!CANCELLED.load(Ordering::SeqCst));
+
+        stream.shutdown(Shutdown::Both).unwrap();
+        drop(stream);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !CANCELLED.load(Ordering::SeqCst) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(CANCELLED.load(Ordering::SeqCst));
+    }
 }
@@ -0,0 +1,336 @@
+//! Defines a token which is cancelled when the client of a request disconnects
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use hyper::body::{Bytes, HttpBody, SizeHint};
+use hyper::header::HeaderMap;
+use hyper::{Body, Response, StatusCode};
+
+use crate::state::{State, StateData};
+
+/// A token which is cancelled when the connection a request was received on is dropped before
+/// the response is complete, e.g. because the client disconnected. It is stored in `State` before
+/// the request is dispatched to the `Handler`.
+///
+/// The response is complete once its body has been sent in full, so the token is cancelled if the
+/// client disconnects while the handler is running, or while a streamed body is being produced.
+/// Once the response is complete the token is never cancelled, so it cannot be used to stop work
+/// which continues after the response has been sent.
+///
+/// When a connection is dropped, the future handling the request is dropped with it, so the token
+/// is most useful to work which runs alongside that future: a spawned task or a blocking job
+/// producing the response can hold a clone of the token, and check or await it to stop early.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate tokio;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::state::{CancellationToken, FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn my_handler(state: State) -> (State, Response<Body>) {
+///     let token = CancellationToken::borrow_from(&state).clone();
+///     let (mut sender, body) = Body::channel();
+///
+///     tokio::spawn(async move {
+///         for row in 0..3 {
+///             if token.is_cancelled() {
+///                 // the client has gone away; stop early
+///                 return;
+///             }
+///
+///             // ... expensive work to produce the row ...
+///             let chunk = format!("row {}\n", row);
+///             if sender.send_data(chunk.into()).await.is_err() {
+///                 return;
+///             }
+///         }
+///     });
+///
+///     (state, Response::new(body))
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(my_handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/")
+/// #       .perform()
+/// #       .unwrap();
+/// #
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "row 0\nrow 1\nrow 2\n");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl StateData for CancellationToken {}
+
+impl CancellationToken {
+    /// Creates a token which has not been cancelled.
+    pub(crate) fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Whether the request has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns a future which completes once the request has been cancelled.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            token: self.clone(),
+        }
+    }
+
+    /// Cancels the request, waking any tasks awaiting `cancelled`.
+    pub(crate) fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        let wakers = {
+            let mut wakers = self
+                .inner
+                .wakers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            std::mem::replace(&mut *wakers, Vec::new())
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// The future returned by `CancellationToken::cancelled`.
+#[derive(Debug)]
+pub struct Cancelled {
+    token: CancellationToken,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let mut wakers = self
+            .token
+            .inner
+            .wakers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        // checked again while holding the lock, so that a concurrent `cancel` is not missed
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Cancels a `CancellationToken` when dropped, unless the request completed first.
+pub(crate) struct CancelOnDrop {
+    token: Option<CancellationToken>,
+}
+
+impl CancelOnDrop {
+    pub(crate) fn new(token: CancellationToken) -> CancelOnDrop {
+        CancelOnDrop { token: Some(token) }
+    }
+
+    /// Marks the request as complete, so that the token is not cancelled.
+    pub(crate) fn complete(mut self) {
+        self.token = None;
+    }
+
+    /// Marks the request as complete once the body of `response` has been sent in full, so that
+    /// the token is cancelled if the body is dropped before then.
+    ///
+    /// Bodies which are already complete, or which are never sent (e.g. for a `204 No Content`
+    /// response), complete the request immediately.
+    pub(crate) fn complete_after(self, response: Response<Body>) -> Response<ResponseBody> {
+        let status = response.status();
+        if response.body().is_end_stream()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+            || status.is_informational()
+        {
+            self.complete();
+            return response.map(ResponseBody::from);
+        }
+
+        response.map(|body| ResponseBody {
+            body,
+            guard: Some(self),
+        })
+    }
+}
+
+/// The body of a response sent by `GothamService`, which holds the request's `CancelOnDrop`
+/// guard until the body has been sent in full. If the body is dropped before then (e.g. because
+/// the client disconnected), the guard cancels the request's `CancellationToken`.
+///
+/// The size hint of the wrapped body is passed on, so Hyper sends the same `Content-Length` as it
+/// would for the wrapped body alone.
+pub(crate) struct ResponseBody {
+    body: Body,
+    guard: Option<CancelOnDrop>,
+}
+
+impl ResponseBody {
+    fn complete(&mut self) {
+        if let Some(guard) = self.guard.take() {
+            guard.complete();
+        }
+    }
+}
+
+impl From<Body> for ResponseBody {
+    fn from(body: Body) -> ResponseBody {
+        ResponseBody { body, guard: None }
+    }
+}
+
+impl HttpBody for ResponseBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, hyper::Error>>> {
+        let next = Pin::new(&mut self.body).poll_data(cx);
+
+        // Hyper stops polling once `is_end_stream` is true, so the last chunk may be the final poll
+        match next {
+            Poll::Ready(None) => self.complete(),
+            Poll::Ready(Some(Ok(_))) if self.body.is_end_stream() => self.complete(),
+            _ => (),
+        }
+
+        next
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, hyper::Error>> {
+        let trailers = Pin::new(&mut self.body).poll_trailers(cx);
+
+        if let Poll::Ready(Ok(_)) = trailers {
+            self.complete();
+        }
+
+        trailers
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(token) = self.token.take() {
+            token.cancel();
+        }
+    }
+}
+
+/// Stores a new `CancellationToken` in `State`, returning a guard which cancels it if dropped
+/// before the request completes.
+pub(crate) fn put_cancellation_token(state: &mut State) -> CancelOnDrop {
+    let token = CancellationToken::new();
+    state.put(token.clone());
+    CancelOnDrop::new(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+    use futures::future::{self, Either};
+
+    #[test]
+    fn guard_cancels_unless_complete() {
+        let token = CancellationToken::new();
+        CancelOnDrop::new(token.clone()).complete();
+        assert!(!token.is_cancelled());
+
+        drop(CancelOnDrop::new(token.clone()));
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn guard_completes_at_end_of_body() {
+        let token = CancellationToken::new();
+        let response =
+            CancelOnDrop::new(token.clone()).complete_after(Response::new(Body::empty()));
+        drop(response);
+        assert!(!token.is_cancelled());
+
+        let token = CancellationToken::new();
+        let response = Response::new(Body::from("streamed"));
+        let response = CancelOnDrop::new(token.clone()).complete_after(response);
+        assert_eq!(response.body().size_hint().exact(), Some(8));
+
+        let body = block_on(hyper::body::to_bytes(response.into_body())).unwrap();
+        assert_eq!(body, "streamed");
+        assert!(!token.is_cancelled());
+
+        let token = CancellationToken::new();
+        let response = Response::new(Body::from("abandoned"));
+        let response = CancelOnDrop::new(token.clone()).complete_after(response);
+        drop(response);
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn cancelled_wakes_waiting_tasks() {
+        let token = CancellationToken::new();
+        let waiting = token.cancelled();
+
+        let cancel = async {
+            token.cancel();
+        };
+
+        block_on(future::join(waiting, cancel));
+        assert!(token.is_cancelled());
+
+        match block_on(future::select(token.cancelled(), future::pending::<()>())) {
+            Either::Left(_) => (),
+            Either::Right(_) => panic!("token should already be cancelled"),
+        }
+    }
+}
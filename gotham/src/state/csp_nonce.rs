@@ -0,0 +1,77 @@
+//! Defines storage for the nonce used in the `Content-Security-Policy` of a response
+
+use crate::state::{FromState, State, StateData};
+
+/// A random nonce generated for each request, stored in `State` before the request is dispatched
+/// to the `Handler` when the router has been configured via `RouterBuilder::add_csp_nonce`.
+///
+/// Templates can add the nonce to inline `<script>` and `<style>` elements, and the router adds
+/// a matching `Content-Security-Policy` header to `text/html` responses.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// # extern crate mime;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::state::{CspNonce, FromState, State};
+/// #
+/// fn my_handler(state: State) -> (State, Response<Body>) {
+///     let body = format!(
+///         "<script nonce=\"{}\">alert('Hello!');</script>",
+///         CspNonce::borrow_from(&state).value()
+///     );
+///
+///     let response = create_response(&state, StatusCode::OK, mime::TEXT_HTML, body);
+///     (state, response)
+/// }
+/// #
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct CspNonce {
+    value: String,
+}
+
+impl StateData for CspNonce {}
+
+impl CspNonce {
+    /// The base64 encoded nonce.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Generates a new nonce and stores it in `State`, unless one is already present (e.g. where the
+/// request has been delegated from another `Router`).
+pub(crate) fn put_csp_nonce(state: &mut State) {
+    if CspNonce::try_borrow_from(state).is_none() {
+        let bytes: [u8; 16] = rand::random();
+        state.put(CspNonce {
+            value: base64::encode(&bytes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonce_is_generated_once_per_request() {
+        let mut state = State::new();
+        put_csp_nonce(&mut state);
+        let nonce = CspNonce::borrow_from(&state).clone();
+        assert_eq!(base64::decode(nonce.value()).unwrap().len(), 16);
+
+        put_csp_nonce(&mut state);
+        assert_eq!(CspNonce::borrow_from(&state), &nonce);
+
+        let mut other = State::new();
+        put_csp_nonce(&mut other);
+        assert_ne!(CspNonce::borrow_from(&other), &nonce);
+    }
+}
@@ -1,21 +1,30 @@
 //! Defines types for passing request state through `Middleware` and `Handler` implementations
 
+mod cancellation;
 pub(crate) mod client_addr;
+pub(crate) mod csp_nonce;
 mod data;
 mod from_state;
 pub mod request_id;
+pub(crate) mod request_start;
 
 use log::trace;
 
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
 
+pub use crate::state::cancellation::{CancellationToken, Cancelled};
 pub use crate::state::client_addr::client_addr;
+pub use crate::state::csp_nonce::CspNonce;
 pub use crate::state::data::StateData;
 pub use crate::state::from_state::FromState;
 pub use crate::state::request_id::request_id;
+pub use crate::state::request_start::RequestStart;
 
-pub(crate) use crate::state::request_id::set_request_id;
+pub(crate) use crate::state::cancellation::{put_cancellation_token, ResponseBody};
+pub(crate) use crate::state::request_id::{
+    replace_generated_request_id, set_request_id, set_request_id_with,
+};
 
 /// Provides storage for request state, and stores one item of each type. The types used for
 /// storage must implement the `gotham::state::StateData` trait to allow its storage. The
@@ -4,11 +4,13 @@ use hyper::header::HeaderMap;
 use log::trace;
 use uuid::Uuid;
 
+use crate::middleware::request_id::RequestIdGenerator;
 use crate::state::{FromState, State};
 
 /// A container type for the value returned by `request_id`.
 pub(super) struct RequestId {
     val: String,
+    generated: bool,
 }
 
 /// Sets a unique identifier for the request if it has not already been stored.
@@ -21,6 +23,15 @@ pub(super) struct RequestId {
 /// This function is invoked by `GothamService` before handing control to its `Router`, to ensure
 /// that a value for `RequestId` is always available.
 pub(crate) fn set_request_id<'a>(state: &'a mut State) -> &'a str {
+    set_request_id_with(state, None)
+}
+
+/// Same as `set_request_id`, but creates the value with `generator` when one is provided, rather
+/// than as a UUID v4.
+pub(crate) fn set_request_id_with<'a>(
+    state: &'a mut State,
+    generator: Option<&dyn RequestIdGenerator>,
+) -> &'a str {
     if !state.has::<RequestId>() {
         let request_id = match HeaderMap::borrow_from(state).get("X-Request-ID") {
             Some(ex_req_id) => {
@@ -29,12 +40,21 @@ pub(crate) fn set_request_id<'a>(state: &'a mut State) -> &'a str {
                     "[{}] RequestId set from external source via X-Request-ID header",
                     id
                 );
-                RequestId { val: id }
+                RequestId {
+                    val: id,
+                    generated: false,
+                }
             }
             None => {
-                let val = Uuid::new_v4().to_hyphenated().to_string();
+                let val = match generator {
+                    Some(generator) => generator.generate(),
+                    None => Uuid::new_v4().to_hyphenated().to_string(),
+                };
                 trace!("[{}] RequestId generated internally", val);
-                RequestId { val }
+                RequestId {
+                    val,
+                    generated: true,
+                }
             }
         };
         state.put(request_id);
@@ -43,6 +63,22 @@ pub(crate) fn set_request_id<'a>(state: &'a mut State) -> &'a str {
     request_id(state)
 }
 
+/// Replaces a request ID which was generated internally by `set_request_id` with `val`. A request
+/// ID provided via the `X-Request-ID` header, or which has already been replaced, is retained.
+pub(crate) fn replace_generated_request_id(state: &mut State, val: String) {
+    if let Some(request_id) = RequestId::try_borrow_from(state) {
+        if !request_id.generated {
+            return;
+        }
+        trace!("[{}] RequestId replaced with {}", request_id.val, val);
+    }
+
+    state.put(RequestId {
+        val,
+        generated: false,
+    });
+}
+
 /// Returns the request ID associated with the current request.
 ///
 /// This is typically used for logging and correlating events that occurred within a request.
@@ -101,11 +137,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn uses_the_provided_generator() {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+
+        let generator = || "generated".to_owned();
+        assert_eq!(
+            "generated",
+            set_request_id_with(&mut state, Some(&generator))
+        );
+    }
+
+    #[test]
+    fn replaces_a_generated_request_id_once() {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+
+        replace_generated_request_id(&mut state, "first".to_owned());
+        assert_eq!("first", request_id(&state));
+
+        replace_generated_request_id(&mut state, "second".to_owned());
+        assert_eq!("first", request_id(&state));
+    }
+
     #[test]
     fn does_not_overwrite_existant_request_id() {
         let mut state = State::new();
         state.put(RequestId {
             val: "1-2-3-4".to_string(),
+            generated: false,
         });
 
         {
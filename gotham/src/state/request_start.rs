@@ -0,0 +1,69 @@
+//! Defines storage for the time at which a request was received
+
+use std::time::{Duration, Instant};
+
+use crate::state::{FromState, State, StateData};
+
+/// The instant at which a request was received by Gotham, stored in `State` before the request is
+/// dispatched to the `Handler`.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::create_empty_response;
+/// # use gotham::state::{FromState, RequestStart, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn my_handler(state: State) -> (State, Response<Body>) {
+///     let elapsed = RequestStart::borrow_from(&state).elapsed();
+///     assert!(elapsed.as_secs() < 60);
+///
+///     let response = create_empty_response(&state, StatusCode::OK);
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(my_handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/")
+/// #       .perform()
+/// #       .unwrap();
+/// #
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct RequestStart {
+    instant: Instant,
+}
+
+impl StateData for RequestStart {}
+
+impl RequestStart {
+    /// The instant at which the request was received.
+    pub fn instant(&self) -> Instant {
+        self.instant
+    }
+
+    /// The time elapsed since the request was received.
+    pub fn elapsed(&self) -> Duration {
+        self.instant.elapsed()
+    }
+}
+
+pub(crate) fn put_request_start(state: &mut State) {
+    state.put(RequestStart {
+        instant: Instant::now(),
+    })
+}
+
+/// Returns the time elapsed since the request was received, if a `RequestStart` is present in
+/// `State`.
+pub(crate) fn elapsed(state: &State) -> Option<Duration> {
+    RequestStart::try_borrow_from(state).map(RequestStart::elapsed)
+}
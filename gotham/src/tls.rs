@@ -4,14 +4,19 @@ use std::net::ToSocketAddrs;
 use std::sync::Arc;
 
 use tokio::net::TcpListener;
+use tokio_rustls::rustls::Session;
 use tokio_rustls::{rustls, TlsAcceptor};
 
-use super::{bind_server, new_runtime, tcp_listener};
+use super::server::{bind_server_with, ServerConfig};
+use super::{new_runtime, tcp_listener};
 
 use super::handler::NewHandler;
 
+pub(crate) mod client_certificate;
 pub mod test;
 
+pub use self::client_certificate::ClientCertificate;
+
 /// Starts a Gotham application with the default number of threads.
 pub fn start<NH, A>(addr: A, new_handler: NH, tls_config: rustls::ServerConfig)
 where
@@ -72,10 +77,27 @@ where
     NH: NewHandler + 'static,
 {
     let tls = TlsAcceptor::from(Arc::new(tls_config));
-    bind_server(listener, new_handler, move |socket| {
-        tls.accept(socket).map_err(|e| {
-            error!(target: "gotham::tls", "TLS handshake error: {:?}", e);
-        })
-    })
+    bind_server_with(
+        listener,
+        new_handler,
+        move |socket| {
+            tls.accept(socket).map_err(|e| {
+                error!(target: "gotham::tls", "TLS handshake error: {:?}", e);
+            })
+        },
+        |stream, service| {
+            let (_, session) = stream.get_ref();
+            let cert = session
+                .get_peer_certificates()
+                .and_then(|certs| certs.into_iter().next())
+                .and_then(|cert| ClientCertificate::from_der(cert.0));
+
+            match cert {
+                Some(cert) => service.with_client_certificate(cert),
+                None => service,
+            }
+        },
+        ServerConfig::default(),
+    )
     .await
 }
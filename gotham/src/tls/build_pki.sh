@@ -3,9 +3,15 @@
 openssl req -x509 -newkey rsa -config ca.cfg -days 3650 -out ca_cert.pem
 openssl req -newkey rsa -config srv.cfg -days 3650 |
   openssl x509 -days 3650 -req -CA ca_cert.pem -CAkey ca_key.pem -extfile srv.cfg -extensions v3_server -set_serial 1 -out cert.pem
-rm ca_key.pem
+openssl req -x509 -newkey rsa -config ca.cfg -keyout client_ca_key.pem -days 3650 -out client_ca_cert.pem
+openssl req -newkey rsa -config client.cfg -days 3650 |
+  openssl x509 -days 3650 -req -CA client_ca_cert.pem -CAkey client_ca_key.pem -extfile client.cfg -extensions v3_client -set_serial 1 -out client_cert.pem
+rm ca_key.pem client_ca_key.pem
 echo "CA certificate:"
 openssl x509 -noout -text < ca_cert.pem
 echo
 echo "Server certificate:"
 openssl x509 -noout -text < cert.pem
+echo
+echo "Client certificate:"
+openssl x509 -noout -text < client_cert.pem
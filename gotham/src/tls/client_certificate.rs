@@ -0,0 +1,120 @@
+//! Defines storage for the certificate presented by a TLS client
+
+use x509_parser::parse_x509_certificate;
+
+use crate::state::{State, StateData};
+
+/// The certificate presented by the client when the connection was established, where the server
+/// has been configured to request client certificates (e.g. via
+/// `rustls::AllowAnyAuthenticatedClient`).
+///
+/// `ClientCertificate` is only present in `State` when the client presented a certificate, which
+/// has been verified by the `rustls::ServerConfig` in use. Handlers can use it to authorize
+/// requests based on the identity of the client.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gotham;
+/// # extern crate hyper;
+/// #
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::helpers::http::response::create_empty_response;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::tls::ClientCertificate;
+/// #
+/// fn my_handler(state: State) -> (State, Response<Body>) {
+///     let status = match ClientCertificate::try_borrow_from(&state) {
+///         Some(cert) if cert.common_name() == Some("gotham-client") => StatusCode::OK,
+///         _ => StatusCode::FORBIDDEN,
+///     };
+///
+///     let response = create_empty_response(&state, status);
+///     (state, response)
+/// }
+/// #
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientCertificate {
+    der: Vec<u8>,
+    subject: String,
+    common_name: Option<String>,
+}
+
+impl StateData for ClientCertificate {}
+
+impl ClientCertificate {
+    /// Parses the subject from a DER encoded X.509 certificate, returning `None` if the
+    /// certificate is malformed.
+    pub(crate) fn from_der(der: Vec<u8>) -> Option<ClientCertificate> {
+        let (subject, common_name) = {
+            let (_, cert) = parse_x509_certificate(&der).ok()?;
+            let subject = cert.subject();
+
+            let common_name = match subject.iter_common_name().last() {
+                Some(cn) => Some(cn.as_str().ok()?.to_owned()),
+                None => None,
+            };
+
+            (subject.to_string(), common_name)
+        };
+
+        Some(ClientCertificate {
+            der,
+            subject,
+            common_name,
+        })
+    }
+
+    /// The subject of the certificate, with attributes in the order they appear in the
+    /// certificate, e.g. `C=US, O=Gotham, CN=gotham-client`.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// The common name (`CN`) of the certificate subject, if present.
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_ref().map(String::as_str)
+    }
+
+    /// The DER encoded certificate, for further inspection.
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+}
+
+pub(crate) fn put_client_certificate(state: &mut State, cert: ClientCertificate) {
+    state.put(cert)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::BufReader;
+
+    use tokio_rustls::rustls::internal::pemfile::certs;
+
+    #[test]
+    fn parses_certificate_subject() {
+        let mut cert_file = BufReader::new(&include_bytes!("client_cert.pem")[..]);
+        let der = certs(&mut cert_file).unwrap().remove(0).0;
+
+        let cert = ClientCertificate::from_der(der.clone()).unwrap();
+
+        assert_eq!(
+            cert.subject(),
+            "C=US, ST=New York, L=Gotham, O=Gotham, OU=WWW-testing, CN=gotham-client"
+        );
+        assert_eq!(cert.common_name(), Some("gotham-client"));
+        assert_eq!(cert.der(), &der[..]);
+    }
+
+    #[test]
+    fn rejects_malformed_certificates() {
+        assert!(ClientCertificate::from_der(vec![]).is_none());
+        assert!(ClientCertificate::from_der(vec![0x30, 0x82, 0x01]).is_none());
+        assert!(ClientCertificate::from_der(vec![0x30, 0x03, 0x02, 0x01, 0x01]).is_none());
+    }
+}
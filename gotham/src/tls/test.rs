@@ -30,7 +30,7 @@ use tokio_rustls::{
     rustls::{
         self,
         internal::pemfile::{certs, pkcs8_private_keys},
-        NoClientAuth,
+        AllowAnyAnonymousOrAuthenticatedClient, ClientCertVerifier, NoClientAuth, RootCertStore,
     },
     webpki::DNSNameRef,
     TlsConnector,
@@ -125,6 +125,39 @@ impl TestServer {
         new_handler: NH,
         timeout: u64,
     ) -> Result<TestServer>
+    where
+        NH::Instance: UnwindSafe,
+    {
+        TestServer::build(new_handler, timeout, NoClientAuth::new())
+    }
+
+    /// Creates a `TestServer` which requests a certificate from each client, trusting the
+    /// certificate presented by clients returned from `client_with_certificate`. Clients which
+    /// present no certificate are still accepted.
+    ///
+    /// Timeout will be set to 10 seconds.
+    pub fn with_client_auth<NH: NewHandler + 'static>(new_handler: NH) -> Result<TestServer>
+    where
+        NH::Instance: UnwindSafe,
+    {
+        let mut roots = RootCertStore::empty();
+        let mut ca_file = BufReader::new(&include_bytes!("client_ca_cert.pem")[..]);
+        roots
+            .add_pem_file(&mut ca_file)
+            .map_err(|_| failure::err_msg("invalid client CA certificate"))?;
+
+        TestServer::build(
+            new_handler,
+            10,
+            AllowAnyAnonymousOrAuthenticatedClient::new(roots),
+        )
+    }
+
+    fn build<NH: NewHandler + 'static>(
+        new_handler: NH,
+        timeout: u64,
+        client_cert_verifier: Arc<dyn ClientCertVerifier>,
+    ) -> Result<TestServer>
     where
         NH::Instance: UnwindSafe,
     {
@@ -137,7 +170,7 @@ impl TestServer {
         ))?;
         let addr = listener.local_addr()?;
 
-        let mut cfg = rustls::ServerConfig::new(NoClientAuth::new());
+        let mut cfg = rustls::ServerConfig::new(client_cert_verifier);
         let mut cert_file = BufReader::new(&include_bytes!("cert.pem")[..]);
         let mut key_file = BufReader::new(&include_bytes!("key.pem")[..]);
         let certs = certs(&mut cert_file).unwrap();
@@ -186,13 +219,23 @@ impl TestServer {
         &self,
         client_addr: net::SocketAddr,
     ) -> TestClient<Self, TestConnect> {
-        self.try_client_with_address(client_addr)
+        self.try_client_with_address(client_addr, false)
+            .expect("TestServer: unable to spawn client")
+    }
+
+    /// Returns a client connected to the `TestServer`, which presents a client certificate with
+    /// the subject `CN=gotham-client` during the TLS handshake. The certificate is only trusted by
+    /// a `TestServer` created via `with_client_auth`.
+    pub fn client_with_certificate(&self) -> TestClient<Self, TestConnect> {
+        let client_addr = SocketAddr::new(IpAddr::from([127, 0, 0, 1]), 10000);
+        self.try_client_with_address(client_addr, true)
             .expect("TestServer: unable to spawn client")
     }
 
     fn try_client_with_address(
         &self,
         _client_addr: net::SocketAddr,
+        client_cert: bool,
     ) -> Result<TestClient<Self, TestConnect>> {
         // We're creating a private TCP-based pipe here. Bind to an ephemeral port, connect to
         // it and then immediately discard the listener.
@@ -200,6 +243,14 @@ impl TestServer {
         let mut cert_file = BufReader::new(&include_bytes!("ca_cert.pem")[..]);
         config.root_store.add_pem_file(&mut cert_file).unwrap();
 
+        if client_cert {
+            let mut cert_file = BufReader::new(&include_bytes!("client_cert.pem")[..]);
+            let mut key_file = BufReader::new(&include_bytes!("client_key.pem")[..]);
+            let certs = certs(&mut cert_file).unwrap();
+            let mut keys = pkcs8_private_keys(&mut key_file).unwrap();
+            config.set_single_client_cert(certs, keys.remove(0));
+        }
+
         let client = Client::builder().build(TestConnect {
             addr: self.data.addr,
             config: Arc::new(config),
@@ -318,6 +369,7 @@ mod tests {
     use crate::handler::{Handler, HandlerFuture, IntoHandlerError, NewHandler};
     use crate::helpers::http::response::create_response;
     use crate::state::{client_addr, FromState, State};
+    use crate::tls::ClientCertificate;
     use http::header::CONTENT_TYPE;
     use log::info;
 
@@ -350,6 +402,19 @@ mod tests {
                     future::ok((state, response)).boxed()
                 }
 
+                "/mycert" => {
+                    info!("TestHandler responding to /mycert");
+                    let subject = ClientCertificate::try_borrow_from(&state)
+                        .map(|cert| cert.subject().to_owned())
+                        .unwrap_or_else(|| "none".to_owned());
+                    let response = Response::builder()
+                        .status(StatusCode::OK)
+                        .body(subject.into())
+                        .unwrap();
+
+                    future::ok((state, response)).boxed()
+                }
+
                 "/myaddr" => {
                     info!("TestHandler responding to /myaddr");
                     let response = Response::builder()
@@ -397,6 +462,38 @@ mod tests {
         assert_eq!(buf, format!("time: {}", ticks));
     }
 
+    #[test]
+    fn exposes_client_certificate() {
+        let new_service = || {
+            Ok(TestHandler {
+                response: "".to_owned(),
+            })
+        };
+
+        let test_server = TestServer::with_client_auth(new_service).unwrap();
+
+        let response = test_server
+            .client_with_certificate()
+            .get("https://example.com/mycert")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "C=US, ST=New York, L=Gotham, O=Gotham, OU=WWW-testing, CN=gotham-client"
+        );
+
+        let response = test_server
+            .client()
+            .get("https://example.com/mycert")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "none");
+    }
+
     #[test]
     #[ignore] // XXX I don't understand why this doesn't work.
               // It seems like Hyper is treating the future::empty() as an empty body...